@@ -1,22 +1,563 @@
 use colored::Colorize;
 use wasm_bindgen::prelude::*;
-use xterm_js_rs::Terminal;
+
+use crate::expand::quote;
+use crate::interp::{self, Flow};
+use crate::parse::is_name;
+use crate::process::Proc;
+use crate::unix::chmod;
 
 #[wasm_bindgen(raw_module = "../../src/stores")]
 extern "C" {
     fn wasmGetHome() -> i32;
 }
 
-pub fn exit(term: &Terminal, _args: Vec<&str>) -> i32 {
-    crate::kmessage(term, "The system is going down for system halt NOW!");
+// the status for exit and return: N, or that of the last command
+fn status_arg(p: &Proc, name: &str) -> Result<i32, i32> {
+    match p.args().first() {
+        None => Ok(p.sh.vars.status()),
+        Some(n) => match n.parse::<i32>() {
+            Ok(v) => Ok(v & 255),
+            Err(_) => {
+                p.stderr
+                    .writeln(&format!("{}: {}: numeric argument required", name, n));
+                Err(2)
+            }
+        },
+    }
+}
+
+// In a script or a subshell, exit only ends that. At the prompt, it
+// leaves su for whoever was there before, and past that there's
+// nothing to go back to but the home page.
+pub fn exit(p: &Proc) -> i32 {
+    let st = status_arg(p, "exit").unwrap_or_else(|e| e);
+    if p.sh.depth.get() > 0 || interp::in_subshell(p.sh) {
+        interp::set_flow(p.sh, Flow::Exit);
+        return st;
+    }
+    if p.sh.vars.pop_session() {
+        return st;
+    }
+    // on the other consoles, it's only that console's shell that goes
+    if p.sh.n != 1 {
+        p.sh.hang_up();
+        return st;
+    }
+    crate::kmessage(p.stdout, "The system is going down for system halt NOW!");
     wasmGetHome();
-    return 0;
+    return st;
+}
+
+pub fn return_(p: &Proc) -> i32 {
+    if !interp::in_function(p.sh) && p.sh.depth.get() == 0 {
+        p.stderr
+            .writeln("return: can only `return' from a function or sourced script");
+        return 2;
+    }
+    let st = status_arg(p, "return").unwrap_or_else(|e| e);
+    interp::set_flow(p.sh, Flow::Return);
+    st
+}
+
+// break [N] and continue [N]
+fn loop_flow(p: &Proc, name: &str, f: fn(usize) -> Flow) -> i32 {
+    let args = p.args();
+    let n = match args.first() {
+        None => 1,
+        Some(a) => match a.parse::<usize>() {
+            Ok(n) if n > 0 => n,
+            Ok(_) => {
+                p.stderr
+                    .writeln(&format!("{}: {}: loop count out of range", name, a));
+                return 1;
+            }
+            Err(_) => {
+                p.stderr
+                    .writeln(&format!("{}: {}: numeric argument required", name, a));
+                return 2;
+            }
+        },
+    };
+    if interp::loops(p.sh) == 0 {
+        p.stderr.writeln(&format!(
+            "{}: only meaningful in a `for', `while', or `until' loop",
+            name
+        ));
+        return 0;
+    }
+    interp::set_flow(p.sh, f(n.min(interp::loops(p.sh))));
+    0
+}
+
+pub fn break_(p: &Proc) -> i32 {
+    loop_flow(p, "break", Flow::Break)
+}
+
+pub fn continue_(p: &Proc) -> i32 {
+    loop_flow(p, "continue", Flow::Continue)
 }
 
-pub fn nano(term: &Terminal, _args: Vec<&str>) -> i32 {
-    term.writeln(&format!(
+pub fn true_(_p: &Proc) -> i32 {
+    0
+}
+
+pub fn false_(_p: &Proc) -> i32 {
+    1
+}
+
+// local NAME[=VALUE]...
+pub fn local(p: &Proc) -> i32 {
+    let mut status = 0;
+    for a in p.args() {
+        let (k, v) = match a.split_once('=') {
+            Some((k, v)) => (k, Some(v)),
+            None => (a, None),
+        };
+        if !is_name(k) {
+            p.stderr
+                .writeln(&format!("local: `{}': not a valid identifier", a));
+            status = 1;
+            continue;
+        }
+        if !interp::make_local(p.sh, k) {
+            p.stderr.writeln("local: can only be used in a function");
+            return 1;
+        }
+        match v {
+            Some(v) => p.sh.vars.setenv(k, v),
+            None => p.sh.vars.unsetenv(k),
+        }
+    }
+    status
+}
+
+pub fn nano(p: &Proc) -> i32 {
+    p.stdout.writeln(&format!(
         "{}",
         "Vim, Emacs, and ed are free...".bright_red().bold()
     ));
     return 127;
 }
+
+pub fn history(p: &Proc) -> i32 {
+    let args = p.args();
+    match args.as_slice() {
+        [] => print_history(p, usize::MAX),
+        ["-c"] => p.sh.hist.clear(),
+        ["-d", n] => {
+            let ok = match n.parse::<isize>() {
+                Ok(v) => p.sh.hist.delete(v),
+                Err(_) => false,
+            };
+            if !ok {
+                p.stderr
+                    .writeln(&format!("history: {}: history position out of range", n));
+                return 1;
+            }
+        }
+        ["-d"] => {
+            p.stderr.writeln("history: -d: option requires an argument");
+            p.stderr
+                .writeln("history: usage: history [-c] [-d offset] [n]");
+            return 2;
+        }
+        [n] if !n.starts_with('-') => match n.parse::<usize>() {
+            Ok(v) => print_history(p, v),
+            Err(_) => {
+                p.stderr
+                    .writeln(&format!("history: {}: numeric argument required", n));
+                return 1;
+            }
+        },
+        _ => {
+            p.stderr
+                .writeln("history: usage: history [-c] [-d offset] [n]");
+            return 2;
+        }
+    }
+    return 0;
+}
+
+fn print_history(p: &Proc, count: usize) {
+    let ents = p.sh.hist.entries();
+    let skip = ents.len().saturating_sub(count);
+    for (num, cmd) in ents.into_iter().skip(skip) {
+        p.stdout.writeln(&format!("{:>5}  {}", num, cmd));
+    }
+}
+
+pub fn export(p: &Proc) -> i32 {
+    let args = p.args();
+    let mut un = false;
+    let mut names = vec![];
+    for a in args {
+        match a {
+            "-n" => un = true,
+            "-p" => {}
+            _ if a.starts_with('-') => {
+                p.stderr.writeln(&format!("export: {}: invalid option", a));
+                p.stderr
+                    .writeln("export: usage: export [-n] [name[=value] ...] or export -p");
+                return 2;
+            }
+            _ => names.push(a),
+        }
+    }
+    if names.is_empty() {
+        for (k, v) in p.sh.vars.vars() {
+            if v.exported {
+                p.stdout.writeln(&format!("export {}={}", k, quote(&v.val)));
+            }
+        }
+        return 0;
+    }
+    let mut status = 0;
+    for a in names {
+        let (k, v) = match a.split_once('=') {
+            Some((k, v)) => (k, Some(v)),
+            None => (a, None),
+        };
+        if !is_name(k) {
+            p.stderr
+                .writeln(&format!("export: `{}': not a valid identifier", a));
+            status = 1;
+            continue;
+        }
+        if un {
+            if let Some(v) = v {
+                p.sh.vars.setenv(k, v);
+            }
+            p.sh.vars.unexport(k);
+        } else {
+            p.sh.vars.export(k, v);
+        }
+    }
+    status
+}
+
+pub fn unset(p: &Proc) -> i32 {
+    let args = p.args();
+    let mut status = 0;
+    let funcs = args.first() == Some(&"-f");
+    for a in args {
+        if a == "-v" || a == "-f" {
+            continue;
+        }
+        if funcs {
+            interp::unset_function(p.sh, a);
+            continue;
+        }
+        if !is_name(a) {
+            p.stderr
+                .writeln(&format!("unset: `{}': not a valid identifier", a));
+            status = 1;
+            continue;
+        }
+        p.sh.vars.unsetenv(a);
+    }
+    status
+}
+
+// env [NAME=VALUE]... [COMMAND [ARG]...]
+pub async fn env(p: &Proc<'_>) -> i32 {
+    let args = p.args();
+    let mut vals = vec![];
+    let mut rest = args.iter();
+    let mut argv: Vec<String> = vec![];
+    for a in rest.by_ref() {
+        match a.split_once('=') {
+            Some((k, v)) if !k.is_empty() => vals.push((k.to_string(), v.to_string())),
+            _ => {
+                argv.push(a.to_string());
+                break;
+            }
+        }
+    }
+    argv.extend(rest.map(|x| x.to_string()));
+    if !argv.is_empty() {
+        let stdin = p.read_stdin();
+        let run = crate::exec(p.sh, &argv, stdin, p.stdout, p.stderr);
+        return p.sh.vars.scoped_env(&vals, run).await;
+    }
+    let mut env = p.env.clone();
+    env.extend(vals);
+    for (k, v) in env {
+        p.stdout.writeln(&format!("{}={}", k, v));
+    }
+    0
+}
+
+pub fn set(p: &Proc) -> i32 {
+    let args = p.args();
+    if !args.is_empty() {
+        p.stderr.writeln("set: shell options are not supported");
+        p.stderr.writeln("set: usage: set");
+        return 2;
+    }
+    for (k, v) in p.sh.vars.vars() {
+        p.stdout.writeln(&format!("{}={}", k, quote(&v.val)));
+    }
+    return 0;
+}
+
+// what bash won't take in an alias name
+fn is_alias_name(s: &str) -> bool {
+    !s.is_empty() && !s.chars().any(|c| " \t\n/$`='\"\\;&|<>()".contains(c))
+}
+
+pub fn alias(p: &Proc) -> i32 {
+    let args = p.args();
+    let args = match args.first() {
+        Some(&"-p") => &args[1..],
+        _ => &args[..],
+    };
+    if args.is_empty() {
+        for (k, v) in p.sh.vars.aliases() {
+            p.stdout.writeln(&format!("alias {}={}", k, quote(&v)));
+        }
+        return 0;
+    }
+    let mut status = 0;
+    for a in args {
+        match a.split_once('=') {
+            Some((k, v)) if is_alias_name(k) => p.sh.vars.set_alias(k, v),
+            Some(_) => {
+                p.stderr
+                    .writeln(&format!("alias: `{}': invalid alias name", a));
+                status = 1;
+            }
+            None => match p.sh.vars.get_alias(a) {
+                Some(v) => p.stdout.writeln(&format!("alias {}={}", a, quote(&v))),
+                None => {
+                    p.stderr.writeln(&format!("alias: {}: not found", a));
+                    status = 1;
+                }
+            },
+        }
+    }
+    status
+}
+
+pub fn unalias(p: &Proc) -> i32 {
+    let args = p.args();
+    if args.first() == Some(&"-a") {
+        p.sh.vars.clear_aliases();
+        return 0;
+    }
+    if args.is_empty() {
+        p.stderr
+            .writeln("unalias: usage: unalias [-a] name [name ...]");
+        return 2;
+    }
+    let mut status = 0;
+    for a in args {
+        if !p.sh.vars.unalias(a) {
+            p.stderr.writeln(&format!("unalias: {}: not found", a));
+            status = 1;
+        }
+    }
+    status
+}
+
+// umask [-S] [MODE], where MODE is the mask in octal, or what's left
+// unmasked, the way chmod would put it
+pub fn umask(p: &Proc) -> i32 {
+    let args = p.args();
+    let (sym, args) = match args.first() {
+        Some(&"-S") => (true, &args[1..]),
+        _ => (false, &args[..]),
+    };
+    let mask = p.sh.vars.umask();
+    let mode = match args {
+        [] if sym => {
+            p.stdout.writeln(&chmod::symbolic(!mask & 0o777));
+            return 0;
+        }
+        [] => {
+            p.stdout.writeln(&format!("{:04o}", mask));
+            return 0;
+        }
+        [m] => *m,
+        _ => {
+            p.stderr.writeln("umask: usage: umask [-S] [mode]");
+            return 2;
+        }
+    };
+    let new = if mode.chars().all(|c| c.is_ascii_digit()) {
+        match u16::from_str_radix(mode, 8) {
+            Ok(m) if m <= 0o777 => m,
+            _ => {
+                p.stderr
+                    .writeln(&format!("umask: {}: octal number out of range", mode));
+                return 1;
+            }
+        }
+    } else {
+        match chmod::parse_mode(mode, !mask & 0o777, true, 0) {
+            Some(m) => !m & 0o777,
+            None => {
+                p.stderr
+                    .writeln(&format!("umask: `{}': invalid symbolic mode", mode));
+                return 1;
+            }
+        }
+    };
+    p.sh.vars.set_umask(new);
+    0
+}
+
+// sh FILE [ARG]...
+// sh -c COMMANDS [NAME [ARG]...]
+pub async fn sh(p: &Proc<'_>) -> i32 {
+    let args: Vec<String> = p.args.clone();
+    match args.first().map(|x| x.as_str()) {
+        None => {
+            p.stderr
+                .writeln("sh: reading commands from the terminal is not supported");
+            p.stderr
+                .writeln("sh: usage: sh FILE [ARG]... or sh -c COMMANDS");
+            2
+        }
+        Some("-c") => match args.get(1) {
+            Some(cmds) => {
+                let mut pos = args[2..].to_vec();
+                if pos.is_empty() {
+                    pos.push("sh".to_string());
+                }
+                crate::script::run_script(p.sh, p.stdout, p.stderr, cmds, pos).await
+            }
+            None => {
+                p.stderr.writeln("sh: -c: option requires an argument");
+                2
+            }
+        },
+        Some(f) => match crate::vfs::futils::read_file(&p.sh.caller(), f.to_string()) {
+            Ok(t) => {
+                let txt = String::from_utf8_lossy(&t);
+                crate::script::run_script(p.sh, p.stdout, p.stderr, &txt, args).await
+            }
+            Err(e) => {
+                p.stderr.writeln(&format!("sh: {}: {}", f, e.errno()));
+                127
+            }
+        },
+    }
+}
+
+// source FILE [ARG]..., or . FILE [ARG]...
+// Runs FILE in this shell, so it can set variables, aliases and the
+// working directory. The positional parameters only change if
+// ARGs are given.
+pub async fn source(p: &Proc<'_>) -> i32 {
+    let args = p.args();
+    let f = match args.first() {
+        Some(f) => f,
+        None => {
+            p.stderr.writeln("source: filename argument required");
+            p.stderr
+                .writeln("source: usage: source filename [arguments]");
+            return 2;
+        }
+    };
+    let txt = match crate::vfs::futils::read_file(&p.sh.caller(), f.to_string()) {
+        Ok(t) => String::from_utf8_lossy(&t).to_string(),
+        Err(e) => {
+            p.stderr.writeln(&format!("source: {}: {}", f, e.errno()));
+            return 1;
+        }
+    };
+    if args.len() == 1 {
+        return crate::script::run_text(p.sh, p.stdout, p.stderr, &txt).await;
+    }
+    let mut pos = vec![p.sh.vars.positional()[0].clone()];
+    pos.extend(args[1..].iter().map(|x| x.to_string()));
+    let run = crate::script::run_text(p.sh, p.stdout, p.stderr, &txt);
+    p.sh.vars.with_positional(pos, run).await
+}
+
+// read [-r] [-p PROMPT] [NAME]...
+// Reads a line and splits it into words on whitespace: each NAME gets
+// a word, and the last one gets the rest of the line. With no NAMEs,
+// the line goes into REPLY. Without -r, a backslash makes the next
+// character literal and a backslash at the end carries on to the
+// next line.
+pub async fn read(p: &Proc<'_>) -> i32 {
+    let args = p.args();
+    let mut raw = false;
+    let mut names = vec![];
+    let mut it = args.iter();
+    while let Some(a) = it.next() {
+        match *a {
+            "-r" => raw = true,
+            "-p" => match it.next() {
+                Some(pr) => p.stderr.write(pr),
+                None => {
+                    p.stderr.writeln("read: -p: option requires an argument");
+                    return 2;
+                }
+            },
+            _ if a.starts_with('-') && a.len() > 1 => {
+                p.stderr.writeln(&format!("read: {}: invalid option", a));
+                p.stderr
+                    .writeln("read: usage: read [-r] [-p prompt] [name ...]");
+                return 2;
+            }
+            _ => names.push(*a),
+        }
+    }
+    if let Some(n) = names.iter().find(|n| !is_name(n)) {
+        p.stderr
+            .writeln(&format!("read: `{}': not a valid identifier", n));
+        return 1;
+    }
+    // each char, and whether it was escaped
+    let mut line: Vec<(char, bool)> = vec![];
+    let mut got = false;
+    while let Some(l) = p.read_line().await {
+        got = true;
+        let ended = l.ends_with('\n');
+        let mut cs = l.trim_end_matches('\n').chars();
+        let mut more = false;
+        while let Some(c) = cs.next() {
+            if c == '\\' && !raw {
+                match cs.next() {
+                    Some(c) => line.push((c, true)),
+                    None => more = ended,
+                }
+            } else {
+                line.push((c, false));
+            }
+        }
+        if !more {
+            break;
+        }
+    }
+    if p.sh.tty.interrupted() {
+        return 130;
+    }
+    if names.is_empty() {
+        p.sh.vars
+            .setenv("REPLY", &line.iter().map(|x| x.0).collect::<String>());
+        return if got { 0 } else { 1 };
+    }
+    let blank = |x: &(char, bool)| !x.1 && x.0.is_whitespace();
+    let mut rest: &[(char, bool)] = &line;
+    for (i, n) in names.iter().enumerate() {
+        let start = rest.iter().position(|x| !blank(x)).unwrap_or(rest.len());
+        rest = &rest[start..];
+        let end = if i == names.len() - 1 {
+            rest.iter().rposition(|x| !blank(x)).map_or(0, |e| e + 1)
+        } else {
+            rest.iter().position(blank).unwrap_or(rest.len())
+        };
+        p.sh.vars
+            .setenv(n, &rest[..end].iter().map(|x| x.0).collect::<String>());
+        rest = &rest[end..];
+    }
+    if got {
+        0
+    } else {
+        1
+    }
+}
@@ -1,9 +1,3 @@
-use xterm_js_rs::Terminal;
-
-pub fn minfo(term: &Terminal, pname: &str) {
-    term.writeln(&format!("Try '{} --help' for more information.", pname));
-}
-
 pub const fn fastceildiv(a: u64, b: u64) -> u64 {
     (a + b - 1) / b
 }
@@ -26,3 +20,333 @@ pub fn shift_in_text(s: &str, c: usize) -> String {
     st.push_str(s);
     st
 }
+
+// A getopt for the tools, in the GNU style: short options that can be
+// bunched up (-la), long ones that can be cut short while they're
+// still unambiguous (--alm), values given either way (-m 755, -m755,
+// --mode 755, --mode=755), options and operands in any order, and --
+// to end the options. --help and --version come with every Spec.
+
+// One option. Make them with flag, short or long, then value if it
+// takes one.
+pub struct Opt {
+    pub short: Option<char>,
+    pub long: Option<&'static str>,
+    // what --help calls its value, if it takes one
+    pub value: Option<&'static str>,
+    pub help: &'static str,
+}
+
+impl Opt {
+    pub const fn flag(short: char, long: &'static str, help: &'static str) -> Self {
+        Self {
+            short: Some(short),
+            long: Some(long),
+            value: None,
+            help,
+        }
+    }
+    pub const fn short(short: char, help: &'static str) -> Self {
+        Self {
+            short: Some(short),
+            long: None,
+            value: None,
+            help,
+        }
+    }
+    pub const fn long(long: &'static str, help: &'static str) -> Self {
+        Self {
+            short: None,
+            long: Some(long),
+            value: None,
+            help,
+        }
+    }
+    pub const fn value(self, name: &'static str) -> Self {
+        Self {
+            value: Some(name),
+            ..self
+        }
+    }
+    // whether this is the option called key, by either name
+    fn is(&self, key: &str) -> bool {
+        self.long == Some(key) || (key.chars().count() == 1 && self.short == key.chars().next())
+    }
+}
+
+// What a tool takes, and what its --help and --version say.
+pub struct Spec {
+    pub name: &'static str,
+    // the Usage: line(s) and what the tool does, before the options
+    pub usage: &'static str,
+    pub opts: &'static [Opt],
+    // anything for after the options, or ""
+    pub footer: &'static str,
+    pub version: Option<&'static str>,
+}
+
+pub enum Getopt<'a> {
+    Run(Parsed<'a>),
+    Help(String),
+    Version(&'static str),
+    // a GNU-style complaint, without the "Try --help" line
+    Error(String),
+}
+
+// the options given, in order, and the operands
+pub struct Parsed<'a> {
+    pub opts: Vec<(&'a Opt, Option<&'a str>)>,
+    pub operands: Vec<&'a str>,
+}
+
+impl<'a> Parsed<'a> {
+    pub fn has(&self, key: &str) -> bool {
+        self.opts.iter().any(|(o, _)| o.is(key))
+    }
+    // the value given to an option; the last one, if there were more
+    pub fn value(&self, key: &str) -> Option<&'a str> {
+        self.opts
+            .iter()
+            .rev()
+            .find(|(o, _)| o.is(key))
+            .and_then(|(_, v)| *v)
+    }
+}
+
+enum Found<'a> {
+    Opt(&'a Opt),
+    Help,
+    Version,
+}
+
+impl Spec {
+    // the long option called name, or the only one it's the start of
+    fn long(&self, name: &str, arg: &str) -> Result<Found<'_>, String> {
+        let mut names: Vec<(&str, Found)> = self
+            .opts
+            .iter()
+            .filter_map(|o| Some((o.long?, Found::Opt(o))))
+            .collect();
+        names.push(("help", Found::Help));
+        if self.version.is_some() {
+            names.push(("version", Found::Version));
+        }
+        if let Some(i) = names.iter().position(|(n, _)| *n == name) {
+            return Ok(names.swap_remove(i).1);
+        }
+        let mut prefixed: Vec<(&str, Found)> = names
+            .into_iter()
+            .filter(|(n, _)| !name.is_empty() && n.starts_with(name))
+            .collect();
+        match prefixed.len() {
+            0 => Err(format!("unrecognized option '{}'", arg)),
+            1 => Ok(prefixed.remove(0).1),
+            _ => Err(format!(
+                "option '{}' is ambiguous; possibilities:{}",
+                arg,
+                prefixed
+                    .iter()
+                    .map(|(n, _)| format!(" '--{}'", n))
+                    .collect::<String>()
+            )),
+        }
+    }
+
+    pub fn parse<'a>(&'a self, args: &[&'a str]) -> Getopt<'a> {
+        let err = |e: String| Getopt::Error(format!("{}: {}", self.name, e));
+        let mut out = Parsed {
+            opts: vec![],
+            operands: vec![],
+        };
+        let mut it = args.iter().copied();
+        while let Some(a) = it.next() {
+            if a == "--" {
+                out.operands.extend(it);
+                break;
+            }
+            if let Some(l) = a.strip_prefix("--") {
+                let (name, val) = match l.split_once('=') {
+                    Some((n, v)) => (n, Some(v)),
+                    None => (l, None),
+                };
+                let o = match self.long(name, a) {
+                    Ok(Found::Opt(o)) => o,
+                    Ok(Found::Help) => return Getopt::Help(self.help()),
+                    Ok(Found::Version) => return Getopt::Version(self.version.unwrap()),
+                    Err(e) => return err(e),
+                };
+                let long = o.long.unwrap();
+                match (o.value, val) {
+                    (Some(_), Some(v)) => out.opts.push((o, Some(v))),
+                    (Some(_), None) => match it.next() {
+                        Some(v) => out.opts.push((o, Some(v))),
+                        None => return err(format!("option '--{}' requires an argument", long)),
+                    },
+                    (None, Some(_)) => {
+                        return err(format!("option '--{}' doesn't allow an argument", long))
+                    }
+                    (None, None) => out.opts.push((o, None)),
+                }
+            } else if a.len() > 1 && a.starts_with('-') {
+                for (i, c) in a.char_indices().skip(1) {
+                    let o = match self.opts.iter().find(|o| o.short == Some(c)) {
+                        Some(o) => o,
+                        None => return err(format!("invalid option -- '{}'", c)),
+                    };
+                    if o.value.is_none() {
+                        out.opts.push((o, None));
+                        continue;
+                    }
+                    // the rest of it is the value, or else the next one is
+                    let rest = &a[i + c.len_utf8()..];
+                    let v = match rest {
+                        "" => match it.next() {
+                            Some(v) => v,
+                            None => return err(format!("option requires an argument -- '{}'", c)),
+                        },
+                        r => r,
+                    };
+                    out.opts.push((o, Some(v)));
+                    break;
+                }
+            } else {
+                out.operands.push(a);
+            }
+        }
+        Getopt::Run(out)
+    }
+
+    // --help: the usage, then a line or so for each option, lined up
+    pub fn help(&self) -> String {
+        let mut rows: Vec<(String, &str)> = self
+            .opts
+            .iter()
+            .map(|o| {
+                let mut l = match (o.short, o.long) {
+                    (Some(s), Some(l)) => format!("  -{}, --{}", s, l),
+                    (Some(s), None) => format!("  -{}", s),
+                    (None, Some(l)) => format!("      --{}", l),
+                    (None, None) => String::new(),
+                };
+                if let Some(v) = o.value {
+                    l.push(if o.long.is_some() { '=' } else { ' ' });
+                    l.push_str(v);
+                }
+                (l, o.help)
+            })
+            .collect();
+        rows.push(("      --help".to_string(), "display this help and exit"));
+        if self.version.is_some() {
+            rows.push((
+                "      --version".to_string(),
+                "output version information and exit",
+            ));
+        }
+        let col = rows.iter().map(|(l, _)| l.len()).max().unwrap_or(0).min(28) + 2;
+        let mut s = format!("{}\n\n", self.usage);
+        for (l, h) in rows {
+            let mut lines = h.lines();
+            s.push_str(&format!(
+                "{:<w$}{}\n",
+                l,
+                lines.next().unwrap_or(""),
+                w = col
+            ));
+            for more in lines {
+                s.push_str(&format!("{:<w$}{}\n", "", more, w = col));
+            }
+        }
+        if !self.footer.is_empty() {
+            s.push('\n');
+            s.push_str(self.footer);
+            s.push('\n');
+        }
+        s.pop();
+        s
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SPEC: Spec = Spec {
+        name: "t",
+        usage: "Usage: t [OPTION]... FILE...",
+        opts: &[
+            Opt::flag('a', "all", "everything"),
+            Opt::flag('m', "mode", "set the mode\nto MODE").value("MODE"),
+            Opt::short('x', "x marks it"),
+            Opt::long("allow", "let it"),
+        ],
+        footer: "That's all.",
+        version: Some("t 1.0"),
+    };
+
+    fn run<'a>(args: &[&'a str]) -> Result<(Vec<String>, Vec<&'a str>), String> {
+        match SPEC.parse(args) {
+            Getopt::Run(p) => Ok((
+                p.opts
+                    .iter()
+                    .map(|(o, v)| format!("{}{}", o.short.unwrap_or('-'), v.unwrap_or("")))
+                    .collect(),
+                p.operands,
+            )),
+            Getopt::Help(_) => Err("help".to_string()),
+            Getopt::Version(v) => Err(v.to_string()),
+            Getopt::Error(e) => Err(e),
+        }
+    }
+
+    #[test]
+    fn getopt() {
+        assert_eq!(
+            run(&["f", "-xa", "-m755", "g", "--mode", "644", "-", "--", "-a"]),
+            Ok((
+                vec!["x".into(), "a".into(), "m755".into(), "m644".into()],
+                vec!["f", "g", "-", "-a"]
+            ))
+        );
+        assert_eq!(
+            run(&["--mo=1", "-m", "2", "--allow"]).unwrap().0,
+            ["m1", "m2", "-"]
+        );
+        let p = match SPEC.parse(&["-m1", "--mode=2"]) {
+            Getopt::Run(p) => p,
+            _ => panic!(),
+        };
+        assert_eq!(
+            (p.has("mode"), p.value("m"), p.has("a")),
+            (true, Some("2"), false)
+        );
+        assert_eq!(run(&["--he"]), Err("help".into()));
+        assert_eq!(run(&["f", "--version"]), Err("t 1.0".into()));
+        for (args, err) in [
+            (&["-ay"][..], "t: invalid option -- 'y'"),
+            (&["--nope"], "t: unrecognized option '--nope'"),
+            (&["-m"], "t: option requires an argument -- 'm'"),
+            (&["--mode"], "t: option '--mode' requires an argument"),
+            (&["--all=1"], "t: option '--all' doesn't allow an argument"),
+            (
+                &["--al"],
+                "t: option '--al' is ambiguous; possibilities: '--all' '--allow'",
+            ),
+        ] {
+            assert_eq!(run(args), Err(err.to_string()));
+        }
+        assert_eq!(
+            SPEC.help(),
+            "Usage: t [OPTION]... FILE...
+
+  -a, --all        everything
+  -m, --mode=MODE  set the mode
+                   to MODE
+  -x               x marks it
+      --allow      let it
+      --help       display this help and exit
+      --version    output version information and exit
+
+That's all."
+        );
+    }
+}
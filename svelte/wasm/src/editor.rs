@@ -0,0 +1,477 @@
+// The irun line editor.
+// This knows nothing about xterm: it is fed abstract keys and answers
+// with what should happen on screen, as escape sequences to write.
+// lib.rs is the adapter that turns DOM key events into Keys and carries
+// out the Ops, which means all of the editing (and the history walking
+// in particular) can be tested natively with `cargo test`.
+
+use crate::linebuf::{self, LineBuffer};
+
+#[derive(Clone)]
+pub enum Key {
+    Enter,
+    Backspace,
+    Left,
+    Right,
+    Up,
+    Down,
+    // Ctrl-C
+    Interrupt,
+    // Ctrl-L
+    ClearScreen,
+    // printable text to insert at the cursor, never containing newlines
+    Text(String),
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Op {
+    Write(String),
+    Bell,
+    ClearScreen,
+    // The line is done and should be run. Until prompt() is called
+    // again the editor ignores everything it is fed.
+    Submit(String),
+    // Like Submit, but the line was thrown away (Ctrl-C)
+    Cancel,
+}
+
+// how the editor sees the command history: oldest entry at index 0
+pub trait HistoryView {
+    fn len(&self) -> usize;
+    fn get(&self, idx: usize) -> Option<String>;
+}
+impl HistoryView for Vec<String> {
+    fn len(&self) -> usize {
+        Vec::len(self)
+    }
+    fn get(&self, idx: usize) -> Option<String> {
+        self.as_slice().get(idx).cloned()
+    }
+}
+
+pub struct LineEditor {
+    lb: LineBuffer,
+    prompt: String,
+    pw: usize,
+    cols: usize,
+    // index of the history entry being shown, if walking the history
+    chp: Option<usize>,
+    // the line being edited before history navigation started
+    saved: String,
+    done: bool,
+}
+
+impl LineEditor {
+    pub fn new(cols: usize) -> Self {
+        Self {
+            lb: LineBuffer::new(),
+            prompt: String::new(),
+            pw: 0,
+            cols: std::cmp::max(cols, 1),
+            chp: None,
+            saved: String::new(),
+            done: true,
+        }
+    }
+    pub fn set_cols(&mut self, cols: usize) {
+        self.cols = std::cmp::max(cols, 1);
+    }
+    // Starts editing a fresh line; returns what to write to show the prompt.
+    // Presumes the terminal cursor is at the start of a line.
+    pub fn prompt(&mut self, prompt: &str) -> String {
+        self.prompt = prompt.to_string();
+        // only the prompt's last line is in front of the text
+        self.pw = linebuf::str_width(prompt.rsplit('\n').next().unwrap());
+        self.lb.clear();
+        self.chp = None;
+        self.saved.clear();
+        self.done = false;
+        let mut s = prompt.to_string();
+        if self.pw != 0 && self.pw.is_multiple_of(self.cols) {
+            s.push_str("\r\n");
+        }
+        s
+    }
+    // column of the terminal cursor, from the start of the prompt
+    fn cur(&self) -> usize {
+        self.pw + self.lb.cursor_width()
+    }
+    fn to_end(&self) -> String {
+        linebuf::move_cursor(self.cur(), self.pw + self.lb.width(), self.cols)
+    }
+    fn show(&mut self, line: &str, cur: usize) -> Op {
+        self.lb.set(line);
+        Op::Write(self.lb.render_tail(0, cur, self.pw, self.cols))
+    }
+    pub fn feed(&mut self, key: Key, hist: &dyn HistoryView) -> Vec<Op> {
+        if self.done {
+            return vec![];
+        }
+        let cur = self.cur();
+        match key {
+            Key::Enter => {
+                self.done = true;
+                vec![
+                    Op::Write(format!("{}\r\n", self.to_end())),
+                    Op::Submit(self.lb.text()),
+                ]
+            }
+            Key::Interrupt => {
+                self.done = true;
+                vec![Op::Write(format!("{}^C\r\n", self.to_end())), Op::Cancel]
+            }
+            Key::ClearScreen => vec![Op::ClearScreen],
+            Key::Backspace => {
+                if !self.lb.backspace() {
+                    return vec![Op::Bell];
+                }
+                vec![Op::Write(self.lb.render_tail(
+                    self.lb.cursor(),
+                    cur,
+                    self.pw,
+                    self.cols,
+                ))]
+            }
+            Key::Left | Key::Right => {
+                let moved = match key {
+                    Key::Left => self.lb.left(),
+                    _ => self.lb.right(),
+                };
+                if !moved {
+                    return vec![Op::Bell];
+                }
+                vec![Op::Write(linebuf::move_cursor(cur, self.cur(), self.cols))]
+            }
+            Key::Up => {
+                let n = match self.chp {
+                    None if hist.len() == 0 => return vec![Op::Bell],
+                    None => {
+                        self.saved = self.lb.text();
+                        hist.len() - 1
+                    }
+                    Some(0) => return vec![Op::Bell],
+                    Some(n) => n - 1,
+                };
+                self.chp = Some(n);
+                let line = hist.get(n).unwrap_or_default();
+                vec![self.show(&line, cur)]
+            }
+            Key::Down => {
+                let line = match self.chp {
+                    None => return vec![Op::Bell],
+                    Some(n) if n + 1 >= hist.len() => {
+                        // walked off the end, give back what was being typed
+                        self.chp = None;
+                        self.saved.clone()
+                    }
+                    Some(n) => {
+                        self.chp = Some(n + 1);
+                        hist.get(n + 1).unwrap_or_default()
+                    }
+                };
+                vec![self.show(&line, cur)]
+            }
+            Key::Text(t) => {
+                let from = self.lb.cursor();
+                self.lb.insert(&t);
+                // a combining mark can pull the cursor back into
+                // the previous grapheme, so redraw from there
+                let from = std::cmp::min(from, self.lb.cursor().saturating_sub(1));
+                vec![Op::Write(
+                    self.lb.render_tail(from, cur, self.pw, self.cols),
+                )]
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use unicode_width::UnicodeWidthChar;
+
+    // Just enough of a VT100 to follow what the editor writes:
+    // printable text, \r, \n, and CSI A/B/C/D/J, with autowrap.
+    // Wide characters take two cells, combining marks none.
+    struct Screen {
+        cols: usize,
+        rows: Vec<Vec<String>>,
+        r: usize,
+        c: usize,
+        // xterm's "pending wrap" after writing the last column
+        pending: bool,
+    }
+    impl Screen {
+        fn new(cols: usize) -> Self {
+            Self {
+                cols,
+                rows: vec![vec![" ".to_string(); cols]],
+                r: 0,
+                c: 0,
+                pending: false,
+            }
+        }
+        fn row(&mut self, r: usize) -> &mut Vec<String> {
+            while self.rows.len() <= r {
+                self.rows.push(vec![" ".to_string(); self.cols]);
+            }
+            &mut self.rows[r]
+        }
+        fn write(&mut self, s: &str) {
+            let cs: Vec<char> = s.chars().collect();
+            let mut i = 0;
+            while i < cs.len() {
+                match cs[i] {
+                    '\r' => {
+                        self.c = 0;
+                        self.pending = false;
+                    }
+                    '\n' => {
+                        self.r += 1;
+                        self.row(self.r);
+                        self.pending = false;
+                    }
+                    '\x07' => {}
+                    '\x1b' => {
+                        let mut j = i + 2;
+                        let mut n = String::new();
+                        while cs[j].is_ascii_digit() || cs[j] == ';' {
+                            n.push(cs[j]);
+                            j += 1;
+                        }
+                        let n: usize = n.parse().unwrap_or(1);
+                        // colors don't cancel a pending wrap
+                        self.pending &= cs[j] == 'm';
+                        match cs[j] {
+                            'A' => self.r -= n,
+                            'B' => self.r += n,
+                            'C' => self.c = std::cmp::min(self.c + n, self.cols - 1),
+                            'D' => self.c = self.c.saturating_sub(n),
+                            'J' => {
+                                let (r, c) = (self.r, self.c);
+                                for x in c..self.cols {
+                                    self.row(r)[x] = " ".to_string();
+                                }
+                                self.rows.truncate(r + 1);
+                            }
+                            // colors
+                            'm' => {}
+                            x => panic!("unexpected CSI {}", x),
+                        }
+                        i = j;
+                    }
+                    ch => {
+                        let w = ch.width().unwrap_or(0);
+                        if w == 0 {
+                            // joins whatever was drawn last
+                            let (r, c) = (self.r, self.c);
+                            let c = if self.pending { c } else { c - 1 };
+                            self.row(r)[c].push(ch);
+                        } else {
+                            if self.pending || self.c + w > self.cols {
+                                self.r += 1;
+                                self.c = 0;
+                                self.pending = false;
+                            }
+                            let (r, c) = (self.r, self.c);
+                            self.row(r)[c] = ch.to_string();
+                            if w == 2 {
+                                self.row(r)[c + 1] = String::new();
+                            }
+                            if self.c + w == self.cols {
+                                self.c = self.cols - 1;
+                                self.pending = true;
+                            } else {
+                                self.c += w;
+                            }
+                        }
+                    }
+                }
+                i += 1;
+            }
+        }
+        fn text(&self) -> String {
+            self.rows
+                .iter()
+                .map(|r| r.concat().trim_end().to_string())
+                .collect::<Vec<String>>()
+                .join("\n")
+        }
+    }
+
+    fn run(ed: &mut LineEditor, scr: &mut Screen, keys: Vec<Key>, h: &Vec<String>) -> Vec<Op> {
+        let mut rest = vec![];
+        for k in keys {
+            for op in ed.feed(k, h) {
+                match op {
+                    Op::Write(s) => scr.write(&s),
+                    o => rest.push(o),
+                }
+            }
+        }
+        rest
+    }
+
+    fn text(s: &str) -> Vec<Key> {
+        s.chars().map(|c| Key::Text(c.to_string())).collect()
+    }
+
+    fn setup(cols: usize) -> (LineEditor, Screen) {
+        let mut ed = LineEditor::new(cols);
+        let mut scr = Screen::new(cols);
+        scr.write(&ed.prompt("$ "));
+        (ed, scr)
+    }
+
+    #[test]
+    fn typing_and_editing() {
+        let (mut ed, mut scr) = setup(80);
+        let h = vec![];
+        run(&mut ed, &mut scr, text("ecoh"), &h);
+        run(
+            &mut ed,
+            &mut scr,
+            vec![Key::Left, Key::Left, Key::Backspace],
+            &h,
+        );
+        run(&mut ed, &mut scr, text("ch"), &h);
+        run(
+            &mut ed,
+            &mut scr,
+            vec![Key::Right, Key::Right, Key::Backspace],
+            &h,
+        );
+        assert_eq!(ed.lb.text(), "echo");
+        assert_eq!(scr.text(), "$ echo");
+        assert_eq!((scr.r, scr.c), (0, 6));
+    }
+
+    #[test]
+    fn bell_at_edges() {
+        let (mut ed, mut scr) = setup(80);
+        let h = vec![];
+        let ops = run(&mut ed, &mut scr, vec![Key::Left, Key::Backspace], &h);
+        assert_eq!(ops, vec![Op::Bell, Op::Bell]);
+        run(&mut ed, &mut scr, text("a"), &h);
+        assert_eq!(run(&mut ed, &mut scr, vec![Key::Right], &h), vec![Op::Bell]);
+    }
+
+    #[test]
+    fn wide_characters() {
+        let (mut ed, mut scr) = setup(80);
+        let h = vec![];
+        run(&mut ed, &mut scr, text("a日本b"), &h);
+        assert_eq!(scr.c, 2 + 6);
+        run(&mut ed, &mut scr, vec![Key::Left, Key::Left], &h);
+        assert_eq!(scr.c, 2 + 3);
+        run(&mut ed, &mut scr, vec![Key::Backspace], &h);
+        assert_eq!(ed.lb.text(), "a本b");
+        assert_eq!(scr.c, 2 + 1);
+    }
+
+    #[test]
+    fn combining_marks_join() {
+        let (mut ed, mut scr) = setup(80);
+        let h = vec![];
+        run(&mut ed, &mut scr, text("e\u{301}"), &h);
+        run(&mut ed, &mut scr, vec![Key::Backspace], &h);
+        assert_eq!(ed.lb.text(), "");
+        assert_eq!(scr.c, 2);
+    }
+
+    #[test]
+    fn wrapping() {
+        let (mut ed, mut scr) = setup(10);
+        let h = vec![];
+        // prompt (2) + 8 fills the first row exactly
+        run(&mut ed, &mut scr, text("abcdefgh"), &h);
+        assert_eq!((scr.r, scr.c), (1, 0));
+        run(&mut ed, &mut scr, text("ij"), &h);
+        assert_eq!(scr.text(), "$ abcdefgh\nij");
+        run(&mut ed, &mut scr, vec![Key::Left, Key::Left, Key::Left], &h);
+        assert_eq!((scr.r, scr.c), (0, 9));
+        run(&mut ed, &mut scr, text("X"), &h);
+        assert_eq!(scr.text(), "$ abcdefgX\nhij");
+        assert_eq!((scr.r, scr.c), (1, 0));
+        run(&mut ed, &mut scr, vec![Key::Backspace, Key::Backspace], &h);
+        assert_eq!(scr.text(), "$ abcdefhi\nj");
+        assert_eq!((scr.r, scr.c), (0, 8));
+    }
+
+    #[test]
+    fn history_walk() {
+        let (mut ed, mut scr) = setup(80);
+        let h = vec!["ls".to_string(), "cat README".to_string()];
+        run(&mut ed, &mut scr, text("pw"), &h);
+        run(&mut ed, &mut scr, vec![Key::Up], &h);
+        assert_eq!(scr.text(), "$ cat README");
+        run(&mut ed, &mut scr, vec![Key::Up], &h);
+        assert_eq!(scr.text(), "$ ls");
+        assert_eq!(run(&mut ed, &mut scr, vec![Key::Up], &h), vec![Op::Bell]);
+        run(&mut ed, &mut scr, vec![Key::Down], &h);
+        assert_eq!(scr.text(), "$ cat README");
+        run(&mut ed, &mut scr, vec![Key::Down], &h);
+        assert_eq!(scr.text(), "$ pw");
+        assert_eq!(ed.lb.text(), "pw");
+        assert_eq!(run(&mut ed, &mut scr, vec![Key::Down], &h), vec![Op::Bell]);
+    }
+
+    #[test]
+    fn history_empty() {
+        let (mut ed, mut scr) = setup(80);
+        let h = vec![];
+        let ops = run(&mut ed, &mut scr, vec![Key::Up, Key::Down], &h);
+        assert_eq!(ops, vec![Op::Bell, Op::Bell]);
+    }
+
+    #[test]
+    fn history_shorter_line_is_erased() {
+        let (mut ed, mut scr) = setup(10);
+        let h = vec!["a".to_string()];
+        run(&mut ed, &mut scr, text("abcdefghijkl"), &h);
+        run(&mut ed, &mut scr, vec![Key::Left; 5], &h);
+        run(&mut ed, &mut scr, vec![Key::Up], &h);
+        assert_eq!(scr.text(), "$ a");
+        assert_eq!((scr.r, scr.c), (0, 3));
+        run(&mut ed, &mut scr, vec![Key::Down], &h);
+        assert_eq!(scr.text(), "$ abcdefgh\nijkl");
+        assert_eq!((scr.r, scr.c), (1, 4));
+    }
+
+    #[test]
+    fn submit_and_cancel() {
+        let (mut ed, mut scr) = setup(10);
+        let h = vec![];
+        run(&mut ed, &mut scr, text("echo hi there"), &h);
+        run(&mut ed, &mut scr, vec![Key::Left; 10], &h);
+        let ops = run(&mut ed, &mut scr, vec![Key::Enter], &h);
+        assert_eq!(ops, vec![Op::Submit("echo hi there".to_string())]);
+        assert_eq!((scr.r, scr.c), (2, 0));
+        // nothing happens until the next prompt
+        assert!(ed.feed(Key::Text("x".to_string()), &h).is_empty());
+        scr.write(&ed.prompt("$ "));
+        run(&mut ed, &mut scr, text("abc"), &h);
+        let ops = run(&mut ed, &mut scr, vec![Key::Interrupt], &h);
+        assert_eq!(ops, vec![Op::Cancel]);
+        assert_eq!(scr.text(), "$ echo hi\nthere\n$ abc^C\n");
+    }
+
+    #[test]
+    fn long_colored_prompt() {
+        let mut ed = LineEditor::new(10);
+        let mut scr = Screen::new(10);
+        let h = vec![];
+        // two lines, the second one wider than the terminal
+        scr.write(&ed.prompt("\x1b[1mhi\x1b[0m\r\n\x1b[32mroot@amyip\x1b[0m:/$ "));
+        assert_eq!((scr.r, scr.c), (2, 4));
+        run(&mut ed, &mut scr, text("echo hi"), &h);
+        assert_eq!((scr.r, scr.c), (3, 1));
+        run(&mut ed, &mut scr, vec![Key::Left; 7], &h);
+        assert_eq!((scr.r, scr.c), (2, 4));
+        run(&mut ed, &mut scr, vec![Key::Backspace, Key::Right], &h);
+        assert_eq!((scr.r, scr.c), (2, 5));
+        run(&mut ed, &mut scr, vec![Key::Interrupt], &h);
+        assert_eq!(scr.text(), "hi\nroot@amyip\n:/$ echo h\ni^C\n");
+    }
+}
@@ -0,0 +1,470 @@
+// Word expansion for irun.
+// Turns parsed words into the strings commands actually get:
+// {a,b}, $VAR, ${VAR}, ${VAR:-default}, ${VAR-default}, $?, $$,
+// the positional parameters ($0..$9, ${10}, $#, $@, $*) and globs
+// (see glob.rs).
+// Single-quoted text is left alone, double-quoted text is expanded
+// but stays one field, and whatever an unquoted expansion produces
+// is split on whitespace like an IFS-default bash would.
+
+use crate::glob;
+use crate::parse::{is_name, Part, Word};
+use crate::session::Session;
+
+// irun is the only process there is
+pub const IRUN_PID: u32 = 1;
+
+fn param(sh: &Session, name: &str) -> Option<String> {
+    match name {
+        "?" => Some(sh.vars.status().to_string()),
+        "$" => Some(IRUN_PID.to_string()),
+        "#" => Some((sh.vars.positional().len() - 1).to_string()),
+        "@" | "*" => Some(sh.vars.positional()[1..].join(" ")),
+        _ if name.chars().all(|c| c.is_ascii_digit()) => sh
+            .vars
+            .positional()
+            .get(name.parse::<usize>().ok()?)
+            .cloned(),
+        _ => sh.vars.getenv(name),
+    }
+}
+
+// the one-character parameters, $? and friends
+fn is_special(c: char) -> bool {
+    matches!(c, '?' | '$' | '#' | '@' | '*') || c.is_ascii_digit()
+}
+
+fn is_name_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_'
+}
+
+// the inside of ${...}
+fn braced(sh: &Session, inner: &str) -> Result<String, String> {
+    let bad = || format!("${{{}}}: bad substitution", inner);
+    let nl = match inner.chars().next() {
+        Some(c) if is_special(c) && !c.is_ascii_digit() => 1,
+        _ => inner.chars().take_while(|&c| is_name_char(c)).count(),
+    };
+    let (name, op) = inner.split_at(nl);
+    // ${10} is fine, ${1x} isn't
+    let digits = name.chars().all(|c| c.is_ascii_digit());
+    if nl == 0 || (nl > 1 && !is_name(name) && !digits) {
+        return Err(bad());
+    }
+    let v = param(sh, name);
+    if op.is_empty() {
+        return Ok(v.unwrap_or_default());
+    }
+    let (d, unset) = if let Some(d) = op.strip_prefix(":-") {
+        (d, v.as_deref().unwrap_or("").is_empty())
+    } else if let Some(d) = op.strip_prefix('-') {
+        (d, v.is_none())
+    } else {
+        return Err(bad());
+    };
+    if unset {
+        expand_text(sh, d)
+    } else {
+        Ok(v.unwrap())
+    }
+}
+
+// Walks `s` and hands each piece to `emit`, along with
+// whether it came out of an expansion (and so may be split).
+fn subst(sh: &Session, s: &str, emit: &mut dyn FnMut(&str, bool)) -> Result<(), String> {
+    let c: Vec<char> = s.chars().collect();
+    let mut lit = String::new();
+    let mut i = 0;
+    while i < c.len() {
+        let next = c.get(i + 1).copied();
+        if c[i] != '$' || next.is_none() {
+            lit.push(c[i]);
+            i += 1;
+            continue;
+        }
+        let val = match next.unwrap() {
+            '{' => {
+                let end = match c[i..].iter().position(|&x| x == '}') {
+                    Some(e) => i + e,
+                    None => return Err(format!("{}: bad substitution", s)),
+                };
+                let inner: String = c[i + 2..end].iter().collect();
+                i = end + 1;
+                braced(sh, &inner)?
+            }
+            // $10 is ${1}0, like in bash
+            n if is_special(n) => {
+                i += 2;
+                param(sh, &n.to_string()).unwrap_or_default()
+            }
+            n if n.is_ascii_alphabetic() || n == '_' => {
+                let mut j = i + 1;
+                while j < c.len() && is_name_char(c[j]) {
+                    j += 1;
+                }
+                let name: String = c[i + 1..j].iter().collect();
+                i = j;
+                param(sh, &name).unwrap_or_default()
+            }
+            // a lone $ is just a dollar sign
+            _ => {
+                lit.push('$');
+                i += 1;
+                continue;
+            }
+        };
+        emit(&lit, false);
+        lit.clear();
+        emit(&val, true);
+    }
+    emit(&lit, false);
+    Ok(())
+}
+
+// expands everything in s, without any splitting
+pub fn expand_text(sh: &Session, s: &str) -> Result<String, String> {
+    let mut out = String::new();
+    subst(sh, s, &mut |t, _| out.push_str(t))?;
+    Ok(out)
+}
+
+// expands a word into one string, as for the value of an assignment
+pub fn expand_str(sh: &Session, w: &Word) -> Result<String, String> {
+    let mut out = String::new();
+    for p in &w.parts {
+        match p {
+            Part::Lit(s) => out.push_str(s),
+            Part::Bare(s) | Part::Quoted(s) => out.push_str(&expand_text(sh, s)?),
+        }
+    }
+    Ok(out)
+}
+
+// A field as it comes out of expansion. `pat` is the same text as a
+// glob pattern, with whatever was quoted escaped.
+#[derive(Default)]
+struct Field {
+    text: String,
+    pat: String,
+    magic: bool,
+}
+
+fn push(cur: &mut Option<Field>, s: &str, active: bool) {
+    let f = cur.get_or_insert_with(Field::default);
+    f.text.push_str(s);
+    for c in s.chars() {
+        if glob::is_special(c) {
+            if active && c != '\\' {
+                f.magic = true;
+            } else {
+                f.pat.push('\\');
+            }
+        }
+        f.pat.push(c);
+    }
+}
+
+fn fields(sh: &Session, w: &Word) -> Result<Vec<Field>, String> {
+    let mut fields = vec![];
+    let mut cur: Option<Field> = None;
+    for p in &w.parts {
+        match p {
+            Part::Lit(s) => push(&mut cur, s, false),
+            // "$@" is every positional parameter, each its own field
+            Part::Quoted(s) if s == "$@" || s == "${@}" => {
+                for (n, a) in sh.vars.positional()[1..].iter().enumerate() {
+                    if n > 0 {
+                        fields.extend(cur.take());
+                    }
+                    push(&mut cur, a, false);
+                }
+            }
+            Part::Quoted(s) => push(&mut cur, &expand_text(sh, s)?, false),
+            Part::Bare(s) => subst(sh, s, &mut |t, split| {
+                if !split {
+                    if !t.is_empty() {
+                        push(&mut cur, t, true);
+                    }
+                    return;
+                }
+                for ch in t.chars() {
+                    if ch.is_whitespace() {
+                        fields.extend(cur.take());
+                    } else {
+                        push(&mut cur, ch.encode_utf8(&mut [0; 4]), true);
+                    }
+                }
+            })?,
+        }
+    }
+    fields.extend(cur);
+    Ok(fields)
+}
+
+// Expands a word into the fields (arguments) it stands for; there can
+// be none at all, e.g. for an unquoted variable that isn't set.
+pub fn expand_word(sh: &Session, w: &Word) -> Result<Vec<String>, String> {
+    Ok(fields(sh, w)?.into_iter().map(|f| f.text).collect())
+}
+
+// Expands a word into a glob pattern for case, with anything that
+// was quoted escaped and no splitting.
+pub fn expand_pattern(sh: &Session, w: &Word) -> Result<String, String> {
+    let mut cur = None;
+    for p in &w.parts {
+        match p {
+            Part::Lit(s) => push(&mut cur, s, false),
+            Part::Quoted(s) => push(&mut cur, &expand_text(sh, s)?, false),
+            Part::Bare(s) => push(&mut cur, &expand_text(sh, s)?, true),
+        }
+    }
+    Ok(cur.map(|f| f.pat).unwrap_or_default())
+}
+
+// Expands a command argument the whole way: braces, then parameters,
+// then globs. A glob that matches nothing is left as it was.
+pub fn expand_arg(sh: &Session, w: &Word) -> Result<Vec<String>, String> {
+    let mut out = vec![];
+    for w in braces(w) {
+        for f in fields(sh, &w)? {
+            let found = if f.magic {
+                glob::glob(&sh.vars.caller(), &f.pat)
+            } else {
+                vec![]
+            };
+            if found.is_empty() {
+                out.push(f.text);
+            } else {
+                out.extend(found);
+            }
+        }
+    }
+    Ok(out)
+}
+
+// which kind of part a character came from
+#[derive(Clone, Copy, PartialEq)]
+enum Kind {
+    Lit,
+    Bare,
+    Quoted,
+}
+
+// a word flattened into its characters
+type Chars = Vec<(char, Kind)>;
+
+fn flatten(w: &Word) -> Chars {
+    let mut c: Chars = vec![];
+    for p in &w.parts {
+        let (s, k) = match p {
+            Part::Lit(s) => (s, Kind::Lit),
+            Part::Bare(s) => (s, Kind::Bare),
+            Part::Quoted(s) => (s, Kind::Quoted),
+        };
+        c.extend(s.chars().map(|x| (x, k)));
+    }
+    c
+}
+
+fn unflatten(c: &[(char, Kind)]) -> Word {
+    let mut parts = vec![];
+    let mut i = 0;
+    while i < c.len() {
+        let k = c[i].1;
+        let n = c[i..].iter().take_while(|x| x.1 == k).count();
+        let s: String = c[i..i + n].iter().map(|x| x.0).collect();
+        parts.push(match k {
+            Kind::Lit => Part::Lit(s),
+            Kind::Bare => Part::Bare(s),
+            Kind::Quoted => Part::Quoted(s),
+        });
+        i += n;
+    }
+    Word { parts }
+}
+
+fn is_bare(c: &Chars, i: usize, ch: char) -> bool {
+    c.get(i) == Some(&(ch, Kind::Bare))
+}
+
+// where a ${...} starting at i ends
+fn skip_param(c: &Chars, i: usize) -> Option<usize> {
+    if !is_bare(c, i, '$') || !is_bare(c, i + 1, '{') {
+        return None;
+    }
+    (i + 2..c.len()).find(|&j| is_bare(c, j, '}'))
+}
+
+// the commas and closing brace of a {...} starting at i
+fn brace_group(c: &Chars, i: usize) -> Option<(Vec<usize>, usize)> {
+    let mut commas = vec![];
+    let mut depth = 0;
+    let mut j = i + 1;
+    while j < c.len() {
+        if let Some(e) = skip_param(c, j) {
+            j = e + 1;
+            continue;
+        }
+        if is_bare(c, j, '{') {
+            depth += 1;
+        } else if is_bare(c, j, '}') {
+            if depth == 0 {
+                return Some((commas, j));
+            }
+            depth -= 1;
+        } else if is_bare(c, j, ',') && depth == 0 {
+            commas.push(j);
+        }
+        j += 1;
+    }
+    None
+}
+
+// Brace expansion: a{b,c}d is abd acd. Only unquoted braces with a
+// comma in them count, and ${...} isn't one.
+fn braces(w: &Word) -> Vec<Word> {
+    let c = flatten(w);
+    let mut i = 0;
+    while i < c.len() {
+        if let Some(e) = skip_param(&c, i) {
+            i = e + 1;
+            continue;
+        }
+        if is_bare(&c, i, '{') {
+            if let Some((commas, end)) = brace_group(&c, i).filter(|g| !g.0.is_empty()) {
+                let mut out = vec![];
+                let mut start = i + 1;
+                for stop in commas.into_iter().chain([end]) {
+                    let mut v = c[..i].to_vec();
+                    v.extend_from_slice(&c[start..stop]);
+                    v.extend_from_slice(&c[end + 1..]);
+                    out.extend(braces(&unflatten(&v)));
+                    start = stop + 1;
+                }
+                return out;
+            }
+        }
+        i += 1;
+    }
+    vec![w.clone()]
+}
+
+// Quotes s so that irun would read it back as the same single word.
+pub fn quote(s: &str) -> String {
+    if !s.is_empty()
+        && s.chars()
+            .all(|c| c.is_ascii_alphanumeric() || "_-+=/.,:@%".contains(c))
+    {
+        return s.to_string();
+    }
+    format!("'{}'", s.replace('\'', "'\\''"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ex(sh: &Session, src: &str) -> Vec<String> {
+        let s = crate::parse::parse(src).unwrap();
+        let mut out = vec![];
+        for w in &s[0].first.cmds[0].simple().words {
+            out.extend(expand_word(sh, w).unwrap());
+        }
+        out
+    }
+
+    #[test]
+    fn expansion() {
+        let sh = &Session::new(1);
+        sh.vars.setenv("EXP_A", "one two");
+        sh.vars.setenv("EXP_E", "");
+        assert_eq!(ex(sh, "echo $EXP_A"), vec!["echo", "one", "two"]);
+        assert_eq!(ex(sh, "echo \"$EXP_A\""), vec!["echo", "one two"]);
+        assert_eq!(ex(sh, "echo '$EXP_A'"), vec!["echo", "$EXP_A"]);
+        assert_eq!(ex(sh, "echo \\$EXP_A"), vec!["echo", "$EXP_A"]);
+        assert_eq!(ex(sh, "echo x${EXP_A}y"), vec!["echo", "xone", "twoy"]);
+        assert_eq!(ex(sh, "echo $EXP_U $EXP_E \"$EXP_U\""), vec!["echo", ""]);
+        assert_eq!(
+            ex(sh, "echo ${EXP_U:-a b} ${EXP_E:-c}"),
+            vec!["echo", "a", "b", "c"]
+        );
+        assert_eq!(
+            ex(sh, "echo ${EXP_E-c} ${EXP_U-$EXP_A}"),
+            vec!["echo", "one", "two"]
+        );
+        assert_eq!(ex(sh, "echo $ a$ $$"), vec!["echo", "$", "a$", "1"]);
+    }
+
+    #[test]
+    fn positional() {
+        use crate::process::block_on;
+        let sh = &Session::new(1);
+        let args = ["sc", "a b", "c", "d", "e", "f", "g", "h", "i", "j", "k"];
+        let pos = args.iter().map(|x| x.to_string()).collect();
+        block_on(sh.vars.with_positional(pos, async {
+            assert_eq!(
+                ex(sh, "echo $0 $1x $# $10 ${10}"),
+                vec!["echo", "sc", "a", "bx", "10", "a", "b0", "k"]
+            );
+            assert_eq!(
+                ex(sh, "echo \"$@\""),
+                vec!["echo", "a b", "c", "d", "e", "f", "g", "h", "i", "j", "k"]
+            );
+            assert_eq!(ex(sh, "echo \"$*\""), vec!["echo", "a b c d e f g h i j k"]);
+        }));
+        block_on(sh.vars.with_positional(vec!["sc".to_string()], async {
+            assert_eq!(ex(sh, "echo \"$@\" $# \"$1\""), vec!["echo", "0", ""]);
+        }));
+    }
+
+    #[test]
+    fn bad_substitution() {
+        let sh = &Session::new(1);
+        let s = crate::parse::parse("echo ${1x} ${A?b}").unwrap();
+        let w = &s[0].first.cmds[0].simple().words;
+        assert_eq!(
+            expand_word(sh, &w[1]),
+            Err("${1x}: bad substitution".to_string())
+        );
+        assert_eq!(
+            expand_word(sh, &w[2]),
+            Err("${A?b}: bad substitution".to_string())
+        );
+    }
+
+    fn args(sh: &Session, src: &str) -> Vec<String> {
+        let s = crate::parse::parse(src).unwrap();
+        let mut out = vec![];
+        for w in &s[0].first.cmds[0].simple().words {
+            out.extend(expand_arg(sh, w).unwrap());
+        }
+        out
+    }
+
+    #[test]
+    fn braces_and_globs() {
+        let sh = &Session::new(1);
+        sh.vars.setenv("EXP_B", "1,2");
+        assert_eq!(args(sh, "a{b,c}d"), vec!["abd", "acd"]);
+        assert_eq!(args(sh, "{a,b{1,2}}"), vec!["a", "b1", "b2"]);
+        assert_eq!(args(sh, "{a} {} x{,y}"), vec!["{a}", "{}", "x", "xy"]);
+        assert_eq!(args(sh, "'{a,b}' {$EXP_B,z}"), vec!["{a,b}", "1,2", "z"]);
+        assert_eq!(args(sh, "${EXP_U-{a,b}}"), vec!["{a,b}"]);
+        let f = fields(
+            sh,
+            &crate::parse::parse("a'*'\\??[").unwrap()[0].first.cmds[0]
+                .simple()
+                .words[0],
+        )
+        .unwrap();
+        assert_eq!((f[0].pat.as_str(), f[0].magic), ("a\\*\\??[", true));
+    }
+
+    #[test]
+    fn quoting() {
+        assert_eq!(quote("abc/d.e"), "abc/d.e");
+        assert_eq!(quote(""), "''");
+        assert_eq!(quote("a b"), "'a b'");
+        assert_eq!(quote("it's"), "'it'\\''s'");
+    }
+}
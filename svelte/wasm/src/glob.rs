@@ -0,0 +1,242 @@
+// Pathname expansion (globbing) for irun.
+// Patterns are matched a path component at a time against what the
+// VFS has in each directory. A backslash makes the character after it
+// literal, which is how expand.rs keeps quoted characters from being
+// special.
+
+use either::Either;
+
+use crate::sysvars::Caller;
+use crate::vfs::futils::find_file;
+use crate::vfs::VirtualFileSystem;
+
+pub fn is_special(c: char) -> bool {
+    matches!(c, '*' | '?' | '[' | '\\')
+}
+
+// whether there's anything unescaped to expand
+fn has_magic(p: &[char]) -> bool {
+    let mut i = 0;
+    while i < p.len() {
+        match p[i] {
+            '\\' => i += 2,
+            '*' | '?' | '[' => return true,
+            _ => i += 1,
+        }
+    }
+    false
+}
+
+fn unescape(p: &[char]) -> String {
+    let mut out = String::new();
+    let mut it = p.iter();
+    while let Some(&c) = it.next() {
+        match c {
+            '\\' => out.extend(it.next()),
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+// A [...] expression at the start of p: whether c is in it, and how
+// long it is. None if it's never closed, in which case the [ is just
+// a [. A ] right after the [ (or [!) is part of the set.
+fn bracket(p: &[char], c: char) -> Option<(bool, usize)> {
+    let neg = matches!(p.get(1), Some('!' | '^'));
+    let mut i = if neg { 2 } else { 1 };
+    let mut first = true;
+    let mut hit = false;
+    loop {
+        let mut a = *p.get(i)?;
+        if a == ']' && !first {
+            break;
+        }
+        first = false;
+        if a == '\\' {
+            i += 1;
+            a = *p.get(i)?;
+        }
+        // a-z, unless the - is the last thing in the set
+        if p.get(i + 1) == Some(&'-') && p.get(i + 2).is_some_and(|&x| x != ']') {
+            let mut b = p[i + 2];
+            i += 3;
+            if b == '\\' {
+                b = *p.get(i)?;
+                i += 1;
+            }
+            hit |= a <= c && c <= b;
+        } else {
+            hit |= a == c;
+            i += 1;
+        }
+    }
+    Some((hit != neg, i + 1))
+}
+
+pub fn matches(p: &[char], s: &[char]) -> bool {
+    match p.first() {
+        None => s.is_empty(),
+        Some('*') => (0..=s.len()).any(|n| matches(&p[1..], &s[n..])),
+        _ if s.is_empty() => false,
+        Some('?') => matches(&p[1..], &s[1..]),
+        Some('[') => match bracket(p, s[0]) {
+            Some((hit, n)) => hit && matches(&p[n..], &s[1..]),
+            None => s[0] == '[' && matches(&p[1..], &s[1..]),
+        },
+        Some('\\') if p.len() > 1 => s[0] == p[1] && matches(&p[2..], &s[1..]),
+        Some(&c) => s[0] == c && matches(&p[1..], &s[1..]),
+    }
+}
+
+fn join(dir: &str, name: &str) -> String {
+    if dir.is_empty() || dir.ends_with('/') {
+        format!("{}{}", dir, name)
+    } else {
+        format!("{}/{}", dir, name)
+    }
+}
+
+// the names in a directory, or None if it isn't one or may not be read
+pub fn entries(c: &Caller, dir: &str) -> Option<Vec<String>> {
+    let dir = if dir.is_empty() { "." } else { dir };
+    match find_file(c, dir.to_string(), false) {
+        Either::Left((mut fs, fd)) => Some(
+            fs.vfd_as_dentry(&fd)?
+                .get_entries()
+                .into_iter()
+                // inode 0 is a deleted entry
+                .filter(|e| e.inum != 0)
+                .map(|e| e.filename)
+                .collect(),
+        ),
+        Either::Right(_) => None,
+    }
+}
+
+fn exists(c: &Caller, path: &str) -> bool {
+    !matches!(find_file(c, path.to_string(), true), Either::Right(None))
+}
+
+// Every existing path that matches pat, sorted. Like bash, names
+// starting with a dot only match a pattern that starts with one too,
+// and . and .. never match.
+pub fn glob(c: &Caller, pat: &str) -> Vec<String> {
+    let (mut found, rest) = match pat.strip_prefix('/') {
+        Some(r) => (vec!["/".to_string()], r),
+        None => (vec![String::new()], pat),
+    };
+    let comps: Vec<Vec<char>> = rest.split('/').map(|x| x.chars().collect()).collect();
+    let last = comps.len() - 1;
+    for (n, comp) in comps.iter().enumerate() {
+        if comp.is_empty() {
+            // a trailing slash only leaves directories
+            if n == last {
+                found.retain(|f| entries(c, f).is_some());
+                for f in found.iter_mut().filter(|f| !f.ends_with('/')) {
+                    f.push('/');
+                }
+            }
+            continue;
+        }
+        let mut next = vec![];
+        for f in &found {
+            if !has_magic(comp) {
+                // directories in the middle get checked by the next listing
+                let p = join(f, &unescape(comp));
+                if n < last || exists(c, &p) {
+                    next.push(p);
+                }
+                continue;
+            }
+            for name in entries(c, f).unwrap_or_default() {
+                if name == "." || name == ".." || (name.starts_with('.') && comp[0] != '.') {
+                    continue;
+                }
+                if matches(comp, &name.chars().collect::<Vec<char>>()) {
+                    next.push(join(f, &name));
+                }
+            }
+        }
+        found = next;
+    }
+    found.sort();
+    found
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn m(p: &str, s: &str) -> bool {
+        let c = |x: &str| x.chars().collect::<Vec<char>>();
+        matches(&c(p), &c(s))
+    }
+
+    #[test]
+    fn patterns() {
+        assert!(m("*.txt", "a.txt"));
+        assert!(m("*", ""));
+        assert!(!m("*.txt", "a.txt~"));
+        assert!(m("a?c", "abc"));
+        assert!(!m("a?c", "ac"));
+        assert!(m("[a-c]x", "bx"));
+        assert!(!m("[!a-c]x", "bx"));
+        assert!(m("[]]", "]"));
+        assert!(m("[a-]", "-"));
+        assert!(m("[x", "[x"));
+        assert!(m("\\*", "*"));
+        assert!(!m("\\*", "a"));
+        assert!(m("日*", "日本"));
+    }
+
+    #[test]
+    fn vfs() {
+        crate::vfs::mount_test_fs();
+        let glob = |p| glob(&Caller::root(), p);
+        assert_eq!(glob("/globd/*.txt"), vec!["/globd/a.txt", "/globd/b.txt"]);
+        assert_eq!(
+            glob("/globd/*"),
+            vec!["/globd/a.txt", "/globd/b.txt", "/globd/c", "/globd/sub"]
+        );
+        assert_eq!(glob("/globd/.*"), vec!["/globd/.hidden"]);
+        assert_eq!(glob("/globd/sub/?"), vec!["/globd/sub/x"]);
+        assert_eq!(glob("/g*/s*/y*"), vec!["/globd/sub/yy"]);
+        assert_eq!(glob("/globd/*/"), vec!["/globd/sub/"]);
+        assert_eq!(glob("/globd/z*"), Vec::<String>::new());
+        assert_eq!(glob("/nope/*"), Vec::<String>::new());
+        // quoted, or nothing matches: the word stays as it is
+        let s = crate::parse::parse("/globd/*.tx? '/globd/'*.txt /nope* '/*'").unwrap();
+        let w = &s[0].first.cmds[0].simple().words;
+        let sh = crate::session::Session::new(1);
+        let args: Vec<Vec<String>> = w
+            .iter()
+            .map(|x| crate::expand::expand_arg(&sh, x).unwrap())
+            .collect();
+        let txt = vec!["/globd/a.txt", "/globd/b.txt"];
+        assert_eq!(args, vec![txt.clone(), txt, vec!["/nope*"], vec!["/*"]]);
+    }
+
+    // root's 0700 directory can't be listed by anyone else
+    #[test]
+    fn guest() {
+        crate::vfs::mount_test_fs();
+        let sh = crate::session::Session::new(1);
+        sh.vars.set_creds(crate::sysvars::Creds {
+            uid: 1000,
+            gid: 1000,
+            groups: vec![],
+        });
+        assert_eq!(glob(&sh.caller(), "/private/*"), Vec::<String>::new());
+        assert_eq!(entries(&sh.caller(), "/private"), None);
+        let out = crate::process::Buffer::new();
+        crate::process::block_on(crate::run_shell_instruction(
+            &sh,
+            &out,
+            &out,
+            "echo /private/*",
+        ));
+        assert_eq!(out.take(), "/private/*\n");
+        assert_eq!(glob(&Caller::root(), "/private/*"), vec!["/private/secret"]);
+    }
+}
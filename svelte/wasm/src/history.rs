@@ -0,0 +1,294 @@
+// irun command history
+// Each console's shell has its own (see session.rs), where builtins
+// can get at it. It is saved to localStorage after every change and
+// loaded back in when the console starts, so it survives page
+// reloads. Every console keeps its own there too, so that they don't
+// write over each other's: tty1 under irun_history, as it always has,
+// and the others under irun_history.ttyN.
+
+use std::cell::RefCell;
+use std::collections::{HashSet, VecDeque};
+
+pub const MAX_HIST_LEN: usize = 1000;
+const HIST_KEY: &str = "irun_history";
+
+struct Inner {
+    ents: VecDeque<String>,
+    // history number of ents[0]; bumped when the front falls off
+    // so that `!n` and `history` numbers stay stable in a session
+    base: usize,
+}
+
+pub struct History {
+    key: String,
+    h: RefCell<Inner>,
+}
+
+impl History {
+    // console tty's history, empty until it's loaded
+    pub fn new(tty: usize) -> Self {
+        let key = match tty {
+            1 => HIST_KEY.to_string(),
+            n => format!("{}.tty{}", HIST_KEY, n),
+        };
+        Self {
+            key,
+            h: RefCell::new(Inner {
+                ents: VecDeque::new(),
+                base: 1,
+            }),
+        }
+    }
+
+    pub fn load(&self) {
+        let mut h = self.h.borrow_mut();
+        h.ents.clear();
+        h.base = 1;
+        if let Some(s) = crate::storage().and_then(|s| s.get_item(&self.key).ok().flatten()) {
+            h.ents
+                .extend(s.lines().filter(|l| !l.is_empty()).map(|l| l.to_string()));
+        }
+    }
+
+    // The stored copy is deduplicated (keeping the newest occurrence)
+    // and capped; the in-session copy only drops consecutive repeats.
+    fn save(&self, h: &Inner) {
+        let mut out: Vec<&str> = vec![];
+        let mut seen = HashSet::new();
+        for e in h.ents.iter().rev() {
+            if out.len() == MAX_HIST_LEN {
+                break;
+            }
+            if seen.insert(e.as_str()) {
+                out.push(e);
+            }
+        }
+        out.reverse();
+        if let Some(s) = crate::storage() {
+            // nothing useful we can do if the quota is exceeded
+            let _ = s.set_item(&self.key, &out.join("\n"));
+        }
+    }
+
+    pub fn push(&self, cmd: &str) {
+        let mut h = self.h.borrow_mut();
+        if cmd.is_empty() || h.ents.back().is_some_and(|b| b == cmd) {
+            return;
+        }
+        h.ents.push_back(cmd.to_string());
+        if h.ents.len() > MAX_HIST_LEN {
+            h.ents.pop_front();
+            h.base += 1;
+        }
+        self.save(&h);
+    }
+
+    pub fn len(&self) -> usize {
+        self.h.borrow().ents.len()
+    }
+
+    // index into the current history, not a history number
+    pub fn get(&self, idx: usize) -> Option<String> {
+        self.h.borrow().ents.get(idx).cloned()
+    }
+
+    // (history number, command) pairs, oldest first
+    pub fn entries(&self) -> Vec<(usize, String)> {
+        let h = self.h.borrow();
+        h.ents
+            .iter()
+            .enumerate()
+            .map(|(n, e)| (n + h.base, e.clone()))
+            .collect()
+    }
+
+    pub fn clear(&self) {
+        let mut h = self.h.borrow_mut();
+        h.ents.clear();
+        h.base = 1;
+        self.save(&h);
+    }
+
+    // deletes by history number; negative numbers count back from the end
+    pub fn delete(&self, num: isize) -> bool {
+        let mut h = self.h.borrow_mut();
+        let idx = if num < 0 {
+            h.ents.len() as isize + num
+        } else {
+            num - h.base as isize
+        };
+        if idx < 0 || idx as usize >= h.ents.len() {
+            return false;
+        }
+        h.ents.remove(idx as usize);
+        self.save(&h);
+        true
+    }
+
+    // Performs bash-style history expansion on a line before it is run.
+    // Supported: !!, !n, !-n, !prefix and ^old^new at the start of a line.
+    // Returns Ok(None) if nothing was expanded, so callers know whether
+    // to echo the new line back like bash does.
+    pub fn expand(&self, line: &str) -> Result<Option<String>, String> {
+        expand(&self.h.borrow(), line)
+    }
+}
+
+// the line editor walks the history through this
+impl crate::editor::HistoryView for History {
+    fn len(&self) -> usize {
+        History::len(self)
+    }
+    fn get(&self, idx: usize) -> Option<String> {
+        History::get(self, idx)
+    }
+}
+
+fn event(h: &Inner, spec: &str) -> Result<String, String> {
+    let nf = || format!("!{}: event not found", spec);
+    if spec == "!" {
+        return h.ents.back().cloned().ok_or_else(nf);
+    }
+    if let Some(n) = spec.strip_prefix('-') {
+        let n: usize = n.parse().map_err(|_| nf())?;
+        if n == 0 || n > h.ents.len() {
+            return Err(nf());
+        }
+        return Ok(h.ents[h.ents.len() - n].clone());
+    }
+    if let Ok(n) = spec.parse::<usize>() {
+        if n < h.base || n - h.base >= h.ents.len() {
+            return Err(nf());
+        }
+        return Ok(h.ents[n - h.base].clone());
+    }
+    h.ents
+        .iter()
+        .rev()
+        .find(|e| e.starts_with(spec))
+        .cloned()
+        .ok_or_else(nf)
+}
+
+fn expand(h: &Inner, line: &str) -> Result<Option<String>, String> {
+    if let Some(rest) = line.strip_prefix('^') {
+        let mut parts = rest.splitn(3, '^');
+        let old = parts.next().unwrap_or("");
+        let new = parts.next().unwrap_or("");
+        let tail = parts.next().unwrap_or("");
+        let prev = h.ents.back().ok_or("!!: event not found".to_string())?;
+        if old.is_empty() || !prev.contains(old) {
+            return Err(format!(":s^{}^{}^: substitution failed", old, new));
+        }
+        return Ok(Some(format!("{}{}", prev.replacen(old, new, 1), tail)));
+    }
+    let c: Vec<char> = line.chars().collect();
+    let mut out = String::new();
+    let mut changed = false;
+    let mut squote = false;
+    let mut n = 0;
+    while n < c.len() {
+        match c[n] {
+            '\\' if !squote && n + 1 < c.len() => {
+                out.push(c[n]);
+                out.push(c[n + 1]);
+                n += 2;
+                continue;
+            }
+            '\'' => squote = !squote,
+            '!' if !squote && n + 1 < c.len() => {
+                let start = n + 1;
+                let mut end = start;
+                if c[start] == '!' {
+                    end += 1;
+                } else if c[start] == '-' || c[start].is_ascii_digit() {
+                    end += 1;
+                    while end < c.len() && c[end].is_ascii_digit() {
+                        end += 1;
+                    }
+                } else if !(c[start].is_whitespace() || "=(\"".contains(c[start])) {
+                    while end < c.len()
+                        && !(c[end].is_whitespace() || ";&|<>()\"'".contains(c[end]))
+                    {
+                        end += 1;
+                    }
+                }
+                if end != start {
+                    let spec: String = c[start..end].iter().collect();
+                    out.push_str(&event(h, &spec)?);
+                    changed = true;
+                    n = end;
+                    continue;
+                }
+            }
+            _ => {}
+        }
+        out.push(c[n]);
+        n += 1;
+    }
+    Ok(if changed { Some(out) } else { None })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn set(h: &History, cmds: &[&str]) {
+        h.clear();
+        for c in cmds {
+            h.push(c);
+        }
+    }
+
+    #[test]
+    fn events() {
+        let h = History::new(1);
+        set(&h, &["ls -l", "echo hi", "echo hi", "cat f"]);
+        // repeats only go in once
+        assert_eq!(h.len(), 3);
+        let ex = |l: &str| h.expand(l);
+        assert_eq!(ex("!!"), Ok(Some("cat f".into())));
+        assert_eq!(ex("sudo !! x"), Ok(Some("sudo cat f x".into())));
+        assert_eq!(ex("!1"), Ok(Some("ls -l".into())));
+        assert_eq!(ex("!-2"), Ok(Some("echo hi".into())));
+        assert_eq!(ex("!ec; !l"), Ok(Some("echo hi; ls -l".into())));
+        assert_eq!(ex("^f^g^ x"), Ok(Some("cat g x".into())));
+        // left alone: escaped, quoted, or not an event
+        assert_eq!(ex("echo \\!! '!!' ! != !(x)"), Ok(None));
+        assert_eq!(ex("echo !"), Ok(None));
+        assert_eq!(ex("!4"), Err("!4: event not found".into()));
+        assert_eq!(ex("!-0"), Err("!-0: event not found".into()));
+        assert_eq!(ex("!-4"), Err("!-4: event not found".into()));
+        assert_eq!(ex("!nope"), Err("!nope: event not found".into()));
+        assert_eq!(ex("^x^y"), Err(":s^x^y^: substitution failed".into()));
+        set(&h, &[]);
+        assert_eq!(ex("!!"), Err("!!: event not found".into()));
+        assert_eq!(ex("^a^b"), Err("!!: event not found".into()));
+    }
+
+    #[test]
+    fn numbers() {
+        let h = History::new(2);
+        for n in 0..MAX_HIST_LEN + 2 {
+            h.push(&format!("c{}", n));
+        }
+        // the first two fell off, but numbers stay the same
+        assert_eq!(h.len(), MAX_HIST_LEN);
+        assert_eq!(h.entries()[0], (3, "c2".to_string()));
+        assert_eq!(h.expand("!2"), Err("!2: event not found".into()));
+        assert_eq!(h.expand("!3"), Ok(Some("c2".into())));
+        assert_eq!(h.expand("!1002"), Ok(Some("c1001".into())));
+        assert!(!h.delete(2));
+        assert!(!h.delete(1003));
+        assert!(h.delete(3));
+        // as in bash, the ones after it move up
+        assert_eq!(h.get(0).as_deref(), Some("c3"));
+        assert_eq!(h.entries()[0].0, 3);
+        assert!(h.delete(-1));
+        assert_eq!(h.entries().last(), Some(&(1000, "c1000".to_string())));
+        assert!(!h.delete(-(MAX_HIST_LEN as isize)));
+        set(&h, &[]);
+        assert_eq!(h.entries(), vec![]);
+        assert_eq!(h.key, "irun_history.tty2");
+    }
+}
@@ -3,8 +3,10 @@
 use core::time::Duration;
 //use core::ops::{Add, Sub, AddAssign, SubAssign};
 
+#[cfg(target_arch = "wasm32")]
 use wasm_bindgen::prelude::*;
 
+#[cfg(target_arch = "wasm32")]
 #[wasm_bindgen(inline_js = r#"
 export function performance_now() {
   return performance.now();
@@ -13,6 +15,15 @@ extern "C" {
     fn performance_now() -> f64;
 }
 
+// natively (in tests) there's no performance.now(), so time counts
+// from the first time anyone asks
+#[cfg(not(target_arch = "wasm32"))]
+fn performance_now() -> f64 {
+    static START: once_cell::sync::Lazy<std::time::Instant> =
+        once_cell::sync::Lazy::new(std::time::Instant::now);
+    START.elapsed().as_secs_f64() * 1000.0
+}
+
 #[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct Instant(u64);
 
@@ -0,0 +1,630 @@
+// Runs what parse.rs builds.
+// Simple commands end up in crate::exec; everything else (pipes,
+// redirections, && and ||, the compound commands and functions) is
+// dealt with here. break, continue, return and exit can't just return
+// a status, so they leave a Flow behind that everything running a
+// list checks for after each command. A Ctrl-C is checked for in the
+// same places.
+
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::sync::Arc;
+
+use crate::parse::{
+    AndOr, Command, Compound, Connector, Pipeline, Redir, RedirKind, Script, Simple,
+};
+use crate::process::{Buffer, Sink, Task};
+use crate::session::Session;
+use crate::sysvars::ShVar;
+use crate::{expand, glob, tty, vfs};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Flow {
+    Break(usize),
+    Continue(usize),
+    Return,
+    Exit,
+}
+
+// one per running function: the variables `local` has to put back
+type Frame = Vec<(String, Option<ShVar>)>;
+// as with scripts, so deep recursion fails instead of the wasm stack
+const MAX_FUNC_DEPTH: usize = 100;
+
+// A shell's functions and where it is in running them. Each console's
+// shell has its own (see session.rs).
+#[derive(Default)]
+pub struct State {
+    flow: Option<Flow>,
+    // how many loops we're inside of, in the current function
+    loops: usize,
+    funcs: BTreeMap<String, Arc<Compound>>,
+    frames: Vec<Frame>,
+    // how many subshells deep we are, where exit only ends the subshell
+    subshells: usize,
+}
+
+pub fn set_flow(sh: &Session, f: Flow) {
+    sh.interp.borrow_mut().flow = Some(f);
+}
+pub fn flow(sh: &Session) -> Option<Flow> {
+    sh.interp.borrow().flow
+}
+// takes the flow if it's `f`
+pub fn take_flow(sh: &Session, f: Flow) -> bool {
+    let cur = &mut sh.interp.borrow_mut().flow;
+    if *cur == Some(f) {
+        *cur = None;
+        return true;
+    }
+    false
+}
+pub fn in_subshell(sh: &Session) -> bool {
+    sh.interp.borrow().subshells > 0
+}
+// Runs f as a child shell, for exit's sake: there it only ends f. The
+// stages of a pipeline are run like this too, as they would be forked.
+async fn subshell<T>(sh: &Session, f: impl Future<Output = T>) -> T {
+    sh.interp.borrow_mut().subshells += 1;
+    let r = f.await;
+    sh.interp.borrow_mut().subshells -= 1;
+    take_flow(sh, Flow::Exit);
+    r
+}
+pub fn clear_flow(sh: &Session) {
+    sh.interp.borrow_mut().flow = None;
+}
+pub fn loops(sh: &Session) -> usize {
+    sh.interp.borrow().loops
+}
+pub fn in_function(sh: &Session) -> bool {
+    !sh.interp.borrow().frames.is_empty()
+}
+
+pub fn functions(sh: &Session) -> BTreeMap<String, Arc<Compound>> {
+    sh.interp.borrow().funcs.clone()
+}
+pub fn set_functions(sh: &Session, f: BTreeMap<String, Arc<Compound>>) {
+    sh.interp.borrow_mut().funcs = f;
+}
+// false if there was no such function
+pub fn unset_function(sh: &Session, name: &str) -> bool {
+    sh.interp.borrow_mut().funcs.remove(name).is_some()
+}
+
+// `local NAME`: remembers what NAME was, so that it's put back when
+// the function returns. False outside of functions.
+pub fn make_local(sh: &Session, name: &str) -> bool {
+    let old = sh.vars.getvar(name);
+    let mut st = sh.interp.borrow_mut();
+    let frame = match st.frames.last_mut() {
+        Some(f) => f,
+        None => return false,
+    };
+    if !frame.iter().any(|x| x.0 == name) {
+        frame.push((name.to_string(), old));
+    }
+    true
+}
+
+// Runs the function argv[0] with the rest as its positional
+// parameters; None if there's no such function.
+pub async fn call(sh: &Session, out: &dyn Sink, err: &dyn Sink, argv: &[String]) -> Option<i32> {
+    let body = sh.interp.borrow().funcs.get(&argv[0]).cloned()?;
+    if sh.interp.borrow().frames.len() >= MAX_FUNC_DEPTH {
+        err.writeln(&format!(
+            "irun: {}: maximum function nesting level exceeded ({})",
+            argv[0], MAX_FUNC_DEPTH
+        ));
+        return Some(1);
+    }
+    // a break in a function can't leave a loop outside of it
+    let loops = {
+        let mut st = sh.interp.borrow_mut();
+        st.frames.push(vec![]);
+        std::mem::take(&mut st.loops)
+    };
+    let mut args = vec![sh.vars.positional()[0].clone()];
+    args.extend_from_slice(&argv[1..]);
+    let st = sh
+        .vars
+        .with_positional(args, run_compound(sh, out, err, &body))
+        .await;
+    take_flow(sh, Flow::Return);
+    let frame = {
+        let mut st = sh.interp.borrow_mut();
+        st.loops = loops;
+        st.frames.pop().unwrap_or_default()
+    };
+    for (k, v) in frame.into_iter().rev() {
+        sh.vars.putvar(&k, v);
+    }
+    Some(st)
+}
+
+// whether to stop running a list here
+fn stop(sh: &Session) -> bool {
+    flow(sh).is_some() || sh.tty.interrupted()
+}
+
+pub async fn run_script(sh: &Session, out: &dyn Sink, err: &dyn Sink, script: &Script) -> i32 {
+    let mut status = 0;
+    for ao in script {
+        status = run_and_or(sh, out, err, ao).await;
+        if stop(sh) {
+            break;
+        }
+    }
+    status
+}
+
+async fn run_and_or(sh: &Session, out: &dyn Sink, err: &dyn Sink, ao: &AndOr) -> i32 {
+    let mut status = run_pipeline(sh, out, err, &ao.first).await;
+    for (conn, pl) in &ao.rest {
+        if stop(sh) {
+            break;
+        }
+        let go = match conn {
+            Connector::And => status == 0,
+            Connector::Or => status != 0,
+        };
+        if go {
+            status = run_pipeline(sh, out, err, pl).await;
+        }
+    }
+    status
+}
+
+// Commands run one after the other; everything but the last writes
+// into a buffer, which becomes the standard input of the next.
+async fn run_pipeline(sh: &Session, out: &dyn Sink, err: &dyn Sink, pl: &Pipeline) -> i32 {
+    let mut input = None;
+    let last = pl.cmds.len() - 1;
+    for cmd in &pl.cmds[..last] {
+        let buf = Buffer::new();
+        subshell(sh, run_command(sh, &buf, err, cmd, input)).await;
+        input = Some(buf.take());
+    }
+    let mut status = match last {
+        0 => run_command(sh, out, err, &pl.cmds[last], input).await,
+        _ => subshell(sh, run_command(sh, out, err, &pl.cmds[last], input)).await,
+    };
+    if pl.bang {
+        status = (status == 0) as i32;
+    }
+    if sh.tty.interrupted() {
+        status = 130;
+    }
+    sh.vars.set_status(status);
+    status
+}
+
+// boxed, since commands can run commands
+fn run_command<'a>(
+    sh: &'a Session,
+    out: &'a dyn Sink,
+    err: &'a dyn Sink,
+    cmd: &'a Command,
+    stdin: Option<String>,
+) -> Task<'a> {
+    Box::pin(async move {
+        match cmd {
+            Command::Simple(s) => run_simple(sh, out, err, s, stdin).await,
+            Command::Compound(c, redirs) => {
+                let r = match redirect(sh, redirs, stdin) {
+                    Ok(r) => r,
+                    Err(e) => {
+                        err.writeln(&format!("irun: {}", e));
+                        return 1;
+                    }
+                };
+                let (o, e) = r.sinks(out, err);
+                let st = run_compound(sh, o, e, c).await;
+                r.finish(sh, err, st)
+            }
+            Command::FuncDef(name, body) => {
+                sh.interp
+                    .borrow_mut()
+                    .funcs
+                    .insert(name.clone(), body.clone());
+                0
+            }
+        }
+    })
+}
+
+async fn run_simple(
+    sh: &Session,
+    out: &dyn Sink,
+    err: &dyn Sink,
+    cmd: &Simple,
+    stdin: Option<String>,
+) -> i32 {
+    let (vals, argv) = match expand_simple(sh, cmd) {
+        Ok(x) => x,
+        Err(e) => {
+            err.writeln(&format!("irun: {}", e));
+            return 1;
+        }
+    };
+    let mut r = match redirect(sh, &cmd.redirs, stdin) {
+        Ok(r) => r,
+        Err(e) => {
+            err.writeln(&format!("irun: {}", e));
+            return 1;
+        }
+    };
+    let stdin = r.stdin.take();
+    let (o, e) = r.sinks(out, err);
+    let st = if argv.is_empty() {
+        for (k, v) in vals {
+            sh.vars.setenv(&k, &v);
+        }
+        0
+    } else {
+        // assignments in front of a command only last for that command
+        sh.vars
+            .scoped_env(&vals, crate::exec(sh, &argv, stdin, o, e))
+            .await
+    };
+    r.finish(sh, err, st)
+}
+
+// whether a loop should stop, having dealt with a break or continue
+// that was meant for it
+fn leave_loop(sh: &Session) -> bool {
+    if sh.tty.interrupted() {
+        return true;
+    }
+    let f = &mut sh.interp.borrow_mut().flow;
+    match *f {
+        None => false,
+        Some(Flow::Break(n)) => {
+            *f = if n > 1 {
+                Some(Flow::Break(n - 1))
+            } else {
+                None
+            };
+            true
+        }
+        Some(Flow::Continue(n)) if n > 1 => {
+            *f = Some(Flow::Continue(n - 1));
+            true
+        }
+        Some(Flow::Continue(_)) => {
+            *f = None;
+            false
+        }
+        Some(_) => true,
+    }
+}
+
+async fn run_loop(sh: &Session, out: &dyn Sink, err: &dyn Sink, c: &Compound) -> i32 {
+    let mut status = 0;
+    match c {
+        Compound::Loop { until, cond, body } => loop {
+            tty::pause().await;
+            let st = run_script(sh, out, err, cond).await;
+            if leave_loop(sh) || (st == 0) == *until {
+                break;
+            }
+            status = run_script(sh, out, err, body).await;
+            if leave_loop(sh) {
+                break;
+            }
+        },
+        Compound::For { var, words, body } => {
+            let items = match words {
+                Some(ws) => {
+                    let mut items = vec![];
+                    for w in ws {
+                        match expand::expand_arg(sh, w) {
+                            Ok(x) => items.extend(x),
+                            Err(e) => {
+                                err.writeln(&format!("irun: {}", e));
+                                return 1;
+                            }
+                        }
+                    }
+                    items
+                }
+                None => sh.vars.positional()[1..].to_vec(),
+            };
+            for it in items {
+                tty::pause().await;
+                sh.vars.setenv(var, &it);
+                status = run_script(sh, out, err, body).await;
+                if leave_loop(sh) {
+                    break;
+                }
+            }
+        }
+        _ => unreachable!(),
+    }
+    status
+}
+
+async fn run_compound(sh: &Session, out: &dyn Sink, err: &dyn Sink, c: &Compound) -> i32 {
+    match c {
+        Compound::Group(s) => run_script(sh, out, err, s).await,
+        Compound::Subshell(s) => {
+            let funcs = functions(sh);
+            let st = subshell(sh, sh.vars.subshell(run_script(sh, out, err, s))).await;
+            set_functions(sh, funcs);
+            // nothing gets out of a subshell, not even exit
+            clear_flow(sh);
+            st
+        }
+        Compound::If { branches, els } => {
+            for (cond, body) in branches {
+                let st = run_script(sh, out, err, cond).await;
+                if stop(sh) {
+                    return st;
+                }
+                if st == 0 {
+                    return run_script(sh, out, err, body).await;
+                }
+            }
+            match els {
+                Some(e) => run_script(sh, out, err, e).await,
+                None => 0,
+            }
+        }
+        Compound::Loop { .. } | Compound::For { .. } => {
+            sh.interp.borrow_mut().loops += 1;
+            let st = run_loop(sh, out, err, c).await;
+            sh.interp.borrow_mut().loops -= 1;
+            st
+        }
+        Compound::Case { word, arms } => {
+            let w: Vec<char> = match expand::expand_str(sh, word) {
+                Ok(w) => w.chars().collect(),
+                Err(e) => {
+                    err.writeln(&format!("irun: {}", e));
+                    return 1;
+                }
+            };
+            for (pats, body) in arms {
+                for p in pats {
+                    let pat = match expand::expand_pattern(sh, p) {
+                        Ok(p) => p,
+                        Err(e) => {
+                            err.writeln(&format!("irun: {}", e));
+                            return 1;
+                        }
+                    };
+                    if glob::matches(&pat.chars().collect::<Vec<char>>(), &w) {
+                        return run_script(sh, out, err, body).await;
+                    }
+                }
+            }
+            0
+        }
+    }
+}
+
+#[derive(Clone, PartialEq)]
+enum Dest {
+    Stdout,
+    Stderr,
+    File(String),
+}
+
+// A command's redirections, carried out: its standard input, where
+// its output goes, and a buffer for each file written to.
+struct Redirected {
+    stdin: Option<String>,
+    out: Dest,
+    err: Dest,
+    files: Vec<(String, Buffer)>,
+}
+
+fn pick<'a>(
+    d: &Dest,
+    out: &'a dyn Sink,
+    err: &'a dyn Sink,
+    files: &'a [(String, Buffer)],
+) -> &'a dyn Sink {
+    match d {
+        Dest::Stdout => out,
+        Dest::Stderr => err,
+        Dest::File(f) => &files.iter().find(|x| x.0 == *f).unwrap().1,
+    }
+}
+
+impl Redirected {
+    // the sinks to write to instead of out and err
+    fn sinks<'a>(&'a self, out: &'a dyn Sink, err: &'a dyn Sink) -> (&'a dyn Sink, &'a dyn Sink) {
+        (
+            pick(&self.out, out, err, &self.files),
+            pick(&self.err, out, err, &self.files),
+        )
+    }
+    // writes out what went to files, once the command is done
+    fn finish(self, sh: &Session, err: &dyn Sink, mut status: i32) -> i32 {
+        let c = sh.vars.caller();
+        for (f, b) in &self.files {
+            // the file was already truncated by redirect()
+            if let Err(x) = vfs::futils::write_file(&c, f, b.take().as_bytes(), true) {
+                err.writeln(&format!("irun: {}: {}", f, x));
+                status = 1;
+            }
+        }
+        status
+    }
+}
+
+// Carries out a command's redirections, in order, before it runs.
+// Like bash, every file written to is created (or truncated) here,
+// but only the last one for each stream gets the output.
+fn redirect(
+    sh: &Session,
+    redirs: &[Redir],
+    mut stdin: Option<String>,
+) -> Result<Redirected, String> {
+    use RedirKind::*;
+    let c = sh.vars.caller();
+    let mut out = Dest::Stdout;
+    let mut err = Dest::Stderr;
+    for r in redirs {
+        if r.kind == ErrToOut {
+            err = out.clone();
+            continue;
+        }
+        let path = match expand::expand_word(sh, &r.target)?.as_slice() {
+            [p] => p.clone(),
+            _ => {
+                return Err(format!(
+                    "{}: ambiguous redirect",
+                    expand::expand_str(sh, &r.target)?
+                ))
+            }
+        };
+        if r.kind == In {
+            match vfs::futils::read_file(&c, path.clone()) {
+                Ok(d) => stdin = Some(String::from_utf8_lossy(&d).to_string()),
+                Err(e) => return Err(format!("{}: {}", path, e.errno())),
+            }
+            continue;
+        }
+        let append = r.kind == Append || r.kind == ErrAppend;
+        vfs::futils::write_file(&c, &path, &[], append).map_err(|e| format!("{}: {}", path, e))?;
+        if r.kind == Out || r.kind == Append {
+            out = Dest::File(path);
+        } else {
+            err = Dest::File(path);
+        }
+    }
+    // one buffer per file, so that 2>&1 into a file interleaves
+    let mut files: Vec<(String, Buffer)> = vec![];
+    for d in [&out, &err] {
+        if let Dest::File(f) = d {
+            if !files.iter().any(|x| x.0 == *f) {
+                files.push((f.clone(), Buffer::new()));
+            }
+        }
+    }
+    Ok(Redirected {
+        stdin,
+        out,
+        err,
+        files,
+    })
+}
+
+type Assignments = Vec<(String, String)>;
+
+// splits a simple command into its leading NAME=value
+// assignments and the expanded argv
+fn expand_simple(sh: &Session, cmd: &Simple) -> Result<(Assignments, Vec<String>), String> {
+    let mut vals = vec![];
+    let mut argv = vec![];
+    let mut words = cmd.words.iter().peekable();
+    while let Some((k, v)) = words.peek().and_then(|w| w.assignment()) {
+        vals.push((k, expand::expand_str(sh, &v)?));
+        words.next();
+    }
+    for w in words {
+        argv.extend(expand::expand_arg(sh, w)?);
+    }
+    Ok((vals, argv))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::process::run_task_test;
+
+    fn sh(src: &str) -> (i32, String, String) {
+        run_task_test(|p| Box::pin(crate::builtins::sh(p)), &["-c", src], None)
+    }
+
+    fn out(src: &str) -> String {
+        sh(src).1
+    }
+
+    #[test]
+    fn control_flow() {
+        crate::vfs::mount_test_fs();
+        let _l = crate::process::shell_lock();
+        assert_eq!(
+            out("if false; then echo a; elif true; then echo b; else echo c; fi"),
+            "b\n"
+        );
+        assert_eq!(out("if false; then echo a; fi; echo $?"), "0\n");
+        assert_eq!(
+            out("i=0; while [ $i != 3 ]; do echo $i; i=${i}x; [ $i = 0xx ] && i=3; done"),
+            "0\n0x\n"
+        );
+        assert_eq!(out("until true; do echo no; done; echo yes"), "yes\n");
+        assert_eq!(out("for x in a 'b c'; do echo $x; done"), "a\nb c\n");
+        assert_eq!(
+            out("set_pos() { for x; do echo $x; done; }; set_pos 1 2"),
+            "1\n2\n"
+        );
+        assert_eq!(
+            out(
+                "for a in 1 2; do for b in x y; do [ $b = y ] && continue 2; echo $a$b; done; done"
+            ),
+            "1x\n2x\n"
+        );
+        assert_eq!(
+            out("for a in 1 2 3; do [ $a = 2 ] && break; echo $a; done"),
+            "1\n"
+        );
+        assert_eq!(
+            out("for w in a.txt b.rs c; do case $w in *.txt|*.md) echo t;; *.rs) echo r;; *) echo o;; esac; done"),
+            "t\nr\no\n"
+        );
+        assert_eq!(
+            out("x='*'; case a in \"$x\") echo q;; $x) echo u;; esac"),
+            "u\n"
+        );
+        assert_eq!(out("! false && echo ok"), "ok\n");
+        assert_eq!(out("(cd /; exit 3); echo $?"), "3\n");
+        assert_eq!(out("{ echo a; echo b; } | cat"), "a\nb\n");
+    }
+
+    // at the prompt, exit in a subshell or a pipeline only ends that
+    #[test]
+    fn exit_in_subshell() {
+        crate::vfs::mount_test_fs();
+        let _l = crate::process::shell_lock();
+        let sh = crate::session::Session::new(2);
+        // as if su had been run
+        sh.vars.push_session();
+        let out = crate::process::Buffer::new();
+        crate::process::block_on(crate::run_shell_instruction(
+            &sh,
+            &out,
+            &out,
+            "(exit 1); echo $?; exit 2 | true; echo x | exit 5; echo $?",
+        ));
+        assert_eq!(out.take(), "1\n5\n");
+        assert!(!sh.hung_up());
+        assert_eq!(super::flow(&sh), None);
+        assert!(sh.vars.pop_session());
+    }
+
+    #[test]
+    fn functions() {
+        crate::vfs::mount_test_fs();
+        let _l = crate::process::shell_lock();
+        assert_eq!(out("f() { echo \"$1-$#\"; }; f a b; echo $1"), "a-2\n\n");
+        assert_eq!(out("f() { return 4; echo no; }; f; echo $?"), "4\n");
+        assert_eq!(
+            out("X=g; f() { local X=l; echo $X; }; f; echo $X"),
+            "l\ng\n"
+        );
+        // debug builds take a lot more stack per call than the wasm one
+        let deep = std::thread::Builder::new()
+            .stack_size(16 << 20)
+            .spawn(|| out("f() { f; }; f; echo $?"))
+            .unwrap();
+        assert_eq!(deep.join().unwrap().lines().last(), Some("1"));
+        assert_eq!(out("f() { echo x; }; unset -f f; f; echo $?"), "127\n");
+        let (st, o, e) = sh("local x; break; echo $?");
+        assert_eq!((st, o.as_str()), (0, "0\n"));
+        assert!(e.contains("local: can only be used in a function"));
+        assert!(e.contains("break: only meaningful"));
+    }
+}
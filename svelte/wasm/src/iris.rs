@@ -0,0 +1,555 @@
+// Running IrisOS programs: WebAssembly modules kept in the VFS.
+//
+// A program is a wasm module that exports its `memory` and a
+// `_start()` to call, and imports whatever it needs from the "iris"
+// module below. Pointers are offsets into the program's own memory,
+// and every call that can fail gives back a negative errno.
+//
+//   write(fd, ptr, len) -> n       fd 1 is stdout, 2 is stderr
+//   read(fd, ptr, len) -> n        fd 0 is stdin; 0 means end of file
+//   open(path, path_len, flags) -> fd
+//       flags: 1 read, 2 write, 4 create, 8 truncate, 16 append
+//   close(fd) -> 0                 writes reach the VFS here
+//   argc() -> n
+//   argv(i, buf, len) -> n         copies up to len bytes of argument i;
+//                                  n is its full length
+//   getenv(name, name_len, buf, len) -> n    same, for a variable
+//   exit(code)                     doesn't return
+//   time() -> f64                  milliseconds since the Unix epoch
+//
+// A program runs on the page's one thread. Where the browser has JSPI
+// (WebAssembly.Suspending and promising), it stops at every syscall
+// for run() to show what it has written so far, let the browser have
+// a turn, and kill it if Ctrl-C was pressed. Elsewhere it runs in one
+// go, with its output shown once it's done. Either way, a program that
+// loops without making syscalls hangs the tab, and a program can't
+// wait for keys: at the terminal, stdin reads as empty. Pipe or
+// redirect something into it instead.
+
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::future::{poll_fn, Future};
+use std::pin::Pin;
+use std::rc::Rc;
+use std::task::{Poll, Waker};
+
+use js_sys::{Function, Object, Promise, Reflect, Uint8Array, WebAssembly};
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+use wasm_bindgen_futures::JsFuture;
+
+use crate::process::Sink;
+use crate::session::Session;
+use crate::sysvars::Caller;
+use crate::tty;
+use crate::vfs::{futils, perm, VfsErrno, VirtualFileSystem};
+
+pub const MAGIC: &[u8] = b"\0asm";
+
+const ENOENT: i32 = 2;
+const EIO: i32 = 5;
+const EBADF: i32 = 9;
+const EACCES: i32 = 13;
+const EFAULT: i32 = 14;
+const EISDIR: i32 = 21;
+const EINVAL: i32 = 22;
+const EMFILE: i32 = 24;
+
+const O_READ: i32 = 1;
+const O_WRITE: i32 = 2;
+const O_CREATE: i32 = 4;
+const O_TRUNC: i32 = 8;
+const O_APPEND: i32 = 16;
+
+const MAX_FILES: usize = 64;
+
+// Files are read in whole when they're opened and written back in
+// whole when they're closed.
+struct OpenFile {
+    path: String,
+    data: Vec<u8>,
+    pos: usize,
+    flags: i32,
+    dirty: bool,
+}
+
+#[derive(Default)]
+struct State {
+    // whoever ran it, which is who opens its files
+    caller: Caller,
+    argv: Vec<String>,
+    env: Vec<(String, String)>,
+    stdin: Vec<u8>,
+    stdin_pos: usize,
+    // what went to stdout and stderr, in order
+    out: Vec<(i32, Vec<u8>)>,
+    files: BTreeMap<i32, OpenFile>,
+    exit: Option<i32>,
+}
+
+impl State {
+    fn write(&mut self, fd: i32, data: &[u8]) -> i32 {
+        match fd {
+            1 | 2 => {
+                match self.out.last_mut() {
+                    Some((f, v)) if *f == fd => v.extend_from_slice(data),
+                    _ => self.out.push((fd, data.to_vec())),
+                }
+                data.len() as i32
+            }
+            _ => match self.files.get_mut(&fd) {
+                Some(f) if f.flags & O_WRITE != 0 => {
+                    if f.flags & O_APPEND != 0 {
+                        f.pos = f.data.len();
+                    }
+                    let end = f.pos + data.len();
+                    if f.data.len() < end {
+                        f.data.resize(end, 0);
+                    }
+                    f.data[f.pos..end].copy_from_slice(data);
+                    f.pos = end;
+                    f.dirty = true;
+                    data.len() as i32
+                }
+                _ => -EBADF,
+            },
+        }
+    }
+
+    fn read(&mut self, fd: i32, len: usize) -> Result<Vec<u8>, i32> {
+        let (data, pos) = match fd {
+            0 => (&self.stdin, &mut self.stdin_pos),
+            _ => match self.files.get_mut(&fd) {
+                Some(f) if f.flags & O_READ != 0 => (&f.data, &mut f.pos),
+                _ => return Err(EBADF),
+            },
+        };
+        let start = (*pos).min(data.len());
+        let end = (start + len).min(data.len());
+        *pos = end;
+        Ok(data[start..end].to_vec())
+    }
+
+    fn open(&mut self, path: &str, flags: i32) -> i32 {
+        if flags & (O_READ | O_WRITE) == 0 {
+            return -EINVAL;
+        }
+        if self.files.len() >= MAX_FILES {
+            return -EMFILE;
+        }
+        let writing = flags & O_WRITE != 0;
+        let mut f = OpenFile {
+            path: path.to_string(),
+            data: vec![],
+            pos: 0,
+            flags,
+            dirty: false,
+        };
+        if crate::script::file_perms(&self.caller, path).is_some_and(crate::script::is_dir) {
+            return -EISDIR;
+        }
+        match futils::resolve(&self.caller, path.to_string()) {
+            Ok((mut fs, mut fd)) => {
+                if writing && fs.access(&fd, perm::W).is_err() {
+                    return -EACCES;
+                }
+                if writing && flags & O_TRUNC != 0 {
+                    f.dirty = true;
+                } else {
+                    // what's there is kept, so it has to be read
+                    if fs.access(&fd, perm::R).is_err() {
+                        return -EACCES;
+                    }
+                    match fs.read_to_eof(&mut fd) {
+                        Some(d) => f.data = d,
+                        None => return -EIO,
+                    }
+                }
+            }
+            Err(VfsErrno::ENOENT) if writing && flags & O_CREATE != 0 => f.dirty = true,
+            Err(VfsErrno::EACCES) => return -EACCES,
+            Err(_) => return -ENOENT,
+        }
+        let fd = (3..).find(|n| !self.files.contains_key(n)).unwrap();
+        self.files.insert(fd, f);
+        fd
+    }
+
+    fn close(&mut self, fd: i32) -> i32 {
+        match self.files.remove(&fd) {
+            Some(f) if f.dirty => match futils::write_file(&self.caller, &f.path, &f.data, false) {
+                Ok(()) => 0,
+                Err(_) => -EIO,
+            },
+            Some(_) => 0,
+            None => -EBADF,
+        }
+    }
+
+    fn close_all(&mut self) {
+        let fds: Vec<i32> = self.files.keys().copied().collect();
+        for fd in fds {
+            self.close(fd);
+        }
+    }
+}
+
+// the state plus the memory, which only exists once the module does
+struct Ctx {
+    st: RefCell<State>,
+    mem: RefCell<Option<WebAssembly::Memory>>,
+    // with JSPI: how to go on (or fail) from the syscall the program
+    // is stopped at, and run() to tell it has stopped
+    resume: RefCell<Option<(Function, Function)>>,
+    waker: RefCell<Option<Waker>>,
+}
+
+impl Ctx {
+    fn bytes(&self, ptr: i32, len: i32) -> Result<Uint8Array, i32> {
+        let mem = self.mem.borrow();
+        let buf = Uint8Array::new(&mem.as_ref().ok_or(EFAULT)?.buffer());
+        let (ptr, len) = (ptr as u32, len as u32);
+        match ptr.checked_add(len) {
+            Some(end) if len as i32 >= 0 && end <= buf.length() => Ok(buf.subarray(ptr, end)),
+            _ => Err(EFAULT),
+        }
+    }
+    fn load(&self, ptr: i32, len: i32) -> Result<Vec<u8>, i32> {
+        Ok(self.bytes(ptr, len)?.to_vec())
+    }
+    fn store(&self, ptr: i32, data: &[u8]) -> Result<(), i32> {
+        self.bytes(ptr, data.len() as i32)?.copy_from(data);
+        Ok(())
+    }
+    // for argv and getenv: as much of s as fits, and its full length
+    fn give(&self, s: &str, buf: i32, len: i32) -> Result<i32, i32> {
+        let n = s.len().min(len.max(0) as usize);
+        self.store(buf, &s.as_bytes()[..n])?;
+        Ok(s.len() as i32)
+    }
+}
+
+fn errno(r: Result<i32, i32>) -> i32 {
+    r.unwrap_or_else(|e| -e)
+}
+
+fn imports(ctx: &Rc<Ctx>) -> Object {
+    let iris = Object::new();
+    let add = |name: &str, f: JsValue| {
+        Reflect::set(&iris, &name.into(), &f).unwrap();
+    };
+    let c = ctx.clone();
+    add(
+        "write",
+        Closure::<dyn Fn(i32, i32, i32) -> i32>::new(move |fd: i32, ptr: i32, len: i32| {
+            errno(c.load(ptr, len).map(|d| c.st.borrow_mut().write(fd, &d)))
+        })
+        .into_js_value(),
+    );
+    let c = ctx.clone();
+    add(
+        "read",
+        Closure::<dyn Fn(i32, i32, i32) -> i32>::new(move |fd: i32, ptr: i32, len: i32| {
+            let d = c.st.borrow_mut().read(fd, len.max(0) as usize);
+            errno(d.and_then(|d| c.store(ptr, &d).map(|_| d.len() as i32)))
+        })
+        .into_js_value(),
+    );
+    let c = ctx.clone();
+    add(
+        "open",
+        Closure::<dyn Fn(i32, i32, i32) -> i32>::new(move |ptr: i32, len: i32, flags: i32| {
+            errno(c.load(ptr, len).and_then(|p| match String::from_utf8(p) {
+                Ok(p) => Ok(c.st.borrow_mut().open(&p, flags)),
+                Err(_) => Err(EINVAL),
+            }))
+        })
+        .into_js_value(),
+    );
+    let c = ctx.clone();
+    add(
+        "close",
+        Closure::<dyn Fn(i32) -> i32>::new(move |fd: i32| c.st.borrow_mut().close(fd))
+            .into_js_value(),
+    );
+    let c = ctx.clone();
+    add(
+        "argc",
+        Closure::<dyn Fn() -> i32>::new(move || c.st.borrow().argv.len() as i32).into_js_value(),
+    );
+    let c = ctx.clone();
+    add(
+        "argv",
+        Closure::<dyn Fn(i32, i32, i32) -> i32>::new(move |i: i32, buf: i32, len: i32| {
+            let a = c.st.borrow().argv.get(i as usize).cloned();
+            errno(a.ok_or(EINVAL).and_then(|a| c.give(&a, buf, len)))
+        })
+        .into_js_value(),
+    );
+    let c = ctx.clone();
+    add(
+        "getenv",
+        Closure::<dyn Fn(i32, i32, i32, i32) -> i32>::new(
+            move |ptr: i32, plen: i32, buf: i32, len: i32| {
+                errno(c.load(ptr, plen).and_then(|name| {
+                    let st = c.st.borrow();
+                    let v = st.env.iter().find(|(k, _)| k.as_bytes() == name);
+                    let v = v.map(|x| x.1.clone()).ok_or(ENOENT)?;
+                    drop(st);
+                    c.give(&v, buf, len)
+                }))
+            },
+        )
+        .into_js_value(),
+    );
+    let c = ctx.clone();
+    // throwing is what stops the program
+    add(
+        "exit",
+        Closure::<dyn Fn(i32) -> Result<(), JsValue>>::new(move |code: i32| {
+            c.st.borrow_mut().exit = Some(code);
+            Err(JsValue::from_str("exit"))
+        })
+        .into_js_value(),
+    );
+    add(
+        "time",
+        Closure::<dyn Fn() -> f64>::new(js_sys::Date::now).into_js_value(),
+    );
+    if let Some(suspending) = jspi().map(|(s, _)| s) {
+        suspend_all(ctx, &iris, &suspending);
+    }
+    let imports = Object::new();
+    Reflect::set(&imports, &"iris".into(), &iris).unwrap();
+    imports
+}
+
+// WebAssembly.Suspending and WebAssembly.promising, if there's JSPI
+fn jspi() -> Option<(Function, Function)> {
+    let wasm = Reflect::get(&js_sys::global(), &"WebAssembly".into()).ok()?;
+    let get = |name: &str| {
+        Reflect::get(&wasm, &name.into())
+            .ok()
+            .and_then(|f| f.dyn_into::<Function>().ok())
+    };
+    Some((get("Suspending")?, get("promising")?))
+}
+
+// Makes every syscall stop the program once it's done, until run()
+// resumes it. Only the program waits; run() and the rest go on.
+fn suspend_all(ctx: &Rc<Ctx>, iris: &Object, suspending: &Function) {
+    let c = ctx.clone();
+    let stop = Closure::<dyn Fn() -> Promise>::new(move || {
+        Promise::new(&mut |resolve, reject| {
+            *c.resume.borrow_mut() = Some((resolve, reject));
+            if let Some(w) = c.waker.borrow_mut().take() {
+                w.wake();
+            }
+        })
+    })
+    .into_js_value();
+    let wrap = Function::new_with_args(
+        "f, stop, Suspending",
+        "return new Suspending(async (...a) => { const r = f(...a); await stop(); return r; })",
+    );
+    for name in Object::keys(iris).iter() {
+        let f = Reflect::get(iris, &name).unwrap();
+        let w = wrap.call3(&JsValue::NULL, &f, &stop, suspending).unwrap();
+        Reflect::set(iris, &name, &w).unwrap();
+    }
+}
+
+// what went to stdout and stderr since last time
+fn flush(ctx: &Ctx, out: &dyn Sink, err: &dyn Sink) {
+    for (fd, d) in ctx.st.borrow_mut().out.drain(..) {
+        let s = String::from_utf8_lossy(&d);
+        if fd == 1 {
+            out.write(&s);
+        } else {
+            err.write(&s);
+        }
+    }
+}
+
+// Runs the program through JSPI: each time it stops at a syscall, its
+// output so far is shown and the browser gets a turn. On Ctrl-C, the
+// syscall throws instead of going on, which ends it.
+async fn run_suspending(
+    sh: &Session,
+    ctx: &Ctx,
+    start: &Function,
+    promising: &Function,
+    out: &dyn Sink,
+    err: &dyn Sink,
+) -> Result<JsValue, JsValue> {
+    let start: Function = promising.call1(&JsValue::NULL, start)?.unchecked_into();
+    // this runs the program up to its first syscall
+    let mut done = JsFuture::from(Promise::from(start.call0(&JsValue::NULL)?));
+    loop {
+        let r = poll_fn(|cx| {
+            if let Poll::Ready(r) = Pin::new(&mut done).poll(cx) {
+                return Poll::Ready(Some(r));
+            }
+            if ctx.resume.borrow().is_some() {
+                return Poll::Ready(None);
+            }
+            *ctx.waker.borrow_mut() = Some(cx.waker().clone());
+            Poll::Pending
+        })
+        .await;
+        flush(ctx, out, err);
+        if let Some(r) = r {
+            return r;
+        }
+        tty::pause().await;
+        let Some((resolve, reject)) = ctx.resume.borrow_mut().take() else {
+            continue;
+        };
+        if sh.tty.interrupted() {
+            ctx.st.borrow_mut().exit = Some(130);
+            reject.call1(&JsValue::NULL, &"interrupted".into())?;
+        } else {
+            resolve.call0(&JsValue::NULL)?;
+        }
+    }
+}
+
+// what a JS exception says, e.g. "RuntimeError: unreachable"
+fn describe(e: &JsValue) -> String {
+    match e.dyn_ref::<js_sys::Error>() {
+        Some(e) => String::from(e.to_string()),
+        None => e.as_string().unwrap_or_else(|| format!("{:?}", e)),
+    }
+}
+
+async fn instantiate(data: &[u8], ctx: &Rc<Ctx>) -> Result<Function, String> {
+    let r = JsFuture::from(WebAssembly::instantiate_buffer(data, &imports(ctx)))
+        .await
+        .map_err(|e| describe(&e))?;
+    let inst: WebAssembly::Instance = Reflect::get(&r, &"instance".into())
+        .map_err(|e| describe(&e))?
+        .unchecked_into();
+    let exports = inst.exports();
+    let mem = Reflect::get(&exports, &"memory".into())
+        .ok()
+        .and_then(|m| m.dyn_into::<WebAssembly::Memory>().ok())
+        .ok_or("program doesn't export its memory")?;
+    *ctx.mem.borrow_mut() = Some(mem);
+    Reflect::get(&exports, &"_start".into())
+        .ok()
+        .and_then(|f| f.dyn_into::<Function>().ok())
+        .ok_or_else(|| "program has no _start".to_string())
+}
+
+pub async fn run(
+    sh: &Session,
+    data: &[u8],
+    argv: &[String],
+    stdin: Option<String>,
+    out: &dyn Sink,
+    err: &dyn Sink,
+) -> i32 {
+    let ctx = Rc::new(Ctx {
+        st: RefCell::new(State {
+            caller: sh.caller(),
+            argv: argv.to_vec(),
+            env: sh.vars.exported(),
+            stdin: stdin.unwrap_or_default().into_bytes(),
+            ..Default::default()
+        }),
+        mem: RefCell::new(None),
+        resume: RefCell::new(None),
+        waker: RefCell::new(None),
+    });
+    let start = match instantiate(data, &ctx).await {
+        Ok(f) => f,
+        Err(e) => {
+            err.writeln(&format!("irun: {}: {}", argv[0], e));
+            return 126;
+        }
+    };
+    let r = match jspi() {
+        Some((_, promising)) => run_suspending(sh, &ctx, &start, &promising, out, err).await,
+        None => start.call0(&JsValue::UNDEFINED),
+    };
+    flush(&ctx, out, err);
+    let mut st = ctx.st.borrow_mut();
+    st.close_all();
+    match (st.exit, r) {
+        (Some(code), _) => code & 255,
+        (None, Ok(_)) => 0,
+        // a trap; like a program killed by a signal
+        (None, Err(e)) => {
+            err.writeln(&format!("irun: {}: {}", argv[0], describe(&e)));
+            134
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn files_and_stdio() {
+        crate::vfs::mount_test_fs();
+        let root = Caller::root();
+        let mut st = State {
+            caller: root.clone(),
+            stdin: b"in".to_vec(),
+            ..Default::default()
+        };
+        assert_eq!(st.write(1, b"a"), 1);
+        st.write(1, b"b");
+        st.write(2, b"c");
+        assert_eq!(st.out, vec![(1, b"ab".to_vec()), (2, b"c".to_vec())]);
+        assert_eq!(st.read(0, 10), Ok(b"in".to_vec()));
+        assert_eq!(st.read(0, 10), Ok(vec![]));
+        assert_eq!(st.read(1, 1), Err(EBADF));
+        assert_eq!(st.write(0, b"x"), -EBADF);
+
+        assert_eq!(st.open("/globd/nope", O_READ), -ENOENT);
+        assert_eq!(st.open("/globd/sub", O_READ), -EISDIR);
+        assert_eq!(st.open("/globd/a.txt", 0), -EINVAL);
+        let fd = st.open("/iris.txt", O_WRITE | O_CREATE);
+        assert_eq!(fd, 3);
+        assert_eq!(st.read(fd, 1), Err(EBADF));
+        assert_eq!(st.write(fd, b"hello"), 5);
+        assert_eq!(st.close(fd), 0);
+        assert_eq!(st.close(fd), -EBADF);
+        let fd = st.open("/iris.txt", O_READ | O_WRITE | O_APPEND);
+        assert_eq!(st.read(fd, 3), Ok(b"hel".to_vec()));
+        st.write(fd, b"!");
+        st.close_all();
+        assert_eq!(
+            futils::read_to_end(&root, "/iris.txt".to_string()),
+            Some(b"hello!".to_vec())
+        );
+        let fd = st.open("/iris.txt", O_WRITE | O_TRUNC);
+        st.close(fd);
+        assert_eq!(
+            futils::read_to_end(&root, "/iris.txt".to_string()),
+            Some(vec![])
+        );
+
+        // what guest may not read isn't there to them, nor overwritten
+        let mut guest = State {
+            caller: Caller {
+                creds: crate::sysvars::Creds {
+                    uid: 1000,
+                    gid: 1000,
+                    groups: vec![],
+                },
+                ..root.clone()
+            },
+            ..Default::default()
+        };
+        futils::write_file(&root, "/iris.txt", b"root's", false).unwrap();
+        assert_eq!(guest.open("/private/secret", O_READ), -EACCES);
+        assert_eq!(guest.open("/iris.txt", O_WRITE | O_CREATE), -EACCES);
+        assert_eq!(
+            futils::read_to_end(&root, "/iris.txt".to_string()),
+            Some(b"root's".to_vec())
+        );
+    }
+}
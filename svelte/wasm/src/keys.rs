@@ -6,9 +6,16 @@ pub const KEY_RIGHT_ARROW: u32 = 39;
 pub const KEY_DOWN_ARROW: u32 = 40;
 
 pub const KEY_C: u32 = 67;
+pub const KEY_D: u32 = 68;
 pub const KEY_L: u32 = 76;
 
-pub const CURSOR_LEFT: &str = "\x1b[D";
-pub const CURSOR_RIGHT: &str = "\x1b[C";
-pub const CURSOR_BACKSPACE: &str = "\u{0008} \u{0008}";
+pub const KEY_F1: u32 = 112;
+
 pub const CURSOR_BELL: &str = "\u{0007}";
+pub const ERASE_BELOW: &str = "\x1b[J";
+pub const ERASE_LINE: &str = "\x1b[2K";
+pub const CURSOR_UP: &str = "\x1b[A";
+
+pub const BRACKETED_PASTE_ON: &str = "\x1b[?2004h";
+pub const PASTE_START: &str = "\x1b[200~";
+pub const PASTE_END: &str = "\x1b[201~";
@@ -0,0 +1,227 @@
+// The kernel log. Every kernel message goes into a ring buffer as well
+// as onto the screen, so boot messages and mounts can still be read
+// with dmesg, or from /dev/kmsg, once they've scrolled away.
+
+use std::cell::Cell;
+use std::collections::VecDeque;
+
+use colored::Colorize;
+
+use crate::process::Sink;
+
+// syslog levels, by the names dmesg -l takes
+pub const LEVELS: [&str; 8] = [
+    "emerg", "alert", "crit", "err", "warn", "notice", "info", "debug",
+];
+pub const EMERG: u8 = 0;
+pub const WARN: u8 = 4;
+pub const INFO: u8 = 6;
+
+// past this many, the oldest messages fall off
+const MAX_LEN: usize = 1024;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Message {
+    // counts up from 0 since boot, even past what fell off
+    pub seq: u64,
+    // microseconds since boot
+    pub usec: u64,
+    pub level: u8,
+    pub text: String,
+}
+
+#[derive(Default)]
+struct Ring {
+    msgs: VecDeque<Message>,
+    next: u64,
+}
+
+// Not a Mutex: wasm can't unwind, so a lock held when it panicked
+// would stay held, and the panic screen and the reboot would both
+// panic on it again. The ring is taken out while it's used; after a
+// panic in between, the log just starts over.
+thread_local! {
+    static RING: Cell<Ring> = const {
+        Cell::new(Ring {
+            msgs: VecDeque::new(),
+            next: 0,
+        })
+    };
+}
+
+fn ring<R>(f: impl FnOnce(&mut Ring) -> R) -> R {
+    let mut r = RING.take();
+    let x = f(&mut r);
+    RING.set(r);
+    x
+}
+
+// microseconds since boot
+pub fn now() -> u64 {
+    crate::TSC.elapsed().as_micros() as u64
+}
+
+// the [    1.234567] in front of a message
+pub fn stamp(usec: u64) -> String {
+    format!("[{:>5}.{:06}] ", usec / 1_000_000, usec % 1_000_000)
+}
+
+// adds a message to the log; gives when it was logged
+pub fn log(level: u8, text: &str) -> u64 {
+    let usec = now();
+    let text = text.to_string();
+    ring(|r| {
+        let seq = r.next;
+        r.next += 1;
+        if r.msgs.len() == MAX_LEN {
+            r.msgs.pop_front();
+        }
+        r.msgs.push_back(Message {
+            seq,
+            usec,
+            level: level.min(7),
+            text,
+        });
+    });
+    usec
+}
+
+// how a message of this level looks on the terminal
+pub fn color(level: u8, text: &str) -> String {
+    match level {
+        0..=2 => text.bright_red().bold().to_string(),
+        3 => text.red().to_string(),
+        4 => text.yellow().to_string(),
+        _ => text.to_string(),
+    }
+}
+
+// logs a message and shows it on out
+pub fn print(out: &dyn Sink, level: u8, text: &str) {
+    let usec = log(level, text);
+    out.write(&stamp(usec));
+    out.writeln(&color(level, text));
+}
+
+// everything still in the buffer, oldest first
+pub fn messages() -> Vec<Message> {
+    ring(|r| r.msgs.iter().cloned().collect())
+}
+
+pub fn clear() {
+    ring(|r| r.msgs.clear());
+}
+
+// /dev/kmsg reads as a line a message, "level,seq,usec,-;text",
+// like on Linux
+pub fn read_dev() -> Vec<u8> {
+    let mut out = String::new();
+    for m in messages() {
+        for (i, l) in m.text.lines().enumerate() {
+            if i == 0 {
+                out += &format!("{},{},{},-;{}\n", m.level, m.seq, m.usec, l);
+            } else {
+                // continuation lines, indented, as Linux does
+                out += &format!(" {}\n", l);
+            }
+        }
+    }
+    out.into_bytes()
+}
+
+// Each line written to /dev/kmsg is logged; a "<N>" in front of it
+// gives the level, and it's a warning otherwise.
+pub fn write_dev(buf: &[u8]) {
+    for l in String::from_utf8_lossy(buf).lines() {
+        let level = l
+            .strip_prefix('<')
+            .and_then(|r| r.split_once('>'))
+            .and_then(|(n, t)| Some((n.parse::<u8>().ok()?, t)));
+        match level {
+            Some((n, t)) => log(n, t),
+            None => log(WARN, l),
+        };
+    }
+}
+
+// milliseconds since the epoch, and minutes east of UTC
+#[cfg(target_arch = "wasm32")]
+fn wall() -> (f64, i64) {
+    let d = js_sys::Date::new_0();
+    (d.get_time(), -(d.get_timezone_offset() as i64))
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn wall() -> (f64, i64) {
+    let ms = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0.0, |d| d.as_millis() as f64);
+    (ms, 0)
+}
+
+// "Mon Oct 19 08:12:01 2026", in local time, for a number of seconds
+// since the epoch
+fn ctime(secs: i64) -> String {
+    const DAYS: [&str; 7] = ["Thu", "Fri", "Sat", "Sun", "Mon", "Tue", "Wed"];
+    const MONTHS: [&str; 12] = [
+        "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+    ];
+    let (days, t) = (secs.div_euclid(86400), secs.rem_euclid(86400));
+    // days to a civil date, after Howard Hinnant
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let d = doy - (153 * mp + 2) / 5 + 1;
+    let m = if mp < 10 { mp + 3 } else { mp - 9 };
+    let y = yoe + era * 400 + (m <= 2) as i64;
+    format!(
+        "{} {} {:>2} {:02}:{:02}:{:02} {}",
+        DAYS[days.rem_euclid(7) as usize],
+        MONTHS[m as usize - 1],
+        d,
+        t / 3600,
+        t / 60 % 60,
+        t % 60,
+        y
+    )
+}
+
+// when a message was logged, by the clock on the wall
+pub fn wall_time(usec: u64) -> String {
+    let (ms, tz) = wall();
+    let boot = ms / 1000.0 - now() as f64 / 1e6;
+    ctime((boot + usec as f64 / 1e6) as i64 + tz * 60)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dates() {
+        assert_eq!(ctime(0), "Thu Jan  1 00:00:00 1970");
+        assert_eq!(ctime(951825600), "Tue Feb 29 12:00:00 2000");
+        assert_eq!(ctime(1792397521), "Mon Oct 19 08:12:01 2026");
+    }
+
+    // as if it panicked with the ring out; natively that unwinds, but
+    // the ring isn't put back either way
+    #[test]
+    fn panic_midway() {
+        log(INFO, "before");
+        let r = std::panic::catch_unwind(|| {
+            ring::<()>(|r| {
+                r.msgs.clear();
+                panic!("oops")
+            })
+        });
+        assert!(r.is_err());
+        log(INFO, "after");
+        let msgs = messages();
+        assert_eq!(msgs.len(), 1);
+        assert_eq!((msgs[0].seq, msgs[0].text.as_str()), (0, "after"));
+    }
+}
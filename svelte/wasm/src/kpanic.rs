@@ -0,0 +1,99 @@
+// Kernel panics. A panic anywhere in Rust code (one of the many
+// unwraps, say) used to leave a terminal that just stopped answering.
+// Now it gets a Linux-style panic screen, and the next key reboots by
+// running main() again.
+//
+// wasm can't unwind, so whatever was running when it panicked simply
+// stops, possibly in the middle of borrowing something. Nothing from
+// before the panic may run again: callbacks and tasks hold the id of
+// the boot they belong to, and give up once it isn't alive anymore.
+
+use std::panic::PanicHookInfo;
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+
+use colored::Colorize;
+use xterm_js_rs::Terminal;
+
+use crate::kmsg;
+
+static BOOT: AtomicU32 = AtomicU32::new(0);
+static PANICKED: AtomicBool = AtomicBool::new(false);
+
+// Starts a new boot, with the hook in place. The panic screen goes to
+// whichever console is on the screen.
+pub fn install() {
+    std::panic::set_hook(Box::new(hook));
+    PANICKED.store(false, Ordering::Relaxed);
+    BOOT.fetch_add(1, Ordering::Relaxed);
+}
+
+// the id of the boot going on now
+pub fn boot_id() -> u32 {
+    BOOT.load(Ordering::Relaxed)
+}
+
+pub fn panicked() -> bool {
+    PANICKED.load(Ordering::Relaxed)
+}
+
+// whether things started during boot `id` may still go on
+pub fn alive(id: u32) -> bool {
+    !panicked() && BOOT.load(Ordering::Relaxed) == id
+}
+
+fn message(info: &PanicHookInfo) -> String {
+    let p = info.payload();
+    match p.downcast_ref::<&str>() {
+        Some(s) => s.to_string(),
+        None => p
+            .downcast_ref::<String>()
+            .cloned()
+            .unwrap_or_else(|| "Box<dyn Any>".to_string()),
+    }
+}
+
+fn hook(info: &PanicHookInfo) {
+    // the browser console still gets the whole story
+    console_error_panic_hook::hook(info);
+    // panicking while showing a panic can't be helped any further
+    if PANICKED.swap(true, Ordering::Relaxed) {
+        return;
+    }
+    let msg = message(info);
+    let loc = info
+        .location()
+        .map_or("unknown location".to_string(), |l| l.to_string());
+    crate::vt::with_active(|t| show(t, &msg, &loc));
+}
+
+fn show(term: &Terminal, msg: &str, loc: &str) {
+    let first = msg.lines().next().unwrap_or("");
+    term.write("\r\n");
+    let head = format!("Kernel panic - not syncing: {}", first);
+    kmsg::print(term, kmsg::EMERG, &head);
+    for l in msg.lines().skip(1) {
+        crate::kmessage(term, l);
+    }
+    crate::kmessage(
+        term,
+        &format!(
+            "CPU: 0 PID: 1 Comm: irun Not tainted {} #1",
+            env!("CARGO_PKG_VERSION")
+        ),
+    );
+    crate::kmessage(term, &format!("RIP: {}", loc));
+    let end = format!("---[ end Kernel panic - not syncing: {} ]---", first);
+    kmsg::print(term, kmsg::EMERG, &end);
+    term.writeln("");
+    term.writeln(&format!("Press {} to reboot.", "any key".bold()));
+}
+
+// The key after a panic. The old consoles go, and their shells with
+// them, since each console's Irun owns its shell's Session. main() then
+// builds it all again, remounting the dummy root as it boots.
+pub fn reboot() {
+    crate::vt::reset();
+    if let Err(e) = crate::main() {
+        crate::log(&format!("reboot failed: {:?}", e));
+    }
+}
@@ -1,38 +1,53 @@
 mod builtins;
 mod common;
+mod editor;
 mod errors;
+mod expand;
+mod glob;
+mod history;
 mod instant;
+mod interp;
+mod iris;
 mod keys;
+mod kmsg;
+mod kpanic;
+mod linebuf;
 mod nanotools;
+mod pager;
+mod parse;
+mod process;
+mod prompt;
+mod roff;
+mod script;
+mod session;
 mod sysvars;
+mod theme;
+mod tty;
 mod unix;
+mod users;
 mod vfs;
+mod vt;
 
-use std::sync::atomic::{AtomicBool, Ordering};
+use std::cell::RefCell;
+use std::rc::{Rc, Weak};
 
 use colored::Colorize;
 use once_cell::sync::Lazy;
 use wasm_bindgen::prelude::*;
 use xterm_js_rs::addons::fit::FitAddon;
 use xterm_js_rs::addons::web_links::WebLinksAddon;
-use xterm_js_rs::keys::BellStyle;
-use xterm_js_rs::{OnKeyEvent, Terminal, TerminalOptions, Theme};
+use xterm_js_rs::{OnKeyEvent, Terminal};
 
 use keys::*;
+use process::Sink;
+use session::Session;
 
-static mut ADDON: Lazy<FitAddon> = Lazy::new(|| FitAddon::new());
 static TSC: Lazy<instant::Instant> = Lazy::new(|| instant::Instant::now());
-static IN_IRUN: AtomicBool = AtomicBool::new(true);
-
-const MAX_HIST_LEN: usize = 1000;
 
+// fits the console on the screen to the page, as it is now
 #[wasm_bindgen]
 pub fn fit() -> () {
-    // because we aren't using any threading,
-    // this is safe. if threading is implemented
-    // in the future, use an external mutex-based lock
-    // or a spinlock. Safety analysis: PASS
-    unsafe { ADDON.fit() };
+    vt::fit();
 }
 
 #[wasm_bindgen]
@@ -41,48 +56,147 @@ extern "C" {
     fn log(s: &str);
 }
 
+// localStorage, for what's kept between visits (history, the look)
+#[cfg(target_arch = "wasm32")]
+pub fn storage() -> Option<web_sys::Storage> {
+    web_sys::window()?.local_storage().ok()?
+}
+
+// natively (in tests) nothing is kept
+#[cfg(not(target_arch = "wasm32"))]
+pub fn storage() -> Option<web_sys::Storage> {
+    None
+}
+
 // TODO: get rid of panics/?/unwraps whenever unnecessary/avoidable
 #[wasm_bindgen(start)]
 pub fn main() -> Result<(), JsValue> {
     colored::control::set_override(true);
-    // TODO: custom panic handler with kmessage/kernel panic
-    console_error_panic_hook::set_once();
-    //addon = FitAddon::new();
-    let term: Terminal = Terminal::new(
-        TerminalOptions::new()
-            .with_cursor_blink(true)
-            .with_cursor_width(10)
-            .with_font_size(20)
-            .with_right_click_selects_word(true)
-            .with_draw_bold_text_in_bright_colors(true)
-            .with_font_family("Inconsolata")
-            .with_convert_eol(true)
-            .with_bell_style(BellStyle::Both)
-            .with_theme(
-                Theme::new()
-                    .with_foreground("#f5f1e3")
-                    .with_background("#191a22"),
-            ),
-    );
-    let el = web_sys::window()
-        .unwrap()
-        .document()
+    // from here on, a panic shows up on the terminal
+    kpanic::install();
+    // the other consoles come when they're switched to; see vt.rs
+    open_console(1)?;
+    vt::activate(1);
+    Ok(())
+}
+
+// Makes console n: a terminal of its own in the page, with a shell on
+// it. tty1 boots the system; the others start a shell on it.
+fn open_console(n: usize) -> Result<(), JsValue> {
+    let boot_id = kpanic::boot_id();
+    let doc = web_sys::window().unwrap().document().unwrap();
+    let el: web_sys::HtmlElement = doc.create_element("div")?.dyn_into()?;
+    el.style().set_property("height", "100%")?;
+    doc.get_element_by_id("terminal")
         .unwrap()
-        .get_element_by_id("terminal")
-        .unwrap();
-    term.open(el.dyn_into()?);
+        .append_child(&el)?;
+    let term: Terminal = Terminal::new(&theme::options());
+    term.open(el.clone());
+    let fit = FitAddon::new();
+    term.load_addon(fit.clone().dyn_into::<FitAddon>()?.into());
+    term.load_addon(WebLinksAddon::new(None, None, None).into());
+    vt::add(n, &term, fit, el);
 
     // The following block is the official definition
     // for IrisOS-nano. Initially, this is just going to
-    // be a cool terminal applet; however, it may eventually
-    // be able to emulate the running of some IrisOS programs.
+    // be a cool terminal applet; however, it can also run
+    // IrisOS programs built for WebAssembly (see iris.rs).
     // Note that some functions are defined outside for convenience.
 
     // BEGIN IrisOS-nano
-    kmessage_instr(&term, "uname -a");
-    kmessage(&term, "tsc: initialized TSC via performance_now");
+    let st: Terminal = Terminal::from(term.clone());
+    // ask xterm to wrap pastes in ESC[200~ ... ESC[201~
+    term.write(BRACKETED_PASTE_ON);
+    let ed = editor::LineEditor::new(term.get_cols() as usize);
+    // starting up is the first thing that runs; the prompt comes after
+    let irun = Rc::new_cyclic(|me| {
+        RefCell::new(Irun {
+            term,
+            ed,
+            last_key: None,
+            pending: String::new(),
+            running: true,
+            sh: Rc::new(Session::new(n)),
+            boot_id,
+            me: me.clone(),
+        })
+    });
+    irun.borrow().login();
+
+    // these callbacks are the primary code of irun
+    let ki = irun.clone();
+    let cb = Closure::wrap(Box::new(move |e: OnKeyEvent| {
+        // after a panic, any key reboots, once xterm is done with it
+        if !kpanic::alive(boot_id) {
+            if kpanic::panicked() {
+                wasm_bindgen_futures::spawn_local(async { kpanic::reboot() });
+            }
+            return;
+        }
+        // Alt+F1..F6, whatever is running
+        let ev = e.dom_event();
+        let k = ev.key_code().wrapping_sub(KEY_F1) as usize;
+        if ev.alt_key() && k < vt::CONSOLES {
+            ev.prevent_default();
+            vt::activate(k + 1);
+            return;
+        }
+        ki.borrow_mut().key(e);
+    }) as Box<dyn FnMut(_)>);
+    st.on_key(cb.as_ref().unchecked_ref());
+    cb.forget();
+    let di = irun.clone();
+    let db = Closure::wrap(Box::new(move |d: String| {
+        if !kpanic::alive(boot_id) {
+            return;
+        }
+        di.borrow_mut().data(d);
+    }) as Box<dyn FnMut(_)>);
+    st.on_data(db.as_ref().unchecked_ref());
+    db.forget();
+    // fit() (on a window resize, or a new font size) changes the size
+    let rt = Terminal::from(st.clone());
+    let ri = irun.clone();
+    let rb = Closure::wrap(Box::new(move |_: JsValue| {
+        // login() catches up with a resize that comes mid-key
+        if let Ok(i) = ri.try_borrow() {
+            i.sh.set_winsize(rt.get_cols() as usize, rt.get_rows() as usize);
+        }
+    }) as Box<dyn FnMut(_)>);
+    st.on_resize(rb.as_ref().unchecked_ref());
+    rb.forget();
+    // TODO: rootfs
+    // END IrisOS-nano
+    Ok(())
+}
+
+async fn boot(sh: &Session, term: &Terminal) {
+    kmessage_instr(sh, term, "uname -a").await;
+    kmessage(term, "tsc: initialized TSC via performance_now");
     vfs::mount_dummy();
-    kmessage(&term, "dummyfs: mounted initfs at /");
+    kmessage(term, "dummyfs: mounted initfs at /");
+    vfs::mount(
+        "dev",
+        Box::new(vfs::devfs::FileSystem::new(vec![
+            vfs::devfs::Device {
+                name: "kmsg",
+                // only root can log, as on Linux
+                mode: 0o644,
+                read: kmsg::read_dev,
+                write: kmsg::write_dev,
+            },
+            vfs::devfs::Device {
+                name: "null",
+                mode: 0o666,
+                read: Vec::new,
+                write: |_| {},
+            },
+        ])),
+    );
+    kmessage(term, "devfs: mounted at /dev");
+    sh.vars.seed_env();
+    script::load_rc(sh, term, term).await;
+    sh.hist.load();
     term.writeln(&format!("Welcome to {}!", "IrisOS-nano".bright_green()));
     term.writeln(&format!("Type {} for a list of commands.", "help".bold()));
     term.writeln(&format!(
@@ -94,163 +208,286 @@ pub fn main() -> Result<(), JsValue> {
         "Type {} for more information.",
         "iris-info".bold()
     ));
-    let mut ps1: &str = "$ ";
-    term.write(ps1);
-    let mut cb: String = String::new();
-    let mut cp: usize = 0;
-    let st: Terminal = Terminal::from(term.clone());
-    let mut hist: std::collections::VecDeque<String> = std::collections::VecDeque::new();
-    let mut chp: usize = usize::MAX;
-    let mut chp_ac: bool = false;
+}
 
-    // this callback is the primary code of irun
-    let cb = Closure::wrap(Box::new(move |e: OnKeyEvent| {
-        let ev = e.dom_event();
-        if !IN_IRUN.load(Ordering::Relaxed) {
-            kmessage(&term, "Kernel panic - piping stdin is not supported");
-            panic!();
-        }
-        // TODO: implement https://gist.github.com/tuxfight3r/60051ac67c5f0445efee
-        // TODO/BUG: don't print non-printable characters (breaks buffer horrendously)
-        match ev.key_code() {
-            KEY_ENTER => {
-                if cb.len() != 0 {
-                    for _ in cp..cb.len() {
-                        term.write(CURSOR_RIGHT);
-                    }
-                    term.writeln("");
-                    // TODO: store shell_instruction result for $?
-                    run_shell_instruction(&term, &cb.trim());
-                    if hist.len() == 0 || *hist.back().unwrap() != cb {
-                        hist.push_back(cb.clone());
+// what the other consoles start with, once tty1 has booted: a shell
+// like the one it was left with
+async fn getty(sh: &Session, term: &Terminal) {
+    sh.vars.seed_env();
+    script::load_rc(sh, term, term).await;
+    sh.hist.load();
+    term.writeln(&format!(
+        "{} {} tty{}",
+        "IrisOS-nano".bright_green(),
+        unix::uname::UNAME_NODENAME,
+        sh.n
+    ));
+    term.writeln("");
+}
+
+// The xterm side of irun: turns DOM key events into editor keys and
+// carries out what the editor asks for. See editor.rs for the rest.
+// While a command runs, keys go to it instead; see tty.rs.
+struct Irun {
+    term: Terminal,
+    ed: editor::LineEditor,
+    // xterm sends the data for every key through onData right after
+    // onKey; this is how we tell those apart from pastes and IME input
+    last_key: Option<String>,
+    // the lines so far of a command that needs more (an open quote,
+    // a trailing backslash or &&)
+    pending: String,
+    // whether a command is running, which it does as its own task
+    running: bool,
+    // the console's shell, which every command it runs is handed
+    sh: Rc<Session>,
+    // see kpanic.rs; a command from before a reboot can't come back
+    boot_id: u32,
+    me: Weak<RefCell<Irun>>,
+}
+impl Irun {
+    fn feed(&mut self, k: editor::Key) {
+        self.ed.set_cols(self.term.get_cols() as usize);
+        for op in self.ed.feed(k, &self.sh.hist) {
+            match op {
+                editor::Op::Write(s) => self.term.write(&s),
+                editor::Op::Bell => self.term.write(CURSOR_BELL),
+                editor::Op::ClearScreen => self.term.clear(),
+                editor::Op::Submit(l) => {
+                    if let Some(ps) = self.submit(l) {
+                        let p = self.prompt(ps);
+                        self.term.write(&self.ed.prompt(&p));
                     }
-                    cb.clear();
-                    term.write(ps1);
-                    cp = 0;
-                    chp = usize::MAX;
-                    chp_ac = false;
-                } else {
-                    term.writeln("");
-                    term.write(ps1);
                 }
-            }
-            KEY_BACKSPACE => {
-                if cp != 0 {
-                    term.write(CURSOR_BACKSPACE);
-                    cb.remove(cp - 1);
-                    cp -= 1;
-                    term.write(cb.get(cp..).unwrap());
-                    term.write(CURSOR_RIGHT);
-                    term.write(CURSOR_BACKSPACE);
-                    for _ in cp..cb.len() {
-                        term.write(CURSOR_LEFT);
-                    }
-                } else {
-                    term.write(CURSOR_BELL);
+                editor::Op::Cancel => {
+                    self.pending.clear();
+                    self.sh.vars.set_status(130);
+                    let p = self.prompt("PS1");
+                    self.term.write(&self.ed.prompt(&p));
                 }
             }
-            KEY_LEFT_ARROW => {
-                if cp != 0 {
-                    term.write(CURSOR_LEFT);
-                    cp -= 1;
-                } else {
-                    term.write(CURSOR_BELL);
-                }
+        }
+    }
+    // Runs a line from the editor, or holds on to it if the command
+    // isn't finished yet. Gives the prompt to show next, or None if a
+    // command started, in which case the prompt comes when it's done.
+    fn submit(&mut self, mut cb: String) -> Option<&'static str> {
+        let term = &self.term;
+        match self.sh.hist.expand(&cb) {
+            Ok(Some(l)) => {
+                term.writeln(&l);
+                cb = l;
             }
-            KEY_UP_ARROW => {
-                if chp == 0 {
-                    term.write(CURSOR_BELL);
-                } else if !chp_ac {
-                    if hist.len() == 0 {
-                        term.write(CURSOR_BELL);
-                    } else {
-                        rpos(&term, cp, &cb);
-                        chp = hist.len() - 1;
-                        chp_ac = true;
-                        term.write(&hist[chp]);
-                        hist.push_back(cb.clone());
-                        cb = hist[chp].clone();
-                        cp = cb.len();
-                        if hist.len() > MAX_HIST_LEN {
-                            chp -= 1;
-                            hist.pop_front();
-                        }
-                    }
-                } else {
-                    rpos(&term, cp, &cb);
-                    chp -= 1;
-                    term.write(&hist[chp]);
-                    cb = hist[chp].clone();
-                    cp = cb.len();
-                }
+            Ok(None) => {}
+            Err(e) => {
+                term.writeln(&format!("irun: {}", e));
+                self.pending.clear();
+                return Some("PS1");
             }
-            KEY_RIGHT_ARROW => {
-                if cp < cb.len() {
-                    term.write(CURSOR_RIGHT);
-                    cp += 1;
-                } else {
-                    term.write(CURSOR_BELL);
-                }
+        }
+        if !self.pending.is_empty() {
+            cb = format!("{}\n{}", self.pending, cb);
+        }
+        if parse::parse(&cb) == Err(parse::ParseError::Incomplete) {
+            self.pending = cb;
+            return Some("PS2");
+        }
+        self.pending.clear();
+        if cb.trim().is_empty() {
+            return Some("PS1");
+        }
+        // history is one line per entry
+        self.sh
+            .hist
+            .push(cb.replace("\\\n", "").replace('\n', " ").trim());
+        self.start(cb);
+        None
+    }
+    // Starts the console's shell, booting first on tty1. Like a
+    // command, it runs as a task, and the prompt comes after.
+    fn login(&self) {
+        let term = Terminal::from(self.term.clone());
+        let me = self.me.clone();
+        let (sh, id) = (self.sh.clone(), self.boot_id);
+        wasm_bindgen_futures::spawn_local(async move {
+            sh.set_winsize(term.get_cols() as usize, term.get_rows() as usize);
+            match sh.n {
+                1 => boot(&sh, &term).await,
+                _ => getty(&sh, &term).await,
             }
-            KEY_DOWN_ARROW => {
-                if !chp_ac || chp == hist.len() - 1 {
-                    term.write(CURSOR_BELL);
-                } else {
-                    rpos(&term, cp, &cb);
-                    chp += 1;
-                    term.write(&hist[chp]);
-                    cb = hist[chp].clone();
-                    cp = cb.len();
-                }
+            if !kpanic::alive(id) {
+                return;
+            }
+            if let Some(me) = me.upgrade() {
+                me.borrow_mut().finish();
+            }
+        });
+    }
+    fn start(&mut self, cmd: String) {
+        self.running = true;
+        self.sh.tty.reset();
+        let term = Terminal::from(self.term.clone());
+        let me = self.me.clone();
+        let (sh, id) = (self.sh.clone(), self.boot_id);
+        wasm_bindgen_futures::spawn_local(async move {
+            run_shell_instruction(&sh, &term, &term, &cmd).await;
+            if !kpanic::alive(id) {
+                return;
+            }
+            // a stray break or return at the prompt shouldn't linger
+            interp::clear_flow(&sh);
+            if let Some(me) = me.upgrade() {
+                me.borrow_mut().finish();
             }
+        });
+    }
+    // the command is done: back to the prompt, with whatever was
+    // typed meanwhile that it didn't read
+    fn finish(&mut self) {
+        // or, if the shell exited, to a new one on a clean screen
+        if self.sh.hung_up() {
+            self.sh = Rc::new(Session::new(self.sh.n));
+            self.pending.clear();
+            self.term.reset();
+            self.term.write(BRACKETED_PASTE_ON);
+            self.login();
+            return;
+        }
+        self.running = false;
+        let p = self.prompt("PS1");
+        self.term.write(&self.ed.prompt(&p));
+        let (lines, rest) = self.sh.tty.take_unread();
+        for l in lines {
+            self.input(&l);
+            self.enter();
+        }
+        self.input(&rest);
+    }
+    // typed text, for the editor or the running command
+    fn input(&mut self, s: &str) {
+        if s.is_empty() {
+            return;
+        }
+        if self.running {
+            self.term.write(s);
+            self.sh.tty.push(s);
+        } else {
+            self.feed(editor::Key::Text(s.to_string()));
+        }
+    }
+    fn enter(&mut self) {
+        if self.running {
+            self.term.write("\r\n");
+            self.sh.tty.newline();
+        } else {
+            self.feed(editor::Key::Enter);
+        }
+    }
+    // keys for a running command: a line at a time, like a tty
+    fn tty_key(&mut self, e: &OnKeyEvent) {
+        let ev = e.dom_event();
+        match ev.key_code() {
             KEY_C if ev.ctrl_key() => {
-                term.writeln("^C");
-                term.write(ps1);
-                cb.clear();
-                cp = 0;
-                chp = usize::MAX;
-                chp_ac = false;
+                self.term.write("^C\r\n");
+                self.sh.tty.interrupt();
+            }
+            KEY_D if ev.ctrl_key() => self.sh.tty.eof(),
+            KEY_ENTER => self.enter(),
+            KEY_BACKSPACE => {
+                if let Some(w) = self.sh.tty.backspace() {
+                    self.term.write(&"\x08 \x08".repeat(w));
+                }
             }
-            KEY_L if ev.ctrl_key() => term.clear(),
             _ => {
-                if !ev.alt_key() && !ev.ctrl_key() && !ev.meta_key() {
-                    term.write(&ev.key());
-                    cb.insert(cp, e.key().chars().next().unwrap());
-                    cp += 1;
-                    term.write(cb.get(cp..).unwrap());
-                    for _ in cp..cb.len() {
-                        term.write(CURSOR_LEFT);
-                    }
+                let k = ev.key();
+                if !(ev.alt_key() || ev.ctrl_key() || ev.meta_key()) && linebuf::is_printable(&k) {
+                    self.input(&k);
                 }
             }
         }
-    }) as Box<dyn FnMut(_)>);
-    st.on_key(cb.as_ref().unchecked_ref());
-    cb.forget();
-    // TODO: rootfs
-    // TODO: help command
-    // TODO: man pages
-    // END IrisOS-nano
+    }
+    fn prompt(&self, var: &str) -> String {
+        let ps = self.sh.vars.getenv(var).unwrap_or_default();
+        prompt::expand(&self.sh, &ps)
+    }
+    fn key(&mut self, e: OnKeyEvent) {
+        let ev = e.dom_event();
+        self.last_key = Some(e.key());
+        if self.running {
+            self.tty_key(&e);
+            return;
+        }
+        // TODO: implement https://gist.github.com/tuxfight3r/60051ac67c5f0445efee
+        let k = match ev.key_code() {
+            KEY_ENTER => editor::Key::Enter,
+            KEY_BACKSPACE => editor::Key::Backspace,
+            KEY_LEFT_ARROW => editor::Key::Left,
+            KEY_UP_ARROW => editor::Key::Up,
+            KEY_RIGHT_ARROW => editor::Key::Right,
+            KEY_DOWN_ARROW => editor::Key::Down,
+            KEY_C if ev.ctrl_key() => editor::Key::Interrupt,
+            KEY_L if ev.ctrl_key() => editor::Key::ClearScreen,
+            _ => {
+                let k = ev.key();
+                if ev.alt_key() || ev.ctrl_key() || ev.meta_key() || !linebuf::is_printable(&k) {
+                    return;
+                }
+                editor::Key::Text(k)
+            }
+        };
+        self.feed(k);
+    }
+    // Everything that arrives through onData without a matching onKey:
+    // bracketed pastes, IME composition results, on-screen keyboards.
+    // Complete lines are run one after another like bash does; whatever
+    // follows the last newline is left in the buffer for editing.
+    fn data(&mut self, d: String) {
+        if self.last_key.take().is_some_and(|k| k == d) {
+            return;
+        }
+        let d = d
+            .strip_prefix(PASTE_START)
+            .and_then(|x| x.strip_suffix(PASTE_END))
+            .unwrap_or(&d);
+        let clean = linebuf::sanitize(d);
+        let mut lines = clean.split('\n').peekable();
+        while let Some(l) = lines.next() {
+            self.input(l);
+            if lines.peek().is_some() {
+                self.enter();
+            }
+        }
+    }
+}
 
-    // This only runs when the module is being initialized.
-    // It will never be run on more than one thread - and
-    // in fact will only be run once. Safety analysis: PASS
-    unsafe {
-        st.load_addon(ADDON.clone().dyn_into::<FitAddon>()?.into());
-        st.load_addon(WebLinksAddon::new(None, None, None).into());
+type PathFn = fn(&process::Proc) -> i32;
+type TaskFn = for<'a> fn(&'a process::Proc<'a>) -> process::Task<'a>;
+
+// commands that run as tasks, since they wait on something
+fn check_task(exec: &str) -> Option<TaskFn> {
+    match exec {
+        "cat" => Some(|p| Box::pin(unix::cat::cat(p))),
+        "sleep" => Some(|p| Box::pin(unix::sleep::sleep(p))),
+        "dmesg" => Some(|p| Box::pin(unix::dmesg::dmesg(p))),
+        "su" => Some(|p| Box::pin(unix::su::su(p))),
+        "login" => Some(|p| Box::pin(unix::su::login(p))),
+        "man" => Some(|p| Box::pin(unix::man::man(p))),
+        "read" => Some(|p| Box::pin(builtins::read(p))),
+        "env" => Some(|p| Box::pin(builtins::env(p))),
+        "sh" => Some(|p| Box::pin(builtins::sh(p))),
+        "source" | "." => Some(|p| Box::pin(builtins::source(p))),
+        "loadwebroot" => Some(|p| Box::pin(nanotools::loadwebroot(p))),
+        "setup" => Some(|p| Box::pin(nanotools::setup(p))),
+        _ => None,
     }
-    st.focus();
-    Ok(())
 }
 
-type PathFn = fn(&Terminal, Vec<&str>) -> i32;
 fn check_path(exec: &str) -> Option<PathFn> {
     // VNP = Very Not POSIX
     // Working with these to make them POSIXy may help
     // MNP = Moderately Not POSIX
     match exec {
         "uname" => Some(unix::uname::uname),
-        "cat" => Some(unix::cat::cat),
         "ls" => Some(unix::ls::ls),
         "kmsg" => Some(nanotools::kmsg),
         "exit" => Some(builtins::exit),
@@ -258,8 +495,6 @@ fn check_path(exec: &str) -> Option<PathFn> {
         "nano" => Some(builtins::nano),
         "sanity-checks.infs" => Some(nanotools::test_infs),
         "sanity-checks.readroot" => Some(nanotools::test_read_root),
-        "loadwebroot" => Some(nanotools::loadwebroot),
-        "setup" => Some(nanotools::setup),
         "cd" => Some(unix::cd::cd),
         "pwd" => Some(unix::pwd::pwd),       // VNP
         "mv" => Some(unix::mv::mv),          // VNP
@@ -271,46 +506,89 @@ fn check_path(exec: &str) -> Option<PathFn> {
         "echo" => Some(unix::echo::echo),    // MNP
         "rmdir" => Some(unix::rmdir::rmdir), // VNP
         "help" => Some(nanotools::help),
+        "history" => Some(builtins::history),
+        "export" => Some(builtins::export),
+        "unset" => Some(builtins::unset),
+        "set" => Some(builtins::set),
+        "alias" => Some(builtins::alias),
+        "unalias" => Some(builtins::unalias),
+        "true" | ":" => Some(builtins::true_),
+        "false" => Some(builtins::false_),
+        "break" => Some(builtins::break_),
+        "continue" => Some(builtins::continue_),
+        "return" => Some(builtins::return_),
+        "local" => Some(builtins::local),
+        "umask" => Some(builtins::umask),
+        "test" => Some(unix::test::test),
+        "[" => Some(unix::test::bracket),
         "neofetch" => Some(nanotools::neofetch),
+        "whoami" => Some(unix::id::whoami),
+        "id" => Some(unix::id::id),
+        "groups" => Some(unix::id::groups),
+        "chmod" => Some(unix::chmod::chmod),
+        "chown" => Some(unix::chown::chown),
+        "chvt" => Some(unix::chvt::chvt),
+        "chgrp" => Some(unix::chown::chgrp),
+        "whatis" => Some(unix::man::whatis),
+        "apropos" => Some(unix::man::apropos),
+        "theme" => Some(nanotools::theme),
+        "setterm" => Some(unix::setterm::setterm),
+        "stty" => Some(unix::stty::stty),
+        "tput" => Some(unix::tput::tput),
         _ => None,
     }
 }
 
-// We don't need to support complex shell instructions...
-fn run_shell_instruction(term: &Terminal, instr: &str) -> i32 {
-    let mut v: Vec<&str> = instr.split(" ").collect();
-    match check_path(v[0]) {
-        Some(f) => f(term, v.drain(1..).collect()),
-        None => {
-            // in the future, this will search the instance rootfs
-            // for now, that's not yet implemented, so we just complain
-            term.writeln(format!("irun: {}: command not found...", v[0]).as_str());
-            return 127;
+// Parses and runs a whole command line; see parse.rs for the grammar.
+// Returns the exit status of the last command that ran, which is
+// also what $? gives afterwards.
+async fn run_shell_instruction(sh: &Session, out: &dyn Sink, err: &dyn Sink, instr: &str) -> i32 {
+    match parse::parse_aliased(instr, &|a| sh.vars.get_alias(a)) {
+        Ok(script) => interp::run_script(sh, out, err, &script).await,
+        Err(e) => {
+            err.writeln(&e.report(instr));
+            sh.vars.set_status(2);
+            return 2;
         }
     }
 }
 
-fn kmessage_instr(term: &Terminal, instr: &str) -> () {
-    term.write(&fmt_ktime());
-    run_shell_instruction(term, instr);
-}
-
-// TODO: write kernel messages to ring buffer
-fn kmessage(term: &Terminal, msg: &str) -> () {
-    term.write(&fmt_ktime());
-    term.writeln(msg);
+async fn exec(
+    sh: &Session,
+    argv: &[String],
+    stdin: Option<String>,
+    out: &dyn Sink,
+    err: &dyn Sink,
+) -> i32 {
+    // functions come before everything else, like in bash
+    if let Some(st) = interp::call(sh, out, err, argv).await {
+        return st;
+    }
+    if let Some(f) = check_task(&argv[0]) {
+        return f(&process::Proc::new(sh, &argv[1..], stdin, out, err)).await;
+    }
+    match check_path(&argv[0]) {
+        Some(f) => f(&process::Proc::new(sh, &argv[1..], stdin, out, err)),
+        None => match script::find_program(sh, &argv[0]) {
+            Some(path) => script::run_program(sh, &path, argv, stdin, out, err).await,
+            None => {
+                err.writeln(format!("irun: {}: command not found...", argv[0]).as_str());
+                return 127;
+            }
+        },
+    }
 }
 
-fn fmt_ktime() -> String {
-    let elapsed = TSC.elapsed();
-    format!("[{:>5}.{:06}] ", elapsed.as_secs(), elapsed.subsec_micros())
+// runs instr and logs what it prints as kernel messages
+async fn kmessage_instr(sh: &Session, term: &Terminal, instr: &str) -> () {
+    let out = process::Buffer::new();
+    run_shell_instruction(sh, &out, term, instr).await;
+    for l in out.take().lines() {
+        kmessage(term, l);
+    }
 }
 
-fn rpos(term: &Terminal, cp: usize, cb: &String) {
-    for _ in cp..cb.len() {
-        term.write(CURSOR_RIGHT);
-    }
-    for _ in 0..cb.len() {
-        term.write(CURSOR_BACKSPACE);
-    }
+// see kmsg.rs; this is the everyday kind, at info level
+fn kmessage(out: &dyn Sink, msg: &str) -> () {
+    kmsg::print(out, kmsg::INFO, msg);
 }
@@ -0,0 +1,320 @@
+// The irun line buffer.
+// The buffer is stored as grapheme clusters, not bytes or chars, so
+// that the cursor can never land in the middle of a character and
+// so that wide characters (CJK, most emoji) take up two cells on
+// screen. All "columns" here are display columns, counted from the
+// start of the prompt line.
+
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
+
+use crate::keys::*;
+
+pub struct LineBuffer {
+    g: Vec<String>,
+    // cursor position, in graphemes
+    cp: usize,
+}
+
+// ZWJ sequences and flags report the sum of their parts,
+// but terminals draw them as a single wide cell pair
+fn gwidth(g: &str) -> usize {
+    let w = g.width();
+    if g.chars().count() > 1 {
+        std::cmp::min(w, 2)
+    } else {
+        w
+    }
+}
+
+// Escape sequences (colors in a prompt, say) take up no room:
+// ESC [ ... up to a letter, ESC ] ... up to BEL or ESC \, or ESC and
+// one more character.
+fn strip_escapes(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    let mut it = s.chars().peekable();
+    while let Some(c) = it.next() {
+        if c != '\x1b' {
+            out.push(c);
+            continue;
+        }
+        match it.next() {
+            Some('[') => {
+                for c in it.by_ref() {
+                    if ('@'..='~').contains(&c) {
+                        break;
+                    }
+                }
+            }
+            Some(']') => {
+                while let Some(c) = it.next() {
+                    if c == '\x07' || (c == '\x1b' && it.next_if_eq(&'\\').is_some()) {
+                        break;
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    out
+}
+
+pub fn str_width(s: &str) -> usize {
+    if s.contains('\x1b') {
+        return strip_escapes(s).graphemes(true).map(gwidth).sum();
+    }
+    s.graphemes(true).map(gwidth).sum()
+}
+
+// Whether a DOM KeyboardEvent.key is something that should go into the
+// buffer. Named keys ("Shift", "F5", "ArrowLeft", "Dead", ...) are all
+// longer than one grapheme, so this filters them out.
+pub fn is_printable(key: &str) -> bool {
+    let mut gs = key.graphemes(true);
+    match (gs.next(), gs.next()) {
+        (Some(g), None) => !g.chars().any(|c| c.is_control()),
+        _ => false,
+    }
+}
+
+// Makes pasted text safe to put in the buffer: line endings become \n,
+// tabs become spaces, and escape sequences and any other control
+// characters are dropped entirely.
+pub fn sanitize(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    let mut it = s
+        .replace("\r\n", "\n")
+        .replace('\r', "\n")
+        .chars()
+        .collect::<Vec<char>>()
+        .into_iter()
+        .peekable();
+    while let Some(c) = it.next() {
+        match c {
+            '\n' => out.push('\n'),
+            '\t' => out.push(' '),
+            '\x1b' => {
+                // CSI sequences run until a final byte in @..~,
+                // anything else is a two-character escape
+                if it.next_if_eq(&'[').is_some() {
+                    for f in it.by_ref() {
+                        if ('@'..='~').contains(&f) {
+                            break;
+                        }
+                    }
+                } else {
+                    it.next();
+                }
+            }
+            _ if c.is_control() => {}
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+impl LineBuffer {
+    pub fn new() -> Self {
+        Self { g: vec![], cp: 0 }
+    }
+    pub fn text(&self) -> String {
+        self.g.concat()
+    }
+    // replaces the contents, leaving the cursor at the end
+    pub fn set(&mut self, s: &str) {
+        self.g = s.graphemes(true).map(|x| x.to_string()).collect();
+        self.cp = self.g.len();
+    }
+    pub fn clear(&mut self) {
+        self.g.clear();
+        self.cp = 0;
+    }
+    pub fn cursor(&self) -> usize {
+        self.cp
+    }
+    fn width_of(&self, from: usize, to: usize) -> usize {
+        self.g[from..to].iter().map(|x| gwidth(x)).sum()
+    }
+    pub fn width(&self) -> usize {
+        self.width_of(0, self.g.len())
+    }
+    pub fn cursor_width(&self) -> usize {
+        self.width_of(0, self.cp)
+    }
+    // Inserts text at the cursor. The text is re-segmented together
+    // with its neighbours, since a combining mark typed after a letter
+    // joins that letter's grapheme.
+    pub fn insert(&mut self, s: &str) {
+        let mut before: String = self.g[..self.cp].concat();
+        let after: String = self.g[self.cp..].concat();
+        before.push_str(s);
+        self.cp = before.graphemes(true).count();
+        before.push_str(&after);
+        self.g = before.graphemes(true).map(|x| x.to_string()).collect();
+        // the insertion may have merged into the grapheme after it
+        self.cp = std::cmp::min(self.cp, self.g.len());
+    }
+    pub fn backspace(&mut self) -> bool {
+        if self.cp == 0 {
+            return false;
+        }
+        self.cp -= 1;
+        self.g.remove(self.cp);
+        true
+    }
+    pub fn left(&mut self) -> bool {
+        if self.cp == 0 {
+            return false;
+        }
+        self.cp -= 1;
+        true
+    }
+    pub fn right(&mut self) -> bool {
+        if self.cp == self.g.len() {
+            return false;
+        }
+        self.cp += 1;
+        true
+    }
+    // Redraws everything from grapheme `from` onwards and puts the
+    // terminal cursor back where the buffer cursor is. `cur` is the
+    // column the terminal cursor is at right now, `pw` is the width
+    // of the prompt and `cols` the width of the terminal.
+    pub fn render_tail(&self, from: usize, cur: usize, pw: usize, cols: usize) -> String {
+        let mut s = move_cursor(cur, pw + self.width_of(0, from), cols);
+        s.push_str(&self.g[from..].concat());
+        let end = pw + self.width();
+        // xterm leaves the cursor hanging on the last cell after
+        // filling a row; force the wrap so our arithmetic holds
+        if end != 0 && end.is_multiple_of(cols) {
+            s.push_str("\r\n");
+        }
+        s.push_str(ERASE_BELOW);
+        s.push_str(&move_cursor(end, pw + self.cursor_width(), cols));
+        s
+    }
+}
+
+// Lays items out in columns, the way ls does: as many as fit in width,
+// each as wide as the widest item in it, two spaces apart. They go
+// down the columns first, or across the rows if across. A width of 0
+// puts them all on one line. Widths are display widths, so wide
+// characters and colors don't throw the columns off.
+pub fn columns(items: &[String], width: usize, across: bool) -> Vec<String> {
+    let lens: Vec<usize> = items.iter().map(|i| str_width(i)).collect();
+    let n = items.len();
+    if n == 0 {
+        return vec![];
+    }
+    // the most columns that fit, and how wide each is
+    let (mut rows, mut widths) = (n, vec![*lens.iter().max().unwrap()]);
+    for cols in (2..=n).rev() {
+        let r = n.div_ceil(cols);
+        // going down, r rows may not need all those columns
+        let cols = if across { cols } else { n.div_ceil(r) };
+        let mut w = vec![0; cols];
+        for (i, l) in lens.iter().enumerate() {
+            let c = if across { i % cols } else { i / r };
+            w[c] = w[c].max(*l);
+        }
+        if width == 0 || w.iter().sum::<usize>() + 2 * (cols - 1) <= width {
+            (rows, widths) = (r, w);
+            break;
+        }
+    }
+    let cols = widths.len();
+    (0..rows)
+        .map(|r| {
+            let mut line = String::new();
+            for (c, w) in widths.iter().enumerate() {
+                let i = if across { r * cols + c } else { c * rows + r };
+                if let Some(item) = items.get(i) {
+                    line.push_str(item);
+                    line.push_str(&" ".repeat(w - lens[i] + 2));
+                }
+            }
+            line.trim_end().to_string()
+        })
+        .collect()
+}
+
+// cursor movement between two columns that may be on different rows
+pub fn move_cursor(from: usize, to: usize, cols: usize) -> String {
+    let (fr, fc) = (from / cols, from % cols);
+    let (tr, tc) = (to / cols, to % cols);
+    let mut s = String::new();
+    if tr < fr {
+        s.push_str(&format!("\x1b[{}A", fr - tr));
+    } else if tr > fr {
+        s.push_str(&format!("\x1b[{}B", tr - fr));
+    }
+    if tc > fc {
+        s.push_str(&format!("\x1b[{}C", tc - fc));
+    } else if tc < fc {
+        s.push_str(&format!("\x1b[{}D", fc - tc));
+    }
+    s
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn printable_keys() {
+        assert!(is_printable("a"));
+        assert!(is_printable(" "));
+        assert!(is_printable("日"));
+        assert!(is_printable("👩‍💻"));
+        assert!(!is_printable("Shift"));
+        assert!(!is_printable("F5"));
+        assert!(!is_printable("ArrowLeft"));
+        assert!(!is_printable("\u{7f}"));
+        assert!(!is_printable(""));
+    }
+
+    #[test]
+    fn sanitize_paste() {
+        assert_eq!(sanitize("ls\r\ncat a\rb"), "ls\ncat a\nb");
+        assert_eq!(sanitize("a\tb\x07c"), "a bc");
+        assert_eq!(sanitize("\x1b[31mred\x1b[0m \x1b]x"), "red x");
+    }
+
+    #[test]
+    fn widths() {
+        assert_eq!(str_width("abc"), 3);
+        assert_eq!(str_width("日本"), 4);
+        assert_eq!(str_width("e\u{301}"), 1);
+        assert_eq!(str_width("👩‍💻"), 2);
+        assert_eq!(str_width("\x1b[1;32mok\x1b[0m"), 2);
+        assert_eq!(str_width("\x1b]0;title\x07$ \x1b]0;x\x1b\\"), 2);
+    }
+
+    #[test]
+    fn layout() {
+        let items: Vec<String> = ["a", "bb", "ccc", "dddd", "e", "f", "g"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+        assert_eq!(
+            columns(&items, 12, false),
+            ["a    dddd  g", "bb   e", "ccc  f"]
+        );
+        assert_eq!(
+            columns(&items, 12, true),
+            ["a    bb", "ccc  dddd", "e    f", "g"]
+        );
+        assert_eq!(columns(&items, 3, false).len(), 7);
+        assert_eq!(columns(&items, 0, false), ["a  bb  ccc  dddd  e  f  g"]);
+        assert!(columns(&[], 80, false).is_empty());
+        // wide and colored names take what they show as, not their length
+        let wide: Vec<String> = ["日本", "\x1b[34mab\x1b[0m", "c", "d"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+        assert_eq!(
+            columns(&wide, 8, false),
+            ["日本  c", "\x1b[34mab\x1b[0m    d"]
+        );
+    }
+}
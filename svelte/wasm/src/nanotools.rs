@@ -3,15 +3,18 @@
 // on full releases of IrisOS, or any non-experimental OS
 // for that matter.
 
+use crate::common::{Opt, Spec};
 use crate::errors::ar;
+use crate::process::Proc;
+use crate::theme;
 use crate::vfs::VirtualFileSystem;
 use colored::Colorize;
 use once_cell::sync::Lazy;
 use wasm_bindgen::JsCast;
-use xterm_js_rs::Terminal;
 
-pub fn kmsg(term: &Terminal, args: Vec<&str>) -> i32 {
-    crate::kmessage(term, &args.join(" "));
+pub fn kmsg(p: &Proc) -> i32 {
+    let args = p.args();
+    crate::kmessage(p.stdout, &args.join(" "));
     return 0;
 }
 
@@ -45,8 +48,8 @@ Source code: {}",
         "<https://github.com/amyipdev/amyip.net>".bright_black()
     )
 });
-pub fn iris_info(term: &Terminal, _args: Vec<&str>) -> i32 {
-    term.writeln(&INFO_MSG);
+pub fn iris_info(p: &Proc) -> i32 {
+    p.stdout.writeln(&INFO_MSG);
     return 0;
 }
 
@@ -83,9 +86,10 @@ pub fn loadwebroot(term: &Terminal, args: Vec<&str>) -> i32 {
     return 0;
 }*/
 
-pub fn loadwebroot(term: &Terminal, args: Vec<&str>) -> i32 {
+pub async fn loadwebroot(p: &Proc<'_>) -> i32 {
+    let args = p.args();
     if args.len() < 1 {
-        term.writeln("loadwebroot: no URL provided");
+        p.stderr.writeln("loadwebroot: no URL provided");
         return 1;
     }
     crate::vfs::mount_root(Box::new(
@@ -93,113 +97,252 @@ pub fn loadwebroot(term: &Terminal, args: Vec<&str>) -> i32 {
             binfetch_wasm::basic_fetch(args[0]),
             ah,
             -8,
-            term
+            p
         ))
         .unwrap_or_else(|| {
-            term.writeln("loadwebroot: something went wrong, failing safe");
+            p.stderr
+                .writeln("loadwebroot: something went wrong, failing safe");
             crate::vfs::infs::mknrfs(128, 4096, 1024)
         }),
     ));
+    crate::kmessage(p.stdout, "infs: mounted rootfs at /");
+    crate::script::load_rc(p.sh, p.stdout, p.stderr).await;
     return 0;
 }
 
-pub fn setup(term: &Terminal, args: Vec<&str>) -> i32 {
-    loadwebroot(term, vec!["/build/i.iar"]);
+pub async fn setup(p: &Proc<'_>) -> i32 {
+    loadwebroot(&p.with_args(&["/build/i.iar"])).await;
     return 0;
 }
 
 // TODO: eventualy delete once fs stable
-pub fn test_infs(term: &Terminal, args: Vec<&str>) -> i32 {
+pub fn test_infs(p: &Proc) -> i32 {
+    let args = p.args();
     if args.len() == 0 {
-        term.writeln("test-infs: cannot test with no args");
+        p.stderr.writeln("test-infs: cannot test with no args");
         return 1;
     }
     // no need to build a mountable fs, just run all the tests here
     let mut fs = crate::vfs::infs::FileSystem::create_test_fs();
-    term.writeln("created fs");
+    p.stdout.writeln("created fs");
     // we know the root dentry is at 1
     let ino: u32 = fs
         .create_file(1, "test.txt".to_string(), args[0].as_bytes())
         .unwrap();
-    term.writeln("wrote file test.txt");
+    p.stdout.writeln("wrote file test.txt");
     // this fd isn't getting stored, so fd number doesn't matter on INFS
     let mut fd = fs.get_fd(ino, 0).unwrap();
-    term.write("read to eof on test.txt: ");
-    term.writeln(&String::from_utf8(fs.read_to_eof(&mut fd).unwrap()).unwrap());
+    p.stdout.write("read to eof on test.txt: ");
+    p.stdout
+        .writeln(&String::from_utf8(fs.read_to_eof(&mut fd).unwrap()).unwrap());
     if args.len() >= 2 {
-        term.writeln("testing multi-file support");
+        p.stdout.writeln("testing multi-file support");
         let mut inos: Vec<u32> = vec![];
         for n in 1..args.len() {
             inos.push(
                 fs.create_file(1, format!("test{}.txt", n), args[n].as_bytes())
                     .unwrap(),
             );
-            term.writeln(&format!("wrote file test{}.txt", n));
+            p.stdout.writeln(&format!("wrote file test{}.txt", n));
         }
         let mut fds = vec![];
         for n in inos {
             fds.push(fs.get_fd(n, 0).unwrap());
         }
         for mut n in fds {
-            term.write("read to eof on multi: ");
-            term.writeln(&String::from_utf8(fs.read_to_eof(&mut n).unwrap()).unwrap());
+            p.stdout.write("read to eof on multi: ");
+            p.stdout
+                .writeln(&String::from_utf8(fs.read_to_eof(&mut n).unwrap()).unwrap());
             fs.delete_file(n.get_inum(), 1).unwrap();
-            term.writeln("deleted multi");
+            p.stdout.writeln("deleted multi");
         }
     }
-    term.writeln("deleting file test.txt");
+    p.stdout.writeln("deleting file test.txt");
     fs.delete_file(ino, 1).unwrap();
-    term.writeln("successfully deleted test.txt");
-    term.writeln("INFS driver works correctly");
+    p.stdout.writeln("successfully deleted test.txt");
+    p.stdout.writeln("INFS driver works correctly");
 
     let ino = fs
         .create_file(1, "mod.txt".to_string(), "FS traversal worked!".as_bytes())
         .unwrap();
-    term.writeln("created file mod.txt");
+    p.stdout.writeln("created file mod.txt");
     crate::vfs::mount_root(Box::new(fs));
-    term.writeln("mounted as rootfs");
+    p.stdout.writeln("mounted as rootfs");
     let tun = crate::vfs::safe_wrap_fdfs("mod.txt".to_string());
     // TODO: dentry searching and other dentry ops
     let mut fd = tun.0.get_fd(ino, 0).unwrap();
-    term.writeln(&String::from_utf8(tun.0.read_to_eof(&mut fd).unwrap()).unwrap());
+    p.stdout
+        .writeln(&String::from_utf8(tun.0.read_to_eof(&mut fd).unwrap()).unwrap());
 
     return 0;
 }
 
 // TODO: eventually delete once fs stable
-pub fn test_read_root(term: &Terminal, _args: Vec<&str>) -> i32 {
+pub fn test_read_root(p: &Proc) -> i32 {
     let fsw = crate::vfs::safe_wrap_fdfs(".".to_string()).0;
     // 1 = /.
     let vdent = fsw.vfd_as_dentry(&fsw.get_fd(1, 0).unwrap()).unwrap();
     for n in vdent.get_entries() {
-        term.writeln(&format!("VDE inode={},filename={}", n.inum, n.filename));
+        p.stdout
+            .writeln(&format!("VDE inode={},filename={}", n.inum, n.filename));
     }
     return 0;
 }
 
+const THEME_SPEC: Spec = Spec {
+    name: "theme",
+    usage: "Usage: theme [OPTION]... [NAME]
+Change the terminal's colors to those of the theme NAME, or say which
+one is in use. Besides the built-in themes, NAME can be any palette in
+/etc/themes/NAME.toml. The choice is remembered across reloads.",
+    opts: &[
+        Opt::flag('l', "list", "list the themes, marking the one in use"),
+        Opt::flag(
+            'p',
+            "print",
+            "print the palette of NAME (or the one in use),\nas a start for one of your own",
+        ),
+    ],
+    footer: "A palette has a line for each color it sets, like
+  background = \"#191a22\"
+for any of foreground, background, cursor, cursor_accent, selection,
+black, red, green, yellow, blue, magenta, cyan, white, and each of
+those eight as bright_black and so on. Colors are #rgb, #rrggbb or
+#rrggbbaa; lines starting with # are comments.",
+    version: None,
+};
+pub fn theme(p: &Proc) -> i32 {
+    let args = match p.getopt(&THEME_SPEC) {
+        Ok(a) => a,
+        Err(st) => return st,
+    };
+    let cur = theme::current();
+    let name = match args.operands.as_slice() {
+        [] => None,
+        [n] => Some(*n),
+        [_, extra, ..] => {
+            p.stderr
+                .writeln(&format!("theme: extra operand '{}'", extra));
+            p.minfo("theme");
+            return 1;
+        }
+    };
+    if args.has("list") {
+        for n in theme::names(&p.sh.caller()) {
+            let mark = if n == cur.theme { "*" } else { " " };
+            p.stdout.writeln(&format!("{} {}", mark, n));
+        }
+        return 0;
+    }
+    let palette = match name.map(|n| theme::find(&p.sh.caller(), n)) {
+        None => cur.palette.clone(),
+        Some(Ok(pal)) => pal,
+        Some(Err(e)) => {
+            p.stderr.writeln(&format!("theme: {}", e));
+            return 1;
+        }
+    };
+    if args.has("print") {
+        p.stdout.writeln(&palette.to_toml());
+        return 0;
+    }
+    match name {
+        Some(n) => theme::apply(theme::Appearance {
+            theme: n.to_string(),
+            palette,
+            ..cur
+        }),
+        None => p.stdout.writeln(&cur.theme),
+    }
+    0
+}
+
 const HELPMSG: &str = "IrisOS-nano irun, version 0.1 (wasm32)
 These commands are built in to irun. Other programs traverse the PATH.
-Type `NAME --help` to find out more about the command `NAME`.
-
-cat          [OPTS].. <FILE>..  ls     [DIRNAME]
-cd           [DIR]              mkdir  <DIRNAME>
-cp           <SRC> <DEST>       mv     <SRC> <DEST>
-echo         [MSG]              pwd
-exit                            rm     <FILE>
-help                            rmdir  <DIRECTORY>
-iris-info                       setup
-kmsg         [MSG]              touch  <FILENAME>
-ln           <TARGET> <NAME>    uname  [OPTIONS]
-loadwebroot  [URL]";
-pub fn help(term: &Terminal, _args: Vec<&str>) -> i32 {
-    term.writeln(HELPMSG);
+Type `NAME --help` or `man NAME` to find out more about the command `NAME`.";
+// what help lists, by name; the arguments are only a reminder
+const COMMANDS: &[(&str, &str)] = &[
+    ("[", "EXPR ]"),
+    ("alias", "[NAME[=VAL]].."),
+    ("apropos", "KEYWORD..."),
+    ("break", "[N]"),
+    ("cat", "[OPTS].. <FILE>.."),
+    ("cd", "[DIR]"),
+    ("chgrp", "[-R] GROUP FILE..."),
+    ("chmod", "[-R] MODE FILE..."),
+    ("chown", "[-R] OWNER[:GROUP] FILE.."),
+    ("chvt", "N"),
+    ("continue", "[N]"),
+    ("cp", "<SRC> <DEST>"),
+    ("dmesg", "[-cCTw] [-l LEVELS]"),
+    ("echo", "[MSG]"),
+    ("env", "[NAME=VAL].. [CMD]"),
+    ("exit", "[N]"),
+    ("export", "[-n] [NAME[=VAL]].."),
+    ("false", ""),
+    ("groups", "[USER].."),
+    ("help", ""),
+    ("history", "[-c] [-d N] [N]"),
+    ("id", "[-ugGn] [USER]"),
+    ("iris-info", ""),
+    ("kmsg", "[MSG]"),
+    ("ln", "<TARGET> <NAME>"),
+    ("loadwebroot", "[URL]"),
+    ("local", "NAME[=VAL].."),
+    ("login", "[USER]"),
+    ("ls", "[-1aACilnx] [FILE].."),
+    ("man", "[SECTION] PAGE..."),
+    ("mkdir", "<DIRNAME>"),
+    ("mv", "<SRC> <DEST>"),
+    ("pwd", ""),
+    ("read", "[-r] [-p PROMPT] [NAME].."),
+    ("return", "[N]"),
+    ("rm", "<FILE>"),
+    ("rmdir", "<DIRECTORY>"),
+    ("set", ""),
+    ("setterm", "[--OPTION [VAL]].."),
+    ("setup", ""),
+    ("sh", "<FILE> [ARG].."),
+    ("sleep", "<NUMBER>.."),
+    ("source", "<FILE> [ARG].."),
+    ("stty", "[-a] [size]"),
+    ("su", "[-] [-c CMD] [USER]"),
+    ("test", "[EXPR]"),
+    ("theme", "[-l] [-p] [NAME]"),
+    ("touch", "<FILENAME>"),
+    ("tput", "CAPNAME..."),
+    ("true", ""),
+    ("umask", "[-S] [MODE]"),
+    ("unalias", "[-a] <NAME>.."),
+    ("uname", "[OPTIONS]"),
+    ("unset", "<NAME>.."),
+    ("whatis", "PAGE..."),
+    ("whoami", ""),
+];
+pub fn help(p: &Proc) -> i32 {
+    p.stdout.writeln(HELPMSG);
+    p.stdout.writeln("");
+    let ents: Vec<String> = COMMANDS
+        .iter()
+        .map(|(n, a)| format!("{:<13}{}", n, a))
+        .collect();
+    // as many columns as fit
+    for l in crate::linebuf::columns(&ents, p.width(), false) {
+        p.stdout.writeln(&l);
+    }
     return 0;
 }
 
-pub fn neofetch(term: &Terminal, _args: Vec<&str>) -> i32 {
-    term.writeln(&format!("
-[0;34;40m                                        [35;49;1m   root@amyip.net
-[0;34;40m                                        [37;49m   --------------
+pub fn neofetch(p: &Proc) -> i32 {
+    let who = format!(
+        "{}@{}",
+        crate::users::user_name(p.sh.vars.creds().uid).unwrap_or_else(|| "?".to_string()),
+        crate::unix::uname::UNAME_NODENAME
+    );
+    let rule = "-".repeat(who.len());
+    p.stdout.writeln(&format!("
+[0;34;40m                                        [35;49;1m   {who}
+[0;34;40m                                        [37;49m   {rule}
 [0;34;40m       [0;1;37;47m                         [0;34;40m        [35;49;1m   OS[37;49m: IrisOS-nano
 [0;34;40m     [0;5;37;47m                              [0;31;40m     [35;49;1m   Kernel[37;49m: {kvsn}
 [0;34;40m  [0;5;33;47m                                    [0;34;40m  [35;49;1m   Uptime[37;49m: {cup}
@@ -241,8 +384,8 @@ fn timeconv(mut n: u64) -> String {
     b
 }
 
-fn ah(term: &Terminal, code: i32) {
-    term.writeln(match code {
+fn ah(p: &Proc, code: i32) {
+    p.stderr.writeln(match code {
         -8 => "loadwebroot: could not load root: network error occurred",
         _ => "nanotools: unknown error",
     });
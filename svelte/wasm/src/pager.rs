@@ -0,0 +1,86 @@
+// A pager along the lines of more(1), for commands with more to say
+// than fits on the screen. The tty only hands over whole lines, so it
+// goes by what's typed before Enter: nothing for one more line, q to
+// stop, and anything else (a space, say) for the next screenful.
+// When the output isn't going to the terminal, it all goes straight
+// through.
+
+use crate::keys::{CURSOR_UP, ERASE_LINE};
+use crate::process::Proc;
+
+pub async fn page(p: &Proc<'_>, lines: &[String]) {
+    let rows = match p.stdout.size() {
+        Some((_, rows)) if p.interactive() && lines.len() >= rows => rows.max(2) - 1,
+        _ => {
+            for l in lines {
+                p.stdout.writeln(l);
+            }
+            return;
+        }
+    };
+    let mut at = 0;
+    let mut step = rows;
+    while at < lines.len() {
+        let end = (at + step).min(lines.len());
+        for l in &lines[at..end] {
+            p.stdout.writeln(l);
+        }
+        at = end;
+        if at == lines.len() {
+            break;
+        }
+        p.stdout.write(&format!(
+            "\x1b[7m--More--({}%)\x1b[27m",
+            at * 100 / lines.len()
+        ));
+        let answer = p.read_line().await;
+        // Enter left the cursor under the prompt; Ctrl-D didn't
+        match &answer {
+            Some(a) if a.ends_with('\n') => p.stdout.write(CURSOR_UP),
+            _ => {}
+        }
+        p.stdout.write(&format!("\r{}", ERASE_LINE));
+        match answer.as_deref().map(|a| a.trim_end_matches('\n')) {
+            None | Some("q") | Some("Q") => break,
+            Some("") => step = 1,
+            Some(_) => step = rows,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::process::{block_on, Buffer, Sink};
+
+    // a terminal four rows high
+    struct Screen(Buffer);
+    impl Sink for Screen {
+        fn write(&self, s: &str) {
+            self.0.write(s);
+        }
+        fn size(&self) -> Option<(usize, usize)> {
+            Some((80, 4))
+        }
+    }
+
+    #[test]
+    fn paging() {
+        let _l = crate::process::shell_lock();
+        let sh = crate::process::test_sh();
+        let lines: Vec<String> = (1..=9).map(|n| n.to_string()).collect();
+        let (scr, err) = (Screen(Buffer::new()), Buffer::new());
+        sh.tty.reset();
+        sh.tty.push("\n \nq\n");
+        block_on(page(&Proc::new(&sh, &[], None, &scr, &err), &lines));
+        let more = |n| format!("\x1b[7m--More--({}%)\x1b[27m\x1b[A\r\x1b[2K", n);
+        assert_eq!(
+            scr.0.take(),
+            format!("1\n2\n3\n{}4\n{}5\n6\n7\n{}", more(33), more(44), more(77))
+        );
+        // not at the terminal, it's all there at once
+        let out = Buffer::new();
+        block_on(page(&Proc::new(&sh, &[], None, &out, &err), &lines));
+        assert_eq!(out.take(), "1\n2\n3\n4\n5\n6\n7\n8\n9\n");
+    }
+}
@@ -0,0 +1,1077 @@
+// The irun grammar.
+// Turns a line (or several, when the editor had to ask for more) into
+// a script: and-or lists of pipelines, separated by ; or newlines.
+// Words keep track of how each piece of them was quoted, because that
+// decides what gets expanded later on.
+//
+// Supported: 'single quotes', "double quotes", backslash escapes,
+// backslash-newline, # comments, ;, &&, ||, |, !, the <, >, >>,
+// 2>, 2>> and 2>&1 redirections, and the compound commands: if,
+// while, until, for, case, { ...; }, ( ... ) and NAME() functions.
+// Expanding what's in the words is up to expand.rs.
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum Part {
+    // single-quoted or backslash-escaped; always taken as-is
+    Lit(String),
+    // unquoted
+    Bare(String),
+    // double-quoted
+    Quoted(String),
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Word {
+    pub parts: Vec<Part>,
+}
+
+impl Word {
+    // NAME=value words, split into the name and the value
+    pub fn assignment(&self) -> Option<(String, Word)> {
+        let first = match self.parts.first() {
+            Some(Part::Bare(s)) => s,
+            _ => return None,
+        };
+        let (name, val) = first.split_once('=')?;
+        if !is_name(name) {
+            return None;
+        }
+        let mut parts = vec![];
+        if !val.is_empty() {
+            parts.push(Part::Bare(val.to_string()));
+        }
+        parts.extend(self.parts[1..].iter().cloned());
+        Some((name.to_string(), Word { parts }))
+    }
+}
+
+pub fn is_name(s: &str) -> bool {
+    let mut c = s.chars();
+    c.next()
+        .is_some_and(|x| x.is_ascii_alphabetic() || x == '_')
+        && c.all(|x| x.is_ascii_alphanumeric() || x == '_')
+}
+
+use std::sync::Arc;
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum RedirKind {
+    // < file
+    In,
+    // > file
+    Out,
+    // >> file
+    Append,
+    // 2> file
+    ErrOut,
+    // 2>> file
+    ErrAppend,
+    // 2>&1, which has no target
+    ErrToOut,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Redir {
+    pub kind: RedirKind,
+    pub target: Word,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Simple {
+    pub words: Vec<Word>,
+    pub redirs: Vec<Redir>,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum Compound {
+    // { list; }
+    Group(Script),
+    // ( list )
+    Subshell(Script),
+    // the if and elif conditions with their bodies, then the else part
+    If {
+        branches: Vec<(Script, Script)>,
+        els: Option<Script>,
+    },
+    // while, or until if `until` is set
+    Loop {
+        until: bool,
+        cond: Script,
+        body: Script,
+    },
+    // no words means "$@"
+    For {
+        var: String,
+        words: Option<Vec<Word>>,
+        body: Script,
+    },
+    Case {
+        word: Word,
+        arms: Vec<(Vec<Word>, Script)>,
+    },
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum Command {
+    Simple(Simple),
+    Compound(Compound, Vec<Redir>),
+    // NAME() compound-command
+    FuncDef(String, Arc<Compound>),
+}
+
+#[cfg(test)]
+impl Command {
+    pub fn simple(&self) -> &Simple {
+        match self {
+            Command::Simple(s) => s,
+            _ => panic!("not a simple command: {:?}", self),
+        }
+    }
+}
+
+// commands joined by |, maybe with a ! in front
+#[derive(Debug, PartialEq, Clone)]
+pub struct Pipeline {
+    pub bang: bool,
+    pub cmds: Vec<Command>,
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Connector {
+    And,
+    Or,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct AndOr {
+    pub first: Pipeline,
+    pub rest: Vec<(Connector, Pipeline)>,
+}
+
+pub type Script = Vec<AndOr>;
+
+#[derive(Debug, PartialEq)]
+pub enum ParseError {
+    // the input stopped in the middle of something (a quote, a trailing
+    // backslash or &&); the editor should ask for another line
+    Incomplete,
+    // pos is in chars from the start of the input
+    Syntax { pos: usize, msg: String },
+}
+
+impl ParseError {
+    // What to show the user, with a caret under the offending column.
+    pub fn report(&self, src: &str) -> String {
+        let (pos, msg) = match self {
+            ParseError::Incomplete => {
+                return "irun: syntax error: unexpected end of file".to_string()
+            }
+            ParseError::Syntax { pos, msg } => (*pos, msg),
+        };
+        let before: String = src.chars().take(pos).collect();
+        let ln = before.matches('\n').count();
+        let start = before.rfind('\n').map(|x| x + 1).unwrap_or(0);
+        let col = before[start..].chars().count() + 1;
+        let line = src.lines().nth(ln).unwrap_or("");
+        let at = if src.contains('\n') {
+            format!("line {}, column {}", ln + 1, col)
+        } else {
+            format!("column {}", col)
+        };
+        format!(
+            "irun: {} at {}\n{}\n{}^",
+            msg,
+            at,
+            line,
+            " ".repeat(crate::linebuf::str_width(&before[start..]))
+        )
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum Tok {
+    Word(Word),
+    Semi,
+    // ;; ends a case arm
+    DSemi,
+    LParen,
+    RParen,
+    And,
+    Or,
+    Pipe,
+    Redir(RedirKind),
+    Newline,
+}
+
+fn unexpected(pos: usize, t: &str) -> ParseError {
+    ParseError::Syntax {
+        pos,
+        msg: format!("syntax error near unexpected token `{}'", t),
+    }
+}
+
+// appends to the last part if it is of the same kind
+fn push(parts: &mut Vec<Part>, p: Part) {
+    match (parts.last_mut(), p) {
+        (Some(Part::Lit(a)), Part::Lit(b))
+        | (Some(Part::Bare(a)), Part::Bare(b))
+        | (Some(Part::Quoted(a)), Part::Quoted(b)) => a.push_str(&b),
+        (_, p) => parts.push(p),
+    }
+}
+
+fn lex(src: &str) -> Result<Vec<(Tok, usize)>, ParseError> {
+    let c: Vec<char> = src.chars().collect();
+    let mut toks = vec![];
+    let mut i = 0;
+    while i < c.len() {
+        let start = i;
+        match c[i] {
+            ' ' | '\t' => i += 1,
+            '\\' if c.get(i + 1) == Some(&'\n') => i += 2,
+            '#' => {
+                while i < c.len() && c[i] != '\n' {
+                    i += 1;
+                }
+            }
+            '\n' => {
+                toks.push((Tok::Newline, i));
+                i += 1;
+            }
+            ';' if c.get(i + 1) == Some(&';') => {
+                toks.push((Tok::DSemi, i));
+                i += 2;
+            }
+            ';' => {
+                toks.push((Tok::Semi, i));
+                i += 1;
+            }
+            '(' | ')' => {
+                toks.push((
+                    if c[i] == '(' {
+                        Tok::LParen
+                    } else {
+                        Tok::RParen
+                    },
+                    i,
+                ));
+                i += 1;
+            }
+            '&' | '|' | '>' if c.get(i + 1) == Some(&c[i]) => {
+                let t = match c[i] {
+                    '&' => Tok::And,
+                    '|' => Tok::Or,
+                    _ => Tok::Redir(RedirKind::Append),
+                };
+                toks.push((t, i));
+                i += 2;
+            }
+            // no background jobs
+            '&' => return Err(unexpected(i, "&")),
+            '2' if c.get(i + 1) == Some(&'>') => {
+                let (k, n) = match (c.get(i + 2), c.get(i + 3)) {
+                    (Some('>'), _) => (RedirKind::ErrAppend, 3),
+                    (Some('&'), Some('1')) => (RedirKind::ErrToOut, 4),
+                    _ => (RedirKind::ErrOut, 2),
+                };
+                toks.push((Tok::Redir(k), i));
+                i += n;
+            }
+            '|' | '<' | '>' => {
+                let t = match c[i] {
+                    '|' => Tok::Pipe,
+                    '<' => Tok::Redir(RedirKind::In),
+                    _ => Tok::Redir(RedirKind::Out),
+                };
+                toks.push((t, i));
+                i += 1;
+            }
+            _ => {
+                let mut parts = vec![];
+                while i < c.len() && !" \t\n;&|<>()".contains(c[i]) {
+                    match c[i] {
+                        '\'' => {
+                            let end = c[i + 1..]
+                                .iter()
+                                .position(|&x| x == '\'')
+                                .ok_or(ParseError::Incomplete)?;
+                            let s: String = c[i + 1..i + 1 + end].iter().collect();
+                            // '' still makes an (empty) argument
+                            parts.push(Part::Lit(s));
+                            i += end + 2;
+                        }
+                        '"' => {
+                            let mut s = String::new();
+                            i += 1;
+                            loop {
+                                match c.get(i) {
+                                    None => return Err(ParseError::Incomplete),
+                                    Some('"') => break,
+                                    // inside double quotes a backslash
+                                    // only escapes the characters that
+                                    // would mean something there
+                                    Some('\\') if c.get(i + 1) == Some(&'\n') => i += 1,
+                                    Some('\\')
+                                        if c.get(i + 1).is_some_and(|x| "$`\"\\".contains(*x)) =>
+                                    {
+                                        i += 1;
+                                        s.push(c[i]);
+                                    }
+                                    Some(x) => s.push(*x),
+                                }
+                                i += 1;
+                            }
+                            parts.push(Part::Quoted(s));
+                            i += 1;
+                        }
+                        // ${...} is kept whole, spaces and all, so that
+                        // ${VAR:-some default} stays one word
+                        '$' if c.get(i + 1) == Some(&'{') => {
+                            let end = c[i..]
+                                .iter()
+                                .position(|&x| x == '}')
+                                .ok_or(ParseError::Incomplete)?;
+                            let s: String = c[i..=i + end].iter().collect();
+                            push(&mut parts, Part::Bare(s));
+                            i += end + 1;
+                        }
+                        '\\' => match c.get(i + 1) {
+                            None => return Err(ParseError::Incomplete),
+                            Some('\n') => i += 2,
+                            Some(x) => {
+                                push(&mut parts, Part::Lit(x.to_string()));
+                                i += 2;
+                            }
+                        },
+                        x => {
+                            push(&mut parts, Part::Bare(x.to_string()));
+                            i += 1;
+                        }
+                    }
+                }
+                toks.push((Tok::Word(Word { parts }), start));
+            }
+        }
+    }
+    Ok(toks)
+}
+
+// words that mean something at the start of a command
+const RESERVED: [&str; 16] = [
+    "if", "then", "elif", "else", "fi", "while", "until", "do", "done", "for", "in", "case",
+    "esac", "{", "}", "!",
+];
+
+fn tok_str(t: &Tok) -> &'static str {
+    match t {
+        Tok::Semi => ";",
+        Tok::DSemi => ";;",
+        Tok::LParen => "(",
+        Tok::RParen => ")",
+        Tok::And => "&&",
+        Tok::Or => "||",
+        Tok::Pipe => "|",
+        Tok::Redir(RedirKind::In) => "<",
+        Tok::Redir(RedirKind::Out) => ">",
+        Tok::Redir(RedirKind::Append) => ">>",
+        Tok::Redir(RedirKind::ErrOut) => "2>",
+        Tok::Redir(RedirKind::ErrAppend) => "2>>",
+        Tok::Redir(RedirKind::ErrToOut) => "2>&1",
+        Tok::Newline => "newline",
+        Tok::Word(_) => "word",
+    }
+}
+
+struct Parser<'a> {
+    toks: Vec<(Tok, usize)>,
+    // the aliases each token came out of, parallel to toks
+    origin: Vec<Vec<String>>,
+    p: usize,
+    // where the input ends, for errors there
+    end: usize,
+    aliases: &'a dyn Fn(&str) -> Option<String>,
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<&Tok> {
+        self.toks.get(self.p).map(|x| &x.0)
+    }
+    fn skip_newlines(&mut self) {
+        while let Some(Tok::Newline) = self.peek() {
+            self.p += 1;
+        }
+    }
+    // the error for whatever is at the current position
+    fn unexpected(&self) -> ParseError {
+        match self.toks.get(self.p) {
+            Some((Tok::Word(w), pos)) => {
+                let t: String = w
+                    .parts
+                    .iter()
+                    .map(|p| match p {
+                        Part::Lit(s) | Part::Bare(s) | Part::Quoted(s) => s.as_str(),
+                    })
+                    .collect();
+                unexpected(*pos, &t)
+            }
+            Some((t, pos)) => unexpected(*pos, tok_str(t)),
+            None => unexpected(self.end, "newline"),
+        }
+    }
+    // like unexpected, but running out of input means there's more to come
+    fn wanted(&self) -> ParseError {
+        if self.p == self.toks.len() {
+            return ParseError::Incomplete;
+        }
+        self.unexpected()
+    }
+    // like bash, a line ending in |, && or || carries on
+    fn after_op(&mut self) -> Result<(), ParseError> {
+        self.skip_newlines();
+        if self.p == self.toks.len() {
+            return Err(ParseError::Incomplete);
+        }
+        Ok(())
+    }
+    // the reserved word at the current position, if there is one
+    fn reserved(&self) -> Option<&'static str> {
+        match self.peek() {
+            Some(Tok::Word(w)) => match w.parts.as_slice() {
+                [Part::Bare(s)] => RESERVED.iter().find(|r| *r == s).copied(),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+    fn expect(&mut self, word: &str) -> Result<(), ParseError> {
+        if self.reserved() != Some(word) {
+            return Err(self.wanted());
+        }
+        self.p += 1;
+        Ok(())
+    }
+    fn expect_tok(&mut self, t: Tok) -> Result<(), ParseError> {
+        if self.peek() != Some(&t) {
+            return Err(self.wanted());
+        }
+        self.p += 1;
+        Ok(())
+    }
+    fn word(&mut self) -> Result<Word, ParseError> {
+        match self.peek() {
+            Some(Tok::Word(w)) => {
+                let w = w.clone();
+                self.p += 1;
+                Ok(w)
+            }
+            _ => Err(self.wanted()),
+        }
+    }
+    // And-or lists separated by ; or newlines, up to one of the
+    // reserved words in `ends`, a ;; or a ). The list may be empty.
+    fn list(&mut self, ends: &[&str]) -> Result<Script, ParseError> {
+        let mut script = vec![];
+        loop {
+            self.skip_newlines();
+            let end = match self.peek() {
+                None => return Err(ParseError::Incomplete),
+                Some(Tok::DSemi | Tok::RParen) => true,
+                _ => self.reserved().is_some_and(|r| ends.contains(&r)),
+            };
+            if end {
+                return Ok(script);
+            }
+            script.push(self.and_or()?);
+            match self.peek() {
+                Some(Tok::Semi | Tok::Newline) => self.p += 1,
+                Some(Tok::DSemi | Tok::RParen) => {}
+                _ if self.reserved().is_some_and(|r| ends.contains(&r)) => {}
+                _ => return Err(self.wanted()),
+            }
+        }
+    }
+    // a list that has to have something in it
+    fn body(&mut self, ends: &[&str]) -> Result<Script, ParseError> {
+        let s = self.list(ends)?;
+        if s.is_empty() {
+            return Err(self.unexpected());
+        }
+        Ok(s)
+    }
+    fn redir(&mut self) -> Result<Option<Redir>, ParseError> {
+        let kind = match self.peek() {
+            Some(Tok::Redir(k)) => *k,
+            _ => return Ok(None),
+        };
+        self.p += 1;
+        if kind == RedirKind::ErrToOut {
+            return Ok(Some(Redir {
+                kind,
+                target: Word { parts: vec![] },
+            }));
+        }
+        match self.peek() {
+            Some(Tok::Word(w)) => {
+                let target = w.clone();
+                self.p += 1;
+                Ok(Some(Redir { kind, target }))
+            }
+            _ => Err(self.unexpected()),
+        }
+    }
+    fn command(&mut self) -> Result<Command, ParseError> {
+        // NAME() followed by the body
+        if let (Some(Tok::Word(w)), Some((Tok::LParen, _))) =
+            (self.peek(), self.toks.get(self.p + 1))
+        {
+            let name = match w.parts.as_slice() {
+                [Part::Bare(s)] if !s.contains('$') && self.reserved().is_none() => s.clone(),
+                _ => {
+                    self.p += 1;
+                    return Err(self.unexpected());
+                }
+            };
+            self.p += 2;
+            self.expect_tok(Tok::RParen)?;
+            self.skip_newlines();
+            return match self.compound()? {
+                Some(c) => Ok(Command::FuncDef(name, Arc::new(c))),
+                None => Err(self.wanted()),
+            };
+        }
+        if let Some(c) = self.compound()? {
+            let mut redirs = vec![];
+            while let Some(r) = self.redir()? {
+                redirs.push(r);
+            }
+            return Ok(Command::Compound(c, redirs));
+        }
+        if self.reserved().is_some() {
+            return Err(self.unexpected());
+        }
+        Ok(Command::Simple(self.simple()?))
+    }
+    fn compound(&mut self) -> Result<Option<Compound>, ParseError> {
+        if self.peek() == Some(&Tok::LParen) {
+            self.p += 1;
+            let s = self.body(&[])?;
+            self.expect_tok(Tok::RParen)?;
+            return Ok(Some(Compound::Subshell(s)));
+        }
+        let r = match self.reserved() {
+            Some(r @ ("if" | "while" | "until" | "for" | "case" | "{")) => r,
+            _ => return Ok(None),
+        };
+        self.p += 1;
+        let c = match r {
+            "if" => {
+                let mut branches = vec![];
+                let mut els = None;
+                loop {
+                    let cond = self.body(&["then"])?;
+                    self.expect("then")?;
+                    branches.push((cond, self.body(&["elif", "else", "fi"])?));
+                    match self.reserved() {
+                        Some("elif") => self.p += 1,
+                        Some("else") => {
+                            self.p += 1;
+                            els = Some(self.body(&["fi"])?);
+                            break;
+                        }
+                        _ => break,
+                    }
+                }
+                self.expect("fi")?;
+                Compound::If { branches, els }
+            }
+            "while" | "until" => {
+                let cond = self.body(&["do"])?;
+                self.expect("do")?;
+                let body = self.body(&["done"])?;
+                self.expect("done")?;
+                Compound::Loop {
+                    until: r == "until",
+                    cond,
+                    body,
+                }
+            }
+            "for" => {
+                let var = match self.peek() {
+                    Some(Tok::Word(w)) => match w.parts.as_slice() {
+                        [Part::Bare(s)] if is_name(s) => s.clone(),
+                        _ => return Err(self.unexpected()),
+                    },
+                    _ => return Err(self.wanted()),
+                };
+                self.p += 1;
+                self.skip_newlines();
+                let mut words = None;
+                if self.reserved() == Some("in") {
+                    self.p += 1;
+                    let mut ws = vec![];
+                    while let Some(Tok::Word(w)) = self.peek() {
+                        ws.push(w.clone());
+                        self.p += 1;
+                    }
+                    words = Some(ws);
+                    match self.peek() {
+                        Some(Tok::Semi | Tok::Newline) => self.p += 1,
+                        _ => return Err(self.wanted()),
+                    }
+                } else if self.peek() == Some(&Tok::Semi) {
+                    self.p += 1;
+                }
+                self.skip_newlines();
+                self.expect("do")?;
+                let body = self.body(&["done"])?;
+                self.expect("done")?;
+                Compound::For { var, words, body }
+            }
+            "case" => {
+                let word = self.word()?;
+                self.skip_newlines();
+                self.expect("in")?;
+                let mut arms = vec![];
+                loop {
+                    self.skip_newlines();
+                    if self.reserved() == Some("esac") {
+                        break;
+                    }
+                    if self.peek() == Some(&Tok::LParen) {
+                        self.p += 1;
+                    }
+                    let mut pats = vec![self.word()?];
+                    while self.peek() == Some(&Tok::Pipe) {
+                        self.p += 1;
+                        pats.push(self.word()?);
+                    }
+                    self.expect_tok(Tok::RParen)?;
+                    arms.push((pats, self.list(&["esac"])?));
+                    if self.peek() != Some(&Tok::DSemi) {
+                        break;
+                    }
+                    self.p += 1;
+                }
+                self.expect("esac")?;
+                Compound::Case { word, arms }
+            }
+            _ => {
+                let s = self.body(&["}"])?;
+                self.expect("}")?;
+                Compound::Group(s)
+            }
+        };
+        Ok(Some(c))
+    }
+    // Replaces an alias at the start of a command with the tokens of
+    // its value, like bash does. An alias isn't expanded again inside
+    // its own value, so `alias ls='ls -F'` works.
+    fn expand_alias(&mut self) -> Result<(), ParseError> {
+        loop {
+            let (name, pos) = match self.toks.get(self.p) {
+                Some((Tok::Word(w), pos)) => match w.parts.as_slice() {
+                    [Part::Bare(s)] => (s.clone(), *pos),
+                    _ => return Ok(()),
+                },
+                _ => return Ok(()),
+            };
+            if self.origin[self.p].contains(&name) {
+                return Ok(());
+            }
+            let val = match (self.aliases)(&name) {
+                Some(v) => v,
+                None => return Ok(()),
+            };
+            // anything in the value is reported at the alias itself
+            let toks: Vec<(Tok, usize)> = lex(&val)
+                .map_err(|_| ParseError::Syntax {
+                    pos,
+                    msg: format!("bad alias `{}'", name),
+                })?
+                .into_iter()
+                .map(|(t, _)| (t, pos))
+                .collect();
+            let mut origin = self.origin[self.p].clone();
+            origin.push(name);
+            let n = toks.len();
+            self.toks.splice(self.p..=self.p, toks);
+            self.origin
+                .splice(self.p..=self.p, std::iter::repeat_n(origin, n));
+        }
+    }
+    fn simple(&mut self) -> Result<Simple, ParseError> {
+        self.expand_alias()?;
+        let mut s = Simple {
+            words: vec![],
+            redirs: vec![],
+        };
+        loop {
+            if let Some(r) = self.redir()? {
+                s.redirs.push(r);
+                continue;
+            }
+            match self.peek() {
+                Some(Tok::Word(w)) => s.words.push(w.clone()),
+                _ => break,
+            }
+            self.p += 1;
+        }
+        if s.words.is_empty() && s.redirs.is_empty() {
+            return Err(self.unexpected());
+        }
+        Ok(s)
+    }
+    fn pipeline(&mut self) -> Result<Pipeline, ParseError> {
+        let bang = self.reserved() == Some("!");
+        if bang {
+            self.p += 1;
+        }
+        let mut cmds = vec![self.command()?];
+        while let Some(Tok::Pipe) = self.peek() {
+            self.p += 1;
+            self.after_op()?;
+            cmds.push(self.command()?);
+        }
+        Ok(Pipeline { bang, cmds })
+    }
+    fn and_or(&mut self) -> Result<AndOr, ParseError> {
+        let first = self.pipeline()?;
+        let mut rest = vec![];
+        loop {
+            let conn = match self.peek() {
+                Some(Tok::And) => Connector::And,
+                Some(Tok::Or) => Connector::Or,
+                _ => break,
+            };
+            self.p += 1;
+            self.after_op()?;
+            rest.push((conn, self.pipeline()?));
+        }
+        Ok(AndOr { first, rest })
+    }
+}
+
+pub fn parse(src: &str) -> Result<Script, ParseError> {
+    parse_aliased(src, &|_| None)
+}
+
+// parses with aliases, looking each command name up with `aliases`
+pub fn parse_aliased(
+    src: &str,
+    aliases: &dyn Fn(&str) -> Option<String>,
+) -> Result<Script, ParseError> {
+    let toks = lex(src)?;
+    let mut ps = Parser {
+        origin: vec![vec![]; toks.len()],
+        toks,
+        p: 0,
+        end: src.chars().count(),
+        aliases,
+    };
+    let mut script = vec![];
+    loop {
+        ps.skip_newlines();
+        if ps.p == ps.toks.len() {
+            break;
+        }
+        script.push(ps.and_or()?);
+        match ps.peek() {
+            None => break,
+            Some(Tok::Semi | Tok::Newline) => ps.p += 1,
+            Some(_) => return Err(ps.unexpected()),
+        }
+    }
+    Ok(script)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text(w: &Word) -> String {
+        w.parts
+            .iter()
+            .map(|p| match p {
+                Part::Lit(s) | Part::Bare(s) | Part::Quoted(s) => s.as_str(),
+            })
+            .collect()
+    }
+
+    // every simple command's words, in order
+    fn argv(src: &str) -> Vec<Vec<String>> {
+        words(parse(src).unwrap())
+    }
+
+    fn words(script: Script) -> Vec<Vec<String>> {
+        let mut out = vec![];
+        for ao in script {
+            for pl in std::iter::once(ao.first).chain(ao.rest.into_iter().map(|x| x.1)) {
+                for c in pl.cmds {
+                    if let Command::Simple(s) = c {
+                        out.push(s.words.iter().map(text).collect());
+                    }
+                }
+            }
+        }
+        out
+    }
+
+    #[test]
+    fn words_and_quotes() {
+        assert_eq!(argv("ls  -l   /"), vec![vec!["ls", "-l", "/"]]);
+        assert_eq!(
+            argv("cat 'a b' \"c d\" e\\ f"),
+            vec![vec!["cat", "a b", "c d", "e f"]]
+        );
+        assert_eq!(argv("echo '' \"\""), vec![vec!["echo", "", ""]]);
+        assert_eq!(argv("echo a'b'\"c\"d"), vec![vec!["echo", "abcd"]]);
+        assert_eq!(
+            argv("echo 'a\\b' \"a\\b\\\"\\\\\""),
+            vec![vec!["echo", "a\\b", "a\\b\"\\"]]
+        );
+        assert_eq!(argv("echo a # b c"), vec![vec!["echo", "a"]]);
+        assert_eq!(argv("echo a#b"), vec![vec!["echo", "a#b"]]);
+        assert_eq!(argv("# nothing"), Vec::<Vec<String>>::new());
+    }
+
+    #[test]
+    fn quoting_is_tracked() {
+        let s = parse("x'$a'\"$b\"\\$c").unwrap();
+        assert_eq!(
+            s[0].first.cmds[0].simple().words[0].parts,
+            vec![
+                Part::Bare("x".to_string()),
+                Part::Lit("$a".to_string()),
+                Part::Quoted("$b".to_string()),
+                Part::Lit("$".to_string()),
+                Part::Bare("c".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn braces_and_assignments() {
+        assert_eq!(argv("echo ${X:-a b}c"), vec![vec!["echo", "${X:-a b}c"]]);
+        assert_eq!(parse("echo ${X"), Err(ParseError::Incomplete));
+        let s = parse("A=1 B='x y' =z C-D=1 ls").unwrap();
+        let w = &s[0].first.cmds[0].simple().words;
+        assert_eq!(w[0].assignment().unwrap().0, "A");
+        let (n, v) = w[1].assignment().unwrap();
+        assert_eq!((n.as_str(), text(&v)), ("B", "x y".to_string()));
+        assert!(w[2].assignment().is_none());
+        assert!(w[3].assignment().is_none());
+        assert!(w[4].assignment().is_none());
+    }
+
+    #[test]
+    fn lists() {
+        let s = parse("a; b && c || d\ne").unwrap();
+        assert_eq!(s.len(), 3);
+        assert_eq!(
+            s[1].rest.iter().map(|x| x.0).collect::<Vec<_>>(),
+            vec![Connector::And, Connector::Or]
+        );
+        assert_eq!(argv("a;b;"), vec![vec!["a"], vec!["b"]]);
+        assert_eq!(argv("a &&\n\nb"), vec![vec!["a"], vec!["b"]]);
+    }
+
+    #[test]
+    fn pipes_and_redirections() {
+        let s = parse("ls -l|cat -n >out 2 && <in cat>>log").unwrap();
+        let cmds = &s[0].first.cmds;
+        assert_eq!(cmds.len(), 2);
+        assert_eq!(
+            cmds[1].simple().words.iter().map(text).collect::<Vec<_>>(),
+            vec!["cat", "-n", "2"]
+        );
+        assert_eq!(cmds[1].simple().redirs[0].kind, RedirKind::Out);
+        assert_eq!(text(&cmds[1].simple().redirs[0].target), "out");
+        let last = s[0].rest[0].1.cmds[0].simple();
+        assert_eq!(
+            last.redirs
+                .iter()
+                .map(|r| (r.kind, text(&r.target)))
+                .collect::<Vec<_>>(),
+            vec![
+                (RedirKind::In, "in".to_string()),
+                (RedirKind::Append, "log".to_string())
+            ]
+        );
+        assert_eq!(argv("> f"), vec![Vec::<String>::new()]);
+        let s = parse("ls 2>&1 2>e 2>>e x2>y").unwrap();
+        let c = s[0].first.cmds[0].simple();
+        assert_eq!(
+            c.redirs.iter().map(|r| r.kind).collect::<Vec<_>>(),
+            vec![
+                RedirKind::ErrToOut,
+                RedirKind::ErrOut,
+                RedirKind::ErrAppend,
+                RedirKind::Out
+            ]
+        );
+        assert_eq!(
+            c.words.iter().map(text).collect::<Vec<_>>(),
+            vec!["ls", "x2"]
+        );
+        assert_eq!(argv("echo 'a|b' \\>c"), vec![vec!["echo", "a|b", ">c"]]);
+    }
+
+    #[test]
+    fn incomplete() {
+        for src in [
+            "echo 'abc",
+            "echo \"abc",
+            "echo abc\\",
+            "a &&",
+            "a ||\n",
+            "echo \"a\\",
+            "ls |",
+            "ls |\n\n",
+        ] {
+            assert_eq!(parse(src), Err(ParseError::Incomplete), "{}", src);
+        }
+        assert_eq!(argv("echo 'a\nb'"), vec![vec!["echo", "a\nb"]]);
+        assert_eq!(argv("echo ab\\\ncd"), vec![vec!["echo", "abcd"]]);
+        assert_eq!(argv("echo \\\nb"), vec![vec!["echo", "b"]]);
+    }
+
+    #[test]
+    fn aliases() {
+        let al = |n: &str| match n {
+            "ll" => Some("ls -l".to_string()),
+            "ls" => Some("ls -F".to_string()),
+            "both" => Some("ll; ls".to_string()),
+            "e" => Some("".to_string()),
+            "bad" => Some("echo 'x".to_string()),
+            _ => None,
+        };
+        let p = |src: &str| words(parse_aliased(src, &al).unwrap());
+        assert_eq!(p("ll /"), vec![vec!["ls", "-F", "-l", "/"]]);
+        assert_eq!(
+            p("echo ll | ll"),
+            vec![vec!["echo", "ll"], vec!["ls", "-F", "-l"]]
+        );
+        assert_eq!(
+            p("both x"),
+            vec![vec!["ls", "-F", "-l"], vec!["ls", "-F", "x"]]
+        );
+        assert_eq!(
+            p("'ll'; \\ls; e ls"),
+            vec![vec!["ll"], vec!["ls"], vec!["ls", "-F"]]
+        );
+        assert!(matches!(
+            parse_aliased("ls; bad", &al),
+            Err(ParseError::Syntax { pos: 4, .. })
+        ));
+    }
+
+    #[test]
+    fn compounds() {
+        let first = |src: &str| parse(src).unwrap()[0].first.cmds[0].clone();
+        match first("if a; then b; elif c\nthen d; else e; fi") {
+            Command::Compound(Compound::If { branches, els }, _) => {
+                assert_eq!(branches.len(), 2);
+                assert_eq!(words(els.unwrap()), vec![vec!["e"]]);
+            }
+            c => panic!("{:?}", c),
+        }
+        match first("until a; do b; c; done >log") {
+            Command::Compound(Compound::Loop { until, body, .. }, r) => {
+                assert!(until);
+                assert_eq!(words(body), vec![vec!["b"], vec!["c"]]);
+                assert_eq!(r[0].kind, RedirKind::Out);
+            }
+            c => panic!("{:?}", c),
+        }
+        match first("for x in a 'b c'; do echo $x; done") {
+            Command::Compound(
+                Compound::For {
+                    var,
+                    words: Some(w),
+                    ..
+                },
+                _,
+            ) => {
+                assert_eq!(var, "x");
+                assert_eq!(w.iter().map(text).collect::<Vec<_>>(), vec!["a", "b c"]);
+            }
+            c => panic!("{:?}", c),
+        }
+        match first("case $x in a|b) one;; (*) two; esac") {
+            Command::Compound(Compound::Case { arms, .. }, _) => {
+                assert_eq!(arms.len(), 2);
+                assert_eq!(arms[0].0.len(), 2);
+                assert_eq!(words(arms[1].1.clone()), vec![vec!["two"]]);
+            }
+            c => panic!("{:?}", c),
+        }
+        assert!(matches!(first("f() { a; }"), Command::FuncDef(n, _) if n == "f"));
+        assert!(matches!(
+            first("(a; b)"),
+            Command::Compound(Compound::Subshell(_), _)
+        ));
+        let s = parse("! a | b").unwrap();
+        assert!(s[0].first.bang);
+        // reserved words are only special where a command starts
+        assert_eq!(
+            argv("echo if then fi"),
+            vec![vec!["echo", "if", "then", "fi"]]
+        );
+        for src in [
+            "if a; then b",
+            "while a; do",
+            "for x in a",
+            "case a in",
+            "{ a;",
+        ] {
+            assert_eq!(parse(src), Err(ParseError::Incomplete), "{}", src);
+        }
+        for src in [
+            "if a; fi",
+            "then",
+            "while; do a; done",
+            "for 1 in a; do b; done",
+            "{ }",
+        ] {
+            assert!(
+                matches!(parse(src), Err(ParseError::Syntax { .. })),
+                "{}",
+                src
+            );
+        }
+    }
+
+    #[test]
+    fn syntax_errors() {
+        let e = parse("ls ;; pwd").unwrap_err();
+        assert_eq!(
+            e.report("ls ;; pwd"),
+            "irun: syntax error near unexpected token `;;' at column 4\nls ;; pwd\n   ^"
+        );
+        assert!(matches!(
+            parse("&& ls"),
+            Err(ParseError::Syntax { pos: 0, .. })
+        ));
+        assert!(matches!(
+            parse("ls | | cat"),
+            Err(ParseError::Syntax { pos: 5, .. })
+        ));
+        assert_eq!(
+            parse("echo >").unwrap_err().report("echo >"),
+            "irun: syntax error near unexpected token `newline' at column 7\necho >\n      ^"
+        );
+        assert!(matches!(
+            parse("cat < > f"),
+            Err(ParseError::Syntax { pos: 6, .. })
+        ));
+        assert!(matches!(
+            parse("ls & "),
+            Err(ParseError::Syntax { pos: 3, .. })
+        ));
+        let src = "echo 'a\n' ; ;";
+        assert_eq!(
+            parse(src).unwrap_err().report(src),
+            "irun: syntax error near unexpected token `;' at line 2, column 5\n' ; ;\n    ^"
+        );
+    }
+}
@@ -0,0 +1,293 @@
+// What a command gets to work with when it runs.
+// Commands never see xterm: they write to Sinks, and the terminal is
+// just one kind of sink. Pipes and redirections hand out Buffers
+// instead, which is also how commands are tested natively.
+
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::pin::Pin;
+
+use xterm_js_rs::Terminal;
+
+use crate::common::{Getopt, Parsed, Spec};
+use crate::session::Session;
+
+pub trait Sink {
+    fn write(&self, s: &str);
+    fn writeln(&self, s: &str) {
+        self.write(s);
+        self.write("\n");
+    }
+    // columns and rows, if this is a terminal
+    fn size(&self) -> Option<(usize, usize)> {
+        None
+    }
+}
+
+impl Sink for Terminal {
+    fn write(&self, s: &str) {
+        Terminal::write(self, s);
+    }
+    fn writeln(&self, s: &str) {
+        Terminal::writeln(self, s);
+    }
+    fn size(&self) -> Option<(usize, usize)> {
+        Some((self.get_cols() as usize, self.get_rows() as usize))
+    }
+}
+
+// What a command that has to wait for something (input, a timer,
+// another command) gives back: its exit status, eventually.
+pub type Task<'a> = Pin<Box<dyn Future<Output = i32> + 'a>>;
+
+// output kept in memory, for pipes and redirections
+pub struct Buffer(RefCell<String>);
+
+impl Buffer {
+    pub fn new() -> Self {
+        Self(RefCell::new(String::new()))
+    }
+    pub fn take(&self) -> String {
+        self.0.take()
+    }
+}
+
+impl Sink for Buffer {
+    fn write(&self, s: &str) {
+        self.0.borrow_mut().push_str(s);
+    }
+}
+
+pub struct Proc<'a> {
+    // the shell it runs in, which builtins change
+    pub sh: &'a Session,
+    // without the command name
+    pub args: Vec<String>,
+    // None when standard input is the terminal, which can't be read yet
+    stdin: RefCell<Option<String>>,
+    pub stdout: &'a dyn Sink,
+    pub stderr: &'a dyn Sink,
+    // the exported variables and working directory as they were when
+    // the command started; builtins that change them go to sh.vars
+    pub env: BTreeMap<String, String>,
+    pub cwd: String,
+}
+
+impl<'a> Proc<'a> {
+    pub fn new(
+        sh: &'a Session,
+        args: &[String],
+        stdin: Option<String>,
+        stdout: &'a dyn Sink,
+        stderr: &'a dyn Sink,
+    ) -> Self {
+        Self {
+            sh,
+            args: args.to_vec(),
+            stdin: RefCell::new(stdin),
+            stdout,
+            stderr,
+            env: sh.vars.exported().into_iter().collect(),
+            cwd: sh.vars.load_cwd(),
+        }
+    }
+    // the same process, running with other arguments
+    pub fn with_args(&self, args: &[&str]) -> Proc<'a> {
+        Proc {
+            sh: self.sh,
+            args: args.iter().map(|x| x.to_string()).collect(),
+            stdin: RefCell::new(self.stdin.borrow().clone()),
+            stdout: self.stdout,
+            stderr: self.stderr,
+            env: self.env.clone(),
+            cwd: self.cwd.clone(),
+        }
+    }
+    pub fn args(&self) -> Vec<&str> {
+        self.args.iter().map(|x| x.as_str()).collect()
+    }
+    // Reads all of standard input. Like a real pipe, it's empty
+    // once it has been read.
+    pub fn read_stdin(&self) -> Option<String> {
+        self.stdin.borrow_mut().as_mut().map(std::mem::take)
+    }
+    // The next line of standard input, with its newline if it had
+    // one. At the terminal, this waits for it to be typed. None at
+    // the end, or once Ctrl-C has been pressed.
+    pub async fn read_line(&self) -> Option<String> {
+        if let Some(s) = self.stdin.borrow_mut().as_mut() {
+            if s.is_empty() {
+                return None;
+            }
+            let n = s.find('\n').map_or(s.len(), |i| i + 1);
+            return Some(s.drain(..n).collect());
+        }
+        self.sh.tty.read_line().await
+    }
+    // whether standard input is the terminal, to ask the user things
+    pub fn interactive(&self) -> bool {
+        self.stdin.borrow().is_none()
+    }
+    // How wide output can be: the terminal's width, or else $COLUMNS
+    // if it was exported, or else 80.
+    pub fn width(&self) -> usize {
+        match self.stdout.size() {
+            Some((cols, _)) => cols,
+            None => self
+                .env
+                .get("COLUMNS")
+                .and_then(|c| c.parse().ok())
+                .unwrap_or(80),
+        }
+    }
+    pub fn minfo(&self, pname: &str) {
+        self.stderr
+            .writeln(&format!("Try '{} --help' for more information.", pname));
+    }
+    // The arguments, as spec reads them. --help, --version and anything
+    // wrong are dealt with here; Err is then what to exit with.
+    pub fn getopt<'s>(&'s self, spec: &'s Spec) -> Result<Parsed<'s>, i32> {
+        self.getopt_from(spec, &self.args())
+    }
+    // the same, for when some of the arguments aren't for spec
+    pub fn getopt_from<'s>(&self, spec: &'s Spec, args: &[&'s str]) -> Result<Parsed<'s>, i32> {
+        match spec.parse(args) {
+            Getopt::Run(parsed) => Ok(parsed),
+            Getopt::Help(h) => {
+                self.stdout.writeln(&h);
+                Err(0)
+            }
+            Getopt::Version(v) => {
+                self.stdout.writeln(v);
+                Err(0)
+            }
+            Getopt::Error(e) => {
+                self.stderr.writeln(&e);
+                self.minfo(spec.name);
+                Err(1)
+            }
+        }
+    }
+}
+
+// Tests that change the VFS or the consoles, which every shell shares,
+// hold this, so they can't race each other.
+#[cfg(test)]
+pub fn shell_lock() -> std::sync::MutexGuard<'static, ()> {
+    static LOCK: std::sync::Mutex<()> = std::sync::Mutex::new(());
+    LOCK.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+// The shell that tests run commands in: one per test thread, so tests
+// can't see each other's variables or cwd.
+#[cfg(test)]
+pub fn test_sh() -> std::rc::Rc<Session> {
+    thread_local! {
+        static SH: std::rc::Rc<Session> = std::rc::Rc::new(Session::new(1));
+    }
+    SH.with(|s| s.clone())
+}
+
+// Runs a command in test_sh() against in-memory buffers; gives back
+// its exit status, stdout and stderr.
+#[cfg(test)]
+pub fn run_test(f: crate::PathFn, args: &[&str], stdin: Option<&str>) -> (i32, String, String) {
+    let (out, err) = (Buffer::new(), Buffer::new());
+    let args: Vec<String> = args.iter().map(|x| x.to_string()).collect();
+    let sh = test_sh();
+    let st = f(&Proc::new(
+        &sh,
+        &args,
+        stdin.map(|x| x.to_string()),
+        &out,
+        &err,
+    ));
+    (st, out.take(), err.take())
+}
+
+// the same, for commands that are tasks
+#[cfg(test)]
+pub fn run_task_test(
+    f: crate::TaskFn,
+    args: &[&str],
+    stdin: Option<&str>,
+) -> (i32, String, String) {
+    let (out, err) = (Buffer::new(), Buffer::new());
+    let args: Vec<String> = args.iter().map(|x| x.to_string()).collect();
+    let sh = test_sh();
+    let st = block_on(f(&Proc::new(
+        &sh,
+        &args,
+        stdin.map(|x| x.to_string()),
+        &out,
+        &err,
+    )));
+    (st, out.take(), err.take())
+}
+
+// Runs a future that doesn't need the browser to finish. Nothing
+// natively can wake it, so it must not wait on the terminal.
+#[cfg(test)]
+pub fn block_on<F: Future>(f: F) -> F::Output {
+    use std::task::{Context, Poll, Waker};
+    let mut f = std::pin::pin!(f);
+    match f.as_mut().poll(&mut Context::from_waker(Waker::noop())) {
+        Poll::Ready(v) => v,
+        Poll::Pending => panic!("test is waiting on the terminal"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn commands() {
+        use crate::unix::{cat::cat, echo::echo};
+        assert_eq!(
+            run_test(echo, &["a", "b"], None),
+            (0, "a b\n".into(), "".into())
+        );
+        let cat: crate::TaskFn = |p| Box::pin(cat(p));
+        assert_eq!(
+            run_task_test(cat, &[], Some("x\ny\n")),
+            (0, "x\ny\n".into(), "".into())
+        );
+        // reading the terminal, after a Ctrl-C
+        test_sh().tty.interrupt();
+        assert_eq!(
+            run_task_test(cat, &["-"], None),
+            (130, "".into(), "".into())
+        );
+        test_sh().tty.reset();
+        let (st, _, err) = run_test(crate::builtins::set, &["-e"], None);
+        assert_eq!(st, 2);
+        assert!(err.contains("usage"));
+    }
+
+    #[test]
+    fn pipes() {
+        let sh = &Session::new(1);
+        let (out, err) = (Buffer::new(), Buffer::new());
+        sh.vars.export("PROC_T", Some("v w"));
+        let st = block_on(crate::run_shell_instruction(
+            sh,
+            &out,
+            &err,
+            "echo $PROC_T | cat; env | cat 2>&1 | cat",
+        ));
+        assert_eq!(st, 0);
+        let out = out.take();
+        assert!(out.starts_with("v w\n"));
+        assert!(out.contains("\nPROC_T=v w\n"));
+        assert_eq!(err.take(), "");
+        block_on(crate::run_shell_instruction(
+            sh,
+            &Buffer::new(),
+            &err,
+            "nosuchcmd",
+        ));
+        assert_eq!(err.take(), "irun: nosuchcmd: command not found...\n");
+    }
+}
@@ -0,0 +1,175 @@
+// PS1 and PS2, the way bash reads them: backslash escapes first
+// (\u, \w, \$ and friends, see expand below), then $VAR and $? in what
+// was written literally. The result can have colors in it, which
+// linebuf::str_width knows take up no room.
+
+use crate::expand;
+use crate::session::Session;
+use crate::unix::uname::UNAME_NODENAME;
+
+// hours, minutes and seconds of the local time
+#[cfg(target_arch = "wasm32")]
+fn clock() -> (u32, u32, u32) {
+    let d = js_sys::Date::new_0();
+    (d.get_hours(), d.get_minutes(), d.get_seconds())
+}
+
+// natively (in tests) there's no time zone to speak of
+#[cfg(not(target_arch = "wasm32"))]
+fn clock() -> (u32, u32, u32) {
+    let t = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs() % 86400) as u32;
+    (t / 3600, t / 60 % 60, t % 60)
+}
+
+// the working directory as \w shows it: home is ~, unless home is /
+fn cwd(sh: &Session) -> String {
+    let cwd = sh.vars.load_cwd();
+    let cwd = match cwd.len() {
+        1 => cwd,
+        _ => cwd.trim_end_matches('/').to_string(),
+    };
+    match sh.vars.getenv("HOME") {
+        Some(h) if h.len() > 1 && (cwd == h || cwd.starts_with(&(h.clone() + "/"))) => {
+            format!("~{}", &cwd[h.len()..])
+        }
+        _ => cwd,
+    }
+}
+
+fn twelve(h: u32) -> u32 {
+    match h % 12 {
+        0 => 12,
+        h => h,
+    }
+}
+
+// What the escape \c stands for, or None if it isn't one
+// (bash leaves those as they are).
+fn escape(sh: &Session, c: char) -> Option<String> {
+    let user = || sh.vars.getenv("USER").unwrap_or_default();
+    let (h, m, s) = clock();
+    Some(match c {
+        'u' => user(),
+        'h' => UNAME_NODENAME.split('.').next().unwrap().to_string(),
+        'H' => UNAME_NODENAME.to_string(),
+        'w' => cwd(sh),
+        'W' => match cwd(sh).as_str() {
+            "/" => "/".to_string(),
+            w => w.rsplit('/').next().unwrap().to_string(),
+        },
+        // by who it runs as, like bash's effective uid, not by $USER
+        '$' => if sh.vars.creds().uid == 0 { "#" } else { "$" }.to_string(),
+        's' => "irun".to_string(),
+        // the terminal, as in tty2
+        'l' => format!("tty{}", sh.n),
+        // the exit status of the last command, for colouring by it
+        '?' => sh.vars.status().to_string(),
+        't' => format!("{:02}:{:02}:{:02}", h, m, s),
+        'T' => format!("{:02}:{:02}:{:02}", twelve(h), m, s),
+        '@' => format!(
+            "{:02}:{:02} {}",
+            twelve(h),
+            m,
+            if h < 12 { "AM" } else { "PM" }
+        ),
+        'A' => format!("{:02}:{:02}", h, m),
+        'n' => "\r\n".to_string(),
+        'e' => "\x1b".to_string(),
+        'a' => "\x07".to_string(),
+        '\\' => "\\".to_string(),
+        // they mark where the invisible bits are, which doesn't
+        // matter here, since widths skip escape sequences anyway
+        '[' | ']' => String::new(),
+        _ => return None,
+    })
+}
+
+// Turns the value of PS1 (or PS2) into what to write. Besides the
+// escapes above there's \NNN, a character in octal, as in \033[1;32m.
+pub fn expand(sh: &Session, ps: &str) -> String {
+    let mut out = String::new();
+    // written literally, so $ still has to be expanded
+    let mut lit = String::new();
+    let flush = |lit: &mut String, out: &mut String| {
+        out.push_str(&expand::expand_text(sh, lit).unwrap_or_else(|_| lit.clone()));
+        lit.clear();
+    };
+    let mut it = ps.chars().peekable();
+    while let Some(c) = it.next() {
+        if c != '\\' {
+            lit.push(c);
+            continue;
+        }
+        let Some(e) = it.next() else {
+            lit.push('\\');
+            break;
+        };
+        if let Some(mut n) = e.to_digit(8) {
+            for _ in 0..2 {
+                match it.peek().and_then(|d| d.to_digit(8)) {
+                    Some(d) => n = n * 8 + d,
+                    None => break,
+                }
+                it.next();
+            }
+            flush(&mut lit, &mut out);
+            out.extend(char::from_u32(n));
+            continue;
+        }
+        match escape(sh, e) {
+            Some(s) => {
+                flush(&mut lit, &mut out);
+                out.push_str(&s);
+            }
+            None => {
+                lit.push('\\');
+                lit.push(e);
+            }
+        }
+    }
+    flush(&mut lit, &mut out);
+    // the terminal wants \r\n, even from a PS1 with a real newline
+    out.replace("\r\n", "\n").replace('\n', "\r\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn escapes() {
+        let sh = &Session::new(2);
+        let expand = |ps| expand(sh, ps);
+        sh.vars.setenv("USER", "root");
+        sh.vars.setenv("HOME", "/");
+        sh.vars.store_cwd("/home/amy/src/".to_string());
+        assert_eq!(expand("\\u@\\H:\\w$ "), "root@amyip.net:/home/amy/src$ ");
+        assert_eq!(expand("\\h \\W \\$ \\s \\l"), "amyip src # irun tty2");
+        sh.vars.setenv("HOME", "/home/amy");
+        assert_eq!(expand("\\w|\\W"), "~/src|src");
+        sh.vars.store_cwd("/home/amy/".to_string());
+        assert_eq!(expand("\\w|\\W"), "~|~");
+        sh.vars.store_cwd("/".to_string());
+        assert_eq!(expand("\\W\\n\\q\\"), "/\r\n\\q\\");
+        assert_eq!(expand("\\[\\e[32m\\]ok\\[\\033[0m\\]"), "\x1b[32mok\x1b[0m");
+        assert_eq!(expand("\\101\\0\\79"), "A\0\u{7}9");
+        sh.vars.set_status(3);
+        sh.vars.setenv("X", "x");
+        assert_eq!(expand("\\? $? ${X}\\$X"), "3 3 x#X");
+        let t = expand("\\t");
+        assert_eq!((t.len(), &t[2..3], &t[5..6]), (8, ":", ":"));
+        assert!(expand("\\@").ends_with('M'));
+        // root is whoever runs as uid 0, whatever $USER says
+        sh.vars.setenv("USER", "amy");
+        assert_eq!(expand("\\$"), "#");
+        sh.vars.set_creds(crate::sysvars::Creds {
+            uid: 1000,
+            gid: 1000,
+            groups: vec![],
+        });
+        sh.vars.setenv("USER", "root");
+        assert_eq!(expand("\\$"), "$");
+    }
+}
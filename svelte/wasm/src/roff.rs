@@ -0,0 +1,556 @@
+// Just enough roff for man pages: the man macros people actually use
+// (TH, SH, SS, PP, TP, IP, RS/RE, B, I and friends), font changes, the
+// common escapes and no-fill blocks, filled to whatever width there
+// is. Bold comes out bold and italics underlined, the way man shows
+// them on a terminal. Anything else is quietly skipped.
+
+use crate::linebuf::str_width;
+
+// how far body text sits in from the headings
+const INDENT: usize = 7;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum Font {
+    R,
+    B,
+    I,
+}
+
+impl Font {
+    fn on(self) -> &'static str {
+        match self {
+            Font::R => "",
+            Font::B => "\x1b[1m",
+            Font::I => "\x1b[4m",
+        }
+    }
+    fn off(self) -> &'static str {
+        match self {
+            Font::R => "",
+            Font::B => "\x1b[22m",
+            Font::I => "\x1b[24m",
+        }
+    }
+}
+
+// a word, in however many fonts it takes
+type Word = Vec<(Font, String)>;
+
+fn put(w: &mut Word, f: Font, s: &str) {
+    match w.last_mut() {
+        Some((lf, t)) if *lf == f => t.push_str(s),
+        _ => w.push((f, s.to_string())),
+    }
+}
+
+fn width(w: &Word) -> usize {
+    w.iter().map(|(_, s)| str_width(s)).sum()
+}
+
+fn show(w: &Word) -> String {
+    w.iter()
+        .map(|(f, s)| format!("{}{}{}", f.on(), s, f.off()))
+        .collect()
+}
+
+// the \(xx and \[xx] characters worth knowing
+fn special(name: &str) -> &'static str {
+    match name {
+        "em" => "—",
+        "en" => "–",
+        "aq" => "'",
+        "lq" | "rq" | "dq" => "\"",
+        "bu" => "•",
+        "co" => "©",
+        "mi" | "hy" => "-",
+        "ti" => "~",
+        "ha" => "^",
+        "rs" => "\\",
+        _ => "",
+    }
+}
+
+// the name after \f or \(: one character, (xx or [xxx]
+fn escape_name(cs: &mut std::iter::Peekable<std::str::Chars>) -> String {
+    match cs.next() {
+        Some('(') => cs.by_ref().take(2).collect(),
+        Some('[') => cs.by_ref().take_while(|&c| c != ']').collect(),
+        Some(c) => c.to_string(),
+        None => String::new(),
+    }
+}
+
+// Fonts carry on from one line to the next, and \fP goes back to the
+// one before.
+struct Fonts {
+    cur: Font,
+    prev: Font,
+}
+
+impl Fonts {
+    fn set(&mut self, f: Font) {
+        self.prev = self.cur;
+        self.cur = f;
+    }
+}
+
+// Breaks a line of text into words, working out the escapes. With
+// keep, spaces are kept and it all comes back as one word, for no-fill.
+fn words(line: &str, fonts: &mut Fonts, keep: bool) -> Vec<Word> {
+    let mut ws = vec![];
+    let mut w = Word::new();
+    let mut cs = line.chars().peekable();
+    while let Some(c) = cs.next() {
+        if c != '\\' {
+            if c.is_whitespace() && !keep {
+                if !w.is_empty() {
+                    ws.push(std::mem::take(&mut w));
+                }
+            } else {
+                put(&mut w, fonts.cur, c.encode_utf8(&mut [0; 4]));
+            }
+            continue;
+        }
+        match cs.peek().copied() {
+            Some('(') | Some('[') => {
+                let name = escape_name(&mut cs);
+                put(&mut w, fonts.cur, special(&name));
+                continue;
+            }
+            _ => {}
+        }
+        match cs.next() {
+            Some('f') => {
+                let f = match escape_name(&mut cs).as_str() {
+                    "B" | "3" => Font::B,
+                    "I" | "2" => Font::I,
+                    "P" => fonts.prev,
+                    _ => Font::R,
+                };
+                fonts.set(f);
+            }
+            // a comment
+            Some('"') => break,
+            Some('-') => put(&mut w, fonts.cur, "-"),
+            Some('e') | Some('\\') => put(&mut w, fonts.cur, "\\"),
+            // a space that doesn't break
+            Some(' ') | Some('~') => put(&mut w, fonts.cur, " "),
+            // zero-width ones, and joining lines
+            Some('&') | Some('|') | Some('^') | Some('c') | None => {}
+            Some(c) => put(&mut w, fonts.cur, c.encode_utf8(&mut [0; 4])),
+        }
+    }
+    if !w.is_empty() {
+        ws.push(w);
+    }
+    ws
+}
+
+// A macro's arguments: split on spaces, except inside "quotes", where
+// "" is a quote.
+fn args(s: &str) -> Vec<String> {
+    let mut out = vec![];
+    let mut cs = s.chars().peekable();
+    loop {
+        while cs.peek() == Some(&' ') {
+            cs.next();
+        }
+        let mut a = String::new();
+        match cs.peek() {
+            None => break,
+            Some('"') => {
+                cs.next();
+                while let Some(c) = cs.next() {
+                    match c {
+                        '"' if cs.peek() == Some(&'"') => {
+                            cs.next();
+                            a.push('"');
+                        }
+                        '"' => break,
+                        c => a.push(c),
+                    }
+                }
+            }
+            Some(_) => {
+                while let Some(&c) = cs.peek() {
+                    if c == ' ' {
+                        break;
+                    }
+                    a.push(c);
+                    cs.next();
+                }
+            }
+        }
+        out.push(a);
+    }
+    out
+}
+
+// leaves off a \" comment
+fn uncomment(s: &str) -> &str {
+    match s.find("\\\"") {
+        Some(i) => &s[..i],
+        None => s,
+    }
+}
+
+// l at the left, c in the middle and r at the right, as in a header
+fn three(l: &str, c: &str, r: &str, width: usize) -> String {
+    let (lw, cw, rw) = (str_width(l), str_width(c), str_width(r));
+    let lgap = ((width.saturating_sub(cw)) / 2).saturating_sub(lw).max(1);
+    let rgap = width.saturating_sub(lw + lgap + cw + rw).max(1);
+    format!("{}{}{}{}{}", l, " ".repeat(lgap), c, " ".repeat(rgap), r)
+}
+
+struct Page {
+    width: usize,
+    lines: Vec<String>,
+    // what's waiting to be filled into lines
+    words: Vec<Word>,
+    // where paragraphs start, and where the text goes now
+    base: usize,
+    indent: usize,
+    // the bases from before each RS
+    rs: Vec<usize>,
+    // from TP and IP: what goes at base in front of the next line
+    tag: Option<Vec<Word>>,
+    // TP takes its tag from the next line of text
+    want_tag: bool,
+    fonts: Fonts,
+    nofill: bool,
+    // the arguments to TH
+    title: Vec<String>,
+    // how many lines there were after the last heading, which
+    // paragraphs don't put a blank line under
+    heading: usize,
+}
+
+impl Page {
+    fn new(width: usize) -> Self {
+        Self {
+            width: width.max(INDENT + 10),
+            lines: vec![],
+            words: vec![],
+            base: INDENT,
+            indent: INDENT,
+            rs: vec![],
+            tag: None,
+            want_tag: false,
+            fonts: Fonts {
+                cur: Font::R,
+                prev: Font::R,
+            },
+            nofill: false,
+            title: vec![],
+            heading: 0,
+        }
+    }
+
+    // Lays out everything waiting, a line at a time, with a tag in
+    // front if there is one. A tag too wide to share its line with
+    // the text gets a line of its own.
+    fn flush(&mut self) {
+        let words = std::mem::take(&mut self.words);
+        // the line so far, and how wide it is
+        let mut line: Option<(String, usize)> = None;
+        if let Some(tag) = self.tag.take() {
+            let t = tag.iter().map(show).collect::<Vec<String>>().join(" ");
+            let tw = self.base + str_width(&t);
+            let t = " ".repeat(self.base) + &t;
+            if tw < self.indent && !words.is_empty() {
+                line = Some((t + &" ".repeat(self.indent - tw), self.indent));
+            } else {
+                self.lines.push(t);
+            }
+        }
+        let mut fresh = true;
+        for w in words {
+            let (s, ww) = (show(&w), width(&w));
+            match &mut line {
+                Some((l, lw)) if fresh => {
+                    l.push_str(&s);
+                    *lw += ww;
+                }
+                Some((l, lw)) if *lw + 1 + ww <= self.width => {
+                    l.push(' ');
+                    l.push_str(&s);
+                    *lw += 1 + ww;
+                }
+                _ => {
+                    if let Some((l, _)) = line.take() {
+                        self.lines.push(l);
+                    }
+                    line = Some((" ".repeat(self.indent) + &s, self.indent + ww));
+                }
+            }
+            fresh = false;
+        }
+        if let Some((l, _)) = line {
+            self.lines.push(l);
+        }
+    }
+
+    // a blank line, unless there's one already
+    fn blank(&mut self) {
+        self.flush();
+        if self.lines.len() != self.heading && self.lines.last().is_some_and(|l| !l.is_empty()) {
+            self.lines.push(String::new());
+        }
+    }
+
+    fn text(&mut self, line: &str) {
+        if self.nofill {
+            self.flush();
+            let ws = words(line, &mut self.fonts, true);
+            let s: String = ws.iter().map(show).collect();
+            self.lines.push(" ".repeat(self.indent) + &s);
+            return;
+        }
+        if line.trim().is_empty() {
+            self.blank();
+            return;
+        }
+        // in roff, a line that starts with a space starts a new line
+        if line.starts_with(' ') {
+            self.flush();
+        }
+        let ws = words(line, &mut self.fonts, false);
+        if self.want_tag {
+            self.want_tag = false;
+            self.tag = Some(ws);
+        } else {
+            self.words.extend(ws);
+        }
+    }
+
+    // an indent given to a macro, or the usual one
+    fn amount(a: Option<&String>) -> usize {
+        a.and_then(|n| n.trim_end_matches('n').parse().ok())
+            .unwrap_or(INDENT)
+    }
+
+    fn mac(&mut self, name: &str, a: Vec<String>) {
+        match name {
+            "TH" => self.title = a,
+            "SH" | "SS" => {
+                self.blank();
+                self.rs.clear();
+                self.base = INDENT;
+                self.indent = INDENT;
+                self.tag = None;
+                self.want_tag = false;
+                let mut bold = Fonts {
+                    cur: Font::B,
+                    prev: Font::B,
+                };
+                let h: String = words(&a.join(" "), &mut bold, true)
+                    .iter()
+                    .map(show)
+                    .collect();
+                let at = if name == "SH" { 0 } else { 3 };
+                self.lines.push(" ".repeat(at) + &h);
+                self.heading = self.lines.len();
+            }
+            "PP" | "P" | "LP" => {
+                self.blank();
+                self.indent = self.base;
+            }
+            "TP" => {
+                self.blank();
+                self.indent = self.base + Self::amount(a.first());
+                self.want_tag = true;
+            }
+            "IP" => {
+                self.blank();
+                self.indent = self.base + Self::amount(a.get(1));
+                if let Some(t) = a.first().filter(|t| !t.is_empty()) {
+                    self.tag = Some(words(t, &mut self.fonts, false));
+                }
+            }
+            "RS" => {
+                self.flush();
+                self.rs.push(self.base);
+                self.base += Self::amount(a.first());
+                self.indent = self.base;
+            }
+            "RE" => {
+                self.flush();
+                self.base = self.rs.pop().unwrap_or(INDENT);
+                self.indent = self.base;
+            }
+            "br" => self.flush(),
+            "sp" => {
+                self.flush();
+                self.lines.push(String::new());
+            }
+            "nf" | "EX" => {
+                self.flush();
+                self.nofill = true;
+            }
+            "fi" | "EE" => {
+                self.flush();
+                self.nofill = false;
+            }
+            "B" | "I" if !a.is_empty() => self.text(&format!("\\f{}{}\\fR", name, a.join(" "))),
+            "BR" | "RB" | "IR" | "RI" | "BI" | "IB" => {
+                let fonts: Vec<char> = name.chars().collect();
+                let mut s: String = a
+                    .iter()
+                    .enumerate()
+                    .map(|(i, x)| format!("\\f{}{}", fonts[i % 2], x))
+                    .collect();
+                s.push_str("\\fR");
+                self.text(&s);
+            }
+            _ => {}
+        }
+    }
+
+    fn finish(mut self) -> Vec<String> {
+        self.flush();
+        while self.lines.last().is_some_and(|l| l.is_empty()) {
+            self.lines.pop();
+        }
+        if self.title.is_empty() {
+            return self.lines;
+        }
+        let t = |i: usize| self.title.get(i).map(|s| s.as_str()).unwrap_or("");
+        let name = format!("{}({})", t(0), t(1));
+        let head = three(&name, t(4), &name, self.width);
+        let foot = three(t(3), t(2), &name, self.width);
+        let mut lines = vec![head, String::new()];
+        lines.append(&mut self.lines);
+        lines.push(String::new());
+        lines.push(foot);
+        lines
+    }
+}
+
+// Whether a line is a request, and if so, which one with what
+fn request(line: &str) -> Option<(&str, Vec<String>)> {
+    let rest = line.strip_prefix('.').or_else(|| line.strip_prefix('\''))?;
+    let rest = uncomment(rest).trim_start();
+    let (name, a) = rest.split_once(' ').unwrap_or((rest, ""));
+    Some((name, args(a)))
+}
+
+// Lays out a man page to fit width columns.
+pub fn render(src: &str, width: usize) -> Vec<String> {
+    let mut page = Page::new(width);
+    for line in src.lines() {
+        match request(line) {
+            Some((name, a)) => page.mac(name, a),
+            None => page.text(line),
+        }
+    }
+    page.finish()
+}
+
+// The text of the NAME section, as plain as it comes, which is where
+// whatis and apropos find what a page is about. By convention it's
+// "name, other \- what it does".
+pub fn name_section(src: &str) -> Option<String> {
+    let mut lines = src.lines();
+    lines.find(|l| {
+        request(l).is_some_and(|(n, a)| n == "SH" && a.join(" ").eq_ignore_ascii_case("name"))
+    })?;
+    let mut fonts = Fonts {
+        cur: Font::R,
+        prev: Font::R,
+    };
+    let mut out = vec![];
+    for l in lines {
+        match request(l) {
+            Some(("SH", _)) => break,
+            Some(_) => {}
+            None => out.extend(
+                words(l, &mut fonts, false)
+                    .into_iter()
+                    .map(|w| w.into_iter().map(|(_, s)| s).collect::<String>()),
+            ),
+        }
+    }
+    match out.is_empty() {
+        true => None,
+        false => Some(out.join(" ")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PAGE: &str = r#".TH LS 1 2024 IrisOS-nano "User Commands"
+.\" a comment
+.SH NAME
+ls \- list directory contents
+.SH SYNOPSIS
+.B ls
+[\fIOPTION\fR]... [\fIFILE\fR]...
+.SH OPTIONS
+.TP
+.BR \-a ", " \-\-all
+do not ignore entries starting with .
+.TP
+.B \-\-a\-very\-long\-option
+shows up on a line of its own
+.SH EXAMPLE
+.nf
+  ls  -l
+.fi
+"#;
+
+    #[test]
+    fn render_page() {
+        let lines = render(PAGE, 40);
+        assert_eq!(
+            lines,
+            [
+                "LS(1)        User Commands         LS(1)",
+                "",
+                "\x1b[1mNAME\x1b[22m",
+                "       ls - list directory contents",
+                "",
+                "\x1b[1mSYNOPSIS\x1b[22m",
+                "       \x1b[1mls\x1b[22m [\x1b[4mOPTION\x1b[24m]... [\x1b[4mFILE\x1b[24m]...",
+                "",
+                "\x1b[1mOPTIONS\x1b[22m",
+                "       \x1b[1m-a\x1b[22m, \x1b[1m--all\x1b[22m",
+                "              do not ignore entries",
+                "              starting with .",
+                "",
+                "       \x1b[1m--a-very-long-option\x1b[22m",
+                "              shows up on a line of its",
+                "              own",
+                "",
+                "\x1b[1mEXAMPLE\x1b[22m",
+                "         ls  -l",
+                "",
+                "IrisOS-nano       2024             LS(1)",
+            ]
+        );
+        assert!(lines.iter().all(|l| str_width(l) <= 40));
+    }
+
+    #[test]
+    fn tags_and_escapes() {
+        let lines = render(
+            ".IP \\(bu 3\nan item\n.PP\n\\fBbold \\fIital\\fP again\\fR",
+            30,
+        );
+        assert_eq!(
+            lines,
+            [
+                "       •  an item",
+                "",
+                "       \x1b[1mbold\x1b[22m \x1b[4mital\x1b[24m \x1b[1magain\x1b[22m",
+            ]
+        );
+        assert_eq!(args(r#"a "b c" "say ""hi""""#), ["a", "b c", "say \"hi\""]);
+        assert_eq!(
+            name_section(PAGE).as_deref(),
+            Some("ls - list directory contents")
+        );
+        assert_eq!(name_section(".SH SYNOPSIS\nls"), None);
+    }
+}
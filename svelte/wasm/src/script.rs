@@ -0,0 +1,196 @@
+// Shell scripts: rc files, sh/source, and programs found on $PATH.
+// Scripts are run a command at a time, with lines joined up the same
+// way the editor does it, so quotes and trailing backslashes can span
+// lines.
+
+use either::Either;
+
+use crate::interp::{self, Flow};
+use crate::parse;
+use crate::process::Sink;
+use crate::session::Session;
+use crate::sysvars::Caller;
+use crate::vfs::futils::{find_file, read_file, read_to_end, resolve};
+use crate::vfs::{perm, VirtualFileSystem};
+
+// how deep scripts may run scripts, so that one which runs itself
+// runs out of this instead of the wasm stack
+const MAX_DEPTH: usize = 64;
+
+// Runs a whole script and gives the status of its last command.
+pub async fn run_text(sh: &Session, out: &dyn Sink, err: &dyn Sink, txt: &str) -> i32 {
+    if sh.depth.get() >= MAX_DEPTH {
+        err.writeln("irun: maximum script nesting level exceeded");
+        return 1;
+    }
+    sh.depth.set(sh.depth.get() + 1);
+    let mut status = 0;
+    let mut pending = String::new();
+    for line in txt.lines() {
+        if !pending.is_empty() {
+            pending.push('\n');
+        }
+        pending.push_str(line);
+        if parse::parse(&pending) != Err(parse::ParseError::Incomplete) {
+            status =
+                crate::run_shell_instruction(sh, out, err, &std::mem::take(&mut pending)).await;
+            // return and exit both end the script, but only return
+            // stops there; exit carries on out of a sourced script
+            if matches!(interp::flow(sh), Some(Flow::Return | Flow::Exit)) {
+                interp::take_flow(sh, Flow::Return);
+                break;
+            }
+            if sh.tty.interrupted() {
+                break;
+            }
+        }
+    }
+    if !pending.is_empty() && interp::flow(sh).is_none() && !sh.tty.interrupted() {
+        status = crate::run_shell_instruction(sh, out, err, &pending).await;
+        interp::take_flow(sh, Flow::Return);
+    }
+    sh.depth.set(sh.depth.get() - 1);
+    status
+}
+
+// Runs /etc/irunrc and then ~/.irunrc from whatever is mounted as the
+// rootfs, if they're there.
+pub async fn load_rc(sh: &Session, out: &dyn Sink, err: &dyn Sink) {
+    let home = sh.vars.getenv("HOME").unwrap_or_default();
+    let user_rc = format!("{}/.irunrc", home.trim_end_matches('/'));
+    for rc in ["/etc/irunrc", user_rc.as_str()] {
+        if let Some(t) = read_to_end(&sh.caller(), rc.to_string()) {
+            run_text(sh, out, err, &String::from_utf8_lossy(&t)).await;
+        }
+    }
+}
+
+pub fn file_perms(c: &Caller, path: &str) -> Option<u16> {
+    match find_file(c, path.to_string(), false) {
+        Either::Left((fs, fd)) => fs.file_perms(&fd),
+        Either::Right(_) => None,
+    }
+}
+
+pub fn is_dir(perms: u16) -> bool {
+    perms & 0xf000 == 0x1000
+}
+
+// whether the shell, as whoever it's running as, may do mode to path
+fn may(c: &Caller, path: &str, mode: u16) -> bool {
+    resolve(c, path.to_string()).is_ok_and(|(fs, fd)| fs.access(&fd, mode).is_ok())
+}
+
+// Looks a program up in the rootfs: as a path if there's a slash in
+// the name, otherwise in each directory of $PATH in turn.
+pub fn find_program(sh: &Session, name: &str) -> Option<String> {
+    let c = sh.caller();
+    if name.contains('/') {
+        return file_perms(&c, name).map(|_| name.to_string());
+    }
+    sh.vars
+        .getenv("PATH")
+        .unwrap_or_default()
+        .split(':')
+        .filter(|d| !d.is_empty())
+        .map(|d| format!("{}/{}", d.trim_end_matches('/'), name))
+        .find(|f| file_perms(&c, f).is_some_and(|m| !is_dir(m)))
+}
+
+// Runs a script as sh would: in a subshell, with args as $0 onwards.
+pub async fn run_script(
+    sh: &Session,
+    out: &dyn Sink,
+    err: &dyn Sink,
+    txt: &str,
+    args: Vec<String>,
+) -> i32 {
+    let funcs = interp::functions(sh);
+    let run = sh.vars.with_positional(args, run_text(sh, out, err, txt));
+    let st = sh.vars.subshell(run).await;
+    interp::set_functions(sh, funcs);
+    interp::take_flow(sh, Flow::Exit);
+    st
+}
+
+// Runs a program found by find_program. Only scripts can be run so
+// far: a #!/bin/sh line (or none at all, like bash) means sh.
+pub async fn run_program(
+    sh: &Session,
+    path: &str,
+    argv: &[String],
+    stdin: Option<String>,
+    out: &dyn Sink,
+    err: &dyn Sink,
+) -> i32 {
+    let c = sh.caller();
+    let perms = file_perms(&c, path).unwrap_or(0);
+    let why = if is_dir(perms) {
+        "Is a directory"
+    } else if !may(&c, path, perm::X) {
+        "Permission denied"
+    } else {
+        ""
+    };
+    if !why.is_empty() {
+        err.writeln(&format!("irun: {}: {}", argv[0], why));
+        return 126;
+    }
+    // scripts and wasm alike get read in here, so both need reading
+    let data = match read_file(&c, path.to_string()) {
+        Ok(d) => d,
+        Err(e) => {
+            err.writeln(&format!("irun: {}: {}", argv[0], e.errno()));
+            return 126;
+        }
+    };
+    if data.starts_with(crate::iris::MAGIC) {
+        return crate::iris::run(sh, &data, argv, stdin, out, err).await;
+    }
+    let txt = match String::from_utf8(data) {
+        Ok(t) if !t.contains('\0') => t,
+        _ => {
+            err.writeln(&format!(
+                "irun: {}: cannot execute binary file: Exec format error",
+                argv[0]
+            ));
+            return 126;
+        }
+    };
+    if let Some(interp) = txt.lines().next().and_then(|l| l.strip_prefix("#!")) {
+        let interp = interp.split_whitespace().next().unwrap_or("");
+        if !matches!(interp, "/bin/sh" | "/bin/irun") {
+            err.writeln(&format!(
+                "irun: {}: {}: bad interpreter: No such file or directory",
+                argv[0], interp
+            ));
+            return 126;
+        }
+    }
+    let mut args = vec![path.to_string()];
+    args.extend_from_slice(&argv[1..]);
+    run_script(sh, out, err, &txt, args).await
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::process::{run_task_test, test_sh};
+
+    #[test]
+    fn sh_c() {
+        crate::vfs::mount_test_fs();
+        let _l = crate::process::shell_lock();
+        let sh: crate::TaskFn = |p| Box::pin(crate::builtins::sh(p));
+        let (st, out, err) = run_task_test(
+            sh,
+            &["-c", "SCRIPT_T=1; cd /; echo $0 $1 $#; . /nope", "n", "a"],
+            None,
+        );
+        assert_eq!((st, out.as_str()), (1, "n a 1\n"));
+        assert_eq!(err, "source: /nope: No such file or directory\n");
+        // that was a subshell
+        assert_eq!(test_sh().vars.getenv("SCRIPT_T"), None);
+        let (st, out, _) = run_task_test(sh, &["-c", "echo $0 \"$@\""], None);
+        assert_eq!((st, out.as_str()), (0, "sh\n"));
+    }
+}
@@ -0,0 +1,63 @@
+// A console's shell: its variables and cwd, functions and loops, tty,
+// history and how deep in scripts it is. Each console's Irun owns one
+// (see lib.rs), and everything the shell runs is handed it, so two
+// consoles never see each other's state. What they do share is the
+// VFS, the kernel log and how the terminal looks.
+
+use std::cell::{Cell, RefCell};
+
+use crate::history::History;
+use crate::interp;
+use crate::sysvars::{Caller, Vars};
+use crate::tty::Tty;
+
+pub struct Session {
+    // which console this is, 1 for tty1
+    pub n: usize,
+    pub vars: Vars,
+    pub interp: RefCell<interp::State>,
+    pub tty: Tty,
+    pub hist: History,
+    // how many scripts deep it is; 0 at the prompt (see script.rs)
+    pub depth: Cell<usize>,
+    // exit was run, and the shell goes once the command is done
+    hung_up: Cell<bool>,
+}
+
+impl Session {
+    // a shell that has never run; seed_env and the rc files set it up
+    pub fn new(n: usize) -> Self {
+        Self {
+            n,
+            vars: Vars::new(),
+            interp: RefCell::default(),
+            tty: Tty::default(),
+            hist: History::new(n),
+            depth: Cell::new(0),
+            hung_up: Cell::new(false),
+        }
+    }
+
+    // the shell, as the VFS sees it
+    pub fn caller(&self) -> Caller {
+        self.vars.caller()
+    }
+
+    // The shell has exited; Irun starts a new one once the command is
+    // done. tty1 has no one to take over from it, so exit halts the
+    // system there instead.
+    pub fn hang_up(&self) {
+        self.hung_up.set(true);
+    }
+    pub fn hung_up(&self) -> bool {
+        self.hung_up.get()
+    }
+
+    // Like bash with checkwinsize, COLUMNS and LINES follow the size
+    // of the terminal.
+    pub fn set_winsize(&self, cols: usize, rows: usize) {
+        self.tty.set_winsize(cols, rows);
+        self.vars.setenv("COLUMNS", &cols.to_string());
+        self.vars.setenv("LINES", &rows.to_string());
+    }
+}
@@ -1,15 +1,369 @@
-use std::sync::atomic::AtomicU16;
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::future::Future;
 
-use once_cell::sync::Lazy;
+// Who the shell is running as: a user, their primary group and any
+// other groups they're in. Until someone logs in, that's root.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Creds {
+    pub uid: u32,
+    pub gid: u32,
+    pub groups: Vec<u32>,
+}
+impl Creds {
+    pub const fn root() -> Self {
+        Self {
+            uid: 0,
+            gid: 0,
+            groups: Vec::new(),
+        }
+    }
+    // the primary group and the others, without repeats
+    pub fn all_groups(&self) -> Vec<u32> {
+        let mut gs = vec![self.gid];
+        for g in &self.groups {
+            if !gs.contains(g) {
+                gs.push(*g);
+            }
+        }
+        gs
+    }
+}
 
-pub(crate) static UMASK: AtomicU16 = AtomicU16::new(0o022);
+// Whoever is asking the VFS for something: who they are, what gets
+// masked off what they make, and where their relative paths start.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Caller {
+    pub creds: Creds,
+    pub umask: u16,
+    // ends with /
+    pub cwd: String,
+}
+// until someone logs in, root
+impl Default for Caller {
+    fn default() -> Self {
+        Self::root()
+    }
+}
+impl Caller {
+    pub fn root() -> Self {
+        Self {
+            creds: Creds::root(),
+            umask: 0o022,
+            cwd: "/".to_string(),
+        }
+    }
+}
+
+// The shell environment. Every variable is a shell variable;
+// the exported ones are what `env` shows and what commands inherit.
+#[derive(Clone)]
+pub struct ShVar {
+    pub val: String,
+    pub exported: bool,
+}
+
+// What su left behind: the shell as it was before, which exit goes
+// back to. login starts over with none.
+#[derive(Clone)]
+struct Saved {
+    env: BTreeMap<String, ShVar>,
+    cwd: String,
+    creds: Creds,
+}
 
-// on other systems, CWD ends without /
-// here, CWD ends with /
-static mut CWD: Lazy<String> = Lazy::new(|| "/".to_string());
-pub fn load_cwd() -> String {
-    unsafe { CWD.clone() }
+struct Inner {
+    env: BTreeMap<String, ShVar>,
+    // irun aliases, by name
+    aliases: BTreeMap<String, String>,
+    // $0 and the positional parameters, $1 onwards
+    positional: Vec<String>,
+    // exit status of the last command, for $?
+    status: i32,
+    // on other systems, CWD ends without /
+    // here, CWD ends with /
+    cwd: String,
+    creds: Creds,
+    umask: u16,
+    saved: Vec<Saved>,
 }
-pub fn store_cwd(s: String) {
-    unsafe { *CWD = s }
+
+// One shell's variables, aliases and arguments, and where and as whom
+// it runs. Each console's shell has its own (see session.rs).
+pub struct Vars(RefCell<Inner>);
+
+impl Default for Vars {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Vars {
+    // no variables, aliases or arguments, as root at /; seed_env fills
+    // it in
+    pub fn new() -> Self {
+        Self(RefCell::new(Inner {
+            env: BTreeMap::new(),
+            aliases: BTreeMap::new(),
+            positional: vec!["irun".to_string()],
+            status: 0,
+            cwd: "/".to_string(),
+            creds: Creds::root(),
+            umask: 0o022,
+            saved: Vec::new(),
+        }))
+    }
+
+    pub fn seed_env(&self) {
+        for (k, v) in [
+            ("PATH", "/bin:/usr/bin"),
+            // there are no home directories yet
+            ("HOME", "/"),
+            ("USER", "root"),
+            ("PS1", "\\u@\\H:\\w$ "),
+            ("PS2", "> "),
+        ] {
+            self.export(k, Some(v));
+        }
+        self.export("PWD", Some(&self.load_cwd()));
+    }
+
+    pub fn load_cwd(&self) -> String {
+        self.0.borrow().cwd.clone()
+    }
+    pub fn store_cwd(&self, s: String) {
+        let pwd = match s.len() {
+            1 => s.clone(),
+            _ => s.trim_end_matches('/').to_string(),
+        };
+        self.setenv("PWD", &pwd);
+        self.0.borrow_mut().cwd = s;
+    }
+
+    pub fn creds(&self) -> Creds {
+        self.0.borrow().creds.clone()
+    }
+    pub fn set_creds(&self, c: Creds) {
+        self.0.borrow_mut().creds = c;
+    }
+
+    pub fn umask(&self) -> u16 {
+        self.0.borrow().umask
+    }
+    pub fn set_umask(&self, m: u16) {
+        self.0.borrow_mut().umask = m;
+    }
+
+    // the shell, as the VFS sees it
+    pub fn caller(&self) -> Caller {
+        let v = self.0.borrow();
+        Caller {
+            creds: v.creds.clone(),
+            umask: v.umask,
+            cwd: v.cwd.clone(),
+        }
+    }
+
+    pub fn status(&self) -> i32 {
+        self.0.borrow().status
+    }
+    pub fn set_status(&self, st: i32) {
+        self.0.borrow_mut().status = st;
+    }
+
+    pub fn getenv(&self, name: &str) -> Option<String> {
+        self.0.borrow().env.get(name).map(|v| v.val.clone())
+    }
+
+    // sets a variable, leaving whether it is exported alone
+    pub fn setenv(&self, name: &str, val: &str) {
+        let env = &mut self.0.borrow_mut().env;
+        match env.get_mut(name) {
+            Some(v) => v.val = val.to_string(),
+            None => {
+                env.insert(
+                    name.to_string(),
+                    ShVar {
+                        val: val.to_string(),
+                        exported: false,
+                    },
+                );
+            }
+        }
+    }
+
+    // marks a variable as exported, setting it first if given a value;
+    // exporting a variable that was never set does nothing
+    pub fn export(&self, name: &str, val: Option<&str>) {
+        if let Some(v) = val {
+            self.setenv(name, v);
+        }
+        if let Some(v) = self.0.borrow_mut().env.get_mut(name) {
+            v.exported = true;
+        }
+    }
+
+    pub fn unexport(&self, name: &str) {
+        if let Some(v) = self.0.borrow_mut().env.get_mut(name) {
+            v.exported = false;
+        }
+    }
+
+    pub fn unsetenv(&self, name: &str) {
+        self.0.borrow_mut().env.remove(name);
+    }
+
+    pub fn getvar(&self, name: &str) -> Option<ShVar> {
+        self.0.borrow().env.get(name).cloned()
+    }
+
+    pub fn putvar(&self, name: &str, var: Option<ShVar>) {
+        let env = &mut self.0.borrow_mut().env;
+        match var {
+            Some(v) => env.insert(name.to_string(), v),
+            None => env.remove(name),
+        };
+    }
+
+    // every variable, sorted by name
+    pub fn vars(&self) -> Vec<(String, ShVar)> {
+        self.0
+            .borrow()
+            .env
+            .iter()
+            .map(|(k, v)| (k.clone(), v.clone()))
+            .collect()
+    }
+
+    // the exported variables, which is what commands get as their
+    // environment
+    pub fn exported(&self) -> Vec<(String, String)> {
+        self.vars()
+            .into_iter()
+            .filter(|(_, v)| v.exported)
+            .map(|(k, v)| (k, v.val))
+            .collect()
+    }
+
+    pub fn get_alias(&self, name: &str) -> Option<String> {
+        self.0.borrow().aliases.get(name).cloned()
+    }
+    pub fn set_alias(&self, name: &str, val: &str) {
+        self.0
+            .borrow_mut()
+            .aliases
+            .insert(name.to_string(), val.to_string());
+    }
+    // false if there was no such alias
+    pub fn unalias(&self, name: &str) -> bool {
+        self.0.borrow_mut().aliases.remove(name).is_some()
+    }
+    pub fn clear_aliases(&self) {
+        self.0.borrow_mut().aliases.clear();
+    }
+    pub fn aliases(&self) -> Vec<(String, String)> {
+        self.0
+            .borrow()
+            .aliases
+            .iter()
+            .map(|(k, v)| (k.clone(), v.clone()))
+            .collect()
+    }
+
+    pub fn positional(&self) -> Vec<String> {
+        self.0.borrow().positional.clone()
+    }
+
+    pub fn push_session(&self) {
+        let mut v = self.0.borrow_mut();
+        let s = Saved {
+            env: v.env.clone(),
+            cwd: v.cwd.clone(),
+            creds: v.creds.clone(),
+        };
+        v.saved.push(s);
+    }
+    // false if there was nothing to go back to
+    pub fn pop_session(&self) -> bool {
+        let mut v = self.0.borrow_mut();
+        let s = match v.saved.pop() {
+            Some(s) => s,
+            None => return false,
+        };
+        v.env = s.env;
+        v.cwd = s.cwd;
+        v.creds = s.creds;
+        true
+    }
+    pub fn clear_sessions(&self) {
+        self.0.borrow_mut().saved.clear();
+    }
+
+    // Runs f with some variables exported just for it,
+    // like `FOO=bar cmd` does, then puts things back.
+    //
+    // These wrappers box f: an async fn keeps a future it's given twice
+    // over, and commands nest deep enough (functions calling functions)
+    // for that to run the stack out.
+    pub fn scoped_env<'a, F: Future + 'a>(
+        &'a self,
+        vals: &'a [(String, String)],
+        f: F,
+    ) -> impl Future<Output = F::Output> + 'a {
+        let f = Box::pin(f);
+        async move {
+            let saved: Vec<(&str, Option<ShVar>)> = vals
+                .iter()
+                .map(|(k, _)| (k.as_str(), self.getvar(k)))
+                .collect();
+            for (k, v) in vals {
+                self.export(k, Some(v));
+            }
+            let r = f.await;
+            for (k, v) in saved.into_iter().rev() {
+                self.putvar(k, v);
+            }
+            r
+        }
+    }
+
+    // runs f with $0 and the positional parameters set to args
+    pub fn with_positional<'a, F: Future + 'a>(
+        &'a self,
+        args: Vec<String>,
+        f: F,
+    ) -> impl Future<Output = F::Output> + 'a {
+        let f = Box::pin(f);
+        async move {
+            let saved = std::mem::replace(&mut self.0.borrow_mut().positional, args);
+            let r = f.await;
+            self.0.borrow_mut().positional = saved;
+            r
+        }
+    }
+
+    // Runs f like a child shell would: whatever it does to variables,
+    // aliases, the working directory, who it runs as or what su left
+    // behind is undone afterwards.
+    pub fn subshell<'a, F: Future + 'a>(&'a self, f: F) -> impl Future<Output = F::Output> + 'a {
+        let f = Box::pin(f);
+        async move {
+            let (env, aliases, cwd, creds, umask, saved) = {
+                let v = self.0.borrow();
+                (
+                    v.env.clone(),
+                    v.aliases.clone(),
+                    v.cwd.clone(),
+                    v.creds.clone(),
+                    v.umask,
+                    v.saved.clone(),
+                )
+            };
+            let r = f.await;
+            let mut v = self.0.borrow_mut();
+            (v.env, v.aliases, v.cwd, v.creds, v.umask, v.saved) =
+                (env, aliases, cwd, creds, umask, saved);
+            r
+        }
+    }
 }
@@ -0,0 +1,480 @@
+// How the terminal looks: its colors, font size and cursor. There are
+// a few palettes built in, and more can be put in /etc/themes/NAME.toml.
+// Whatever was picked last is kept in localStorage, palette and all,
+// so a theme from the rootfs still applies at boot, before there is one.
+// Changes go straight to xterm through term.options.
+
+use std::cell::Cell;
+
+use js_sys::Reflect;
+use wasm_bindgen::JsValue;
+use xterm_js_rs::keys::{BellStyle, CursorStyle};
+use xterm_js_rs::{TerminalOptions, Theme};
+
+use crate::sysvars::Caller;
+
+const APPEARANCE_KEY: &str = "irun_appearance";
+pub const THEME_DIR: &str = "/etc/themes";
+
+// the colors a palette can set, as xterm's ITheme has them
+pub const COLORS: [&str; 21] = [
+    "foreground",
+    "background",
+    "cursor",
+    "cursor_accent",
+    "selection",
+    "black",
+    "red",
+    "green",
+    "yellow",
+    "blue",
+    "magenta",
+    "cyan",
+    "white",
+    "bright_black",
+    "bright_red",
+    "bright_green",
+    "bright_yellow",
+    "bright_blue",
+    "bright_magenta",
+    "bright_cyan",
+    "bright_white",
+];
+
+pub const CURSORS: [&str; 3] = ["block", "underline", "bar"];
+
+// the font size can't go past these
+pub const FONT_SIZES: std::ops::RangeInclusive<u32> = 6..=72;
+
+// what irun has always looked like
+const DARK: &str = r##"foreground = "#f5f1e3"
+background = "#191a22""##;
+
+const LIGHT: &str = r##"foreground = "#383a42"
+background = "#fafafa"
+cursor = "#526fff"
+selection = "#bfceff"
+black = "#383a42"
+red = "#e45649"
+green = "#50a14f"
+yellow = "#c18401"
+blue = "#4078f2"
+magenta = "#a626a4"
+cyan = "#0184bc"
+white = "#a0a1a7"
+bright_black = "#4f525e"
+bright_red = "#e06c75"
+bright_green = "#98c379"
+bright_yellow = "#e5c07b"
+bright_blue = "#61afef"
+bright_magenta = "#c678dd"
+bright_cyan = "#56b6c2"
+bright_white = "#ffffff""##;
+
+const SOLARIZED: &str = r##"foreground = "#839496"
+background = "#002b36"
+cursor = "#93a1a1"
+selection = "#073642"
+black = "#073642"
+red = "#dc322f"
+green = "#859900"
+yellow = "#b58900"
+blue = "#268bd2"
+magenta = "#d33682"
+cyan = "#2aa198"
+white = "#eee8d5"
+bright_black = "#002b36"
+bright_red = "#cb4b16"
+bright_green = "#586e75"
+bright_yellow = "#657b83"
+bright_blue = "#839496"
+bright_magenta = "#6c71c4"
+bright_cyan = "#93a1a1"
+bright_white = "#fdf6e3""##;
+
+const HIGH_CONTRAST: &str = r##"foreground = "#ffffff"
+background = "#000000"
+cursor = "#ffff00"
+cursor_accent = "#000000"
+selection = "#ffff0080"
+black = "#000000"
+red = "#ff5555"
+green = "#55ff55"
+yellow = "#ffff55"
+blue = "#7878ff"
+magenta = "#ff55ff"
+cyan = "#55ffff"
+white = "#e5e5e5"
+bright_black = "#a0a0a0"
+bright_red = "#ff8080"
+bright_green = "#80ff80"
+bright_yellow = "#ffff80"
+bright_blue = "#a0a0ff"
+bright_magenta = "#ff80ff"
+bright_cyan = "#80ffff"
+bright_white = "#ffffff""##;
+
+pub const BUILTIN: [(&str, &str); 4] = [
+    ("dark", DARK),
+    ("light", LIGHT),
+    ("solarized", SOLARIZED),
+    ("high-contrast", HIGH_CONTRAST),
+];
+
+// Reads the little of TOML that palettes need: one `name = value` a
+// line, with # comments. Strings lose their quotes; anything else
+// (numbers, true and false) is kept as written.
+fn pairs(src: &str) -> Result<Vec<(String, String)>, String> {
+    let mut out = vec![];
+    for (n, l) in src.lines().enumerate() {
+        let l = l.trim();
+        if l.is_empty() || l.starts_with('#') {
+            continue;
+        }
+        let bad = || format!("line {}: expected NAME = VALUE", n + 1);
+        let (k, v) = l.split_once('=').ok_or_else(bad)?;
+        let (k, v) = (k.trim(), v.trim());
+        if k.is_empty()
+            || !k
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+        {
+            return Err(bad());
+        }
+        let v = match v.strip_prefix('"') {
+            Some(s) => match s.split_once('"') {
+                Some((s, rest)) if rest.trim().is_empty() || rest.trim().starts_with('#') => s,
+                _ => return Err(format!("line {}: unterminated string", n + 1)),
+            },
+            None => v.split('#').next().unwrap().trim(),
+        };
+        out.push((k.to_string(), v.to_string()));
+    }
+    Ok(out)
+}
+
+// #rgb, #rrggbb or #rrggbbaa
+fn is_color(s: &str) -> bool {
+    s.strip_prefix('#')
+        .is_some_and(|h| [3, 6, 8].contains(&h.len()) && h.chars().all(|c| c.is_ascii_hexdigit()))
+}
+
+// which colors are set to what; the rest are left to xterm
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Palette(Vec<(String, String)>);
+
+impl Palette {
+    pub fn parse(src: &str) -> Result<Self, String> {
+        let mut p = Palette::default();
+        for (k, v) in pairs(src)? {
+            if !COLORS.contains(&k.as_str()) {
+                return Err(format!("unknown color '{}'", k));
+            }
+            if !is_color(&v) {
+                return Err(format!("{}: not a color: '{}'", k, v));
+            }
+            p.set(&k, &v);
+        }
+        Ok(p)
+    }
+    fn set(&mut self, key: &str, val: &str) {
+        match self.0.iter_mut().find(|(k, _)| k == key) {
+            Some(e) => e.1 = val.to_string(),
+            None => self.0.push((key.to_string(), val.to_string())),
+        }
+    }
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.0
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v.as_str())
+    }
+    // as a palette file would have it, in the usual order
+    pub fn to_toml(&self) -> String {
+        COLORS
+            .iter()
+            .filter_map(|c| Some(format!("{} = \"{}\"", c, self.get(c)?)))
+            .collect::<Vec<String>>()
+            .join("\n")
+    }
+    fn theme(&self) -> Theme {
+        let t = Theme::new();
+        for (k, v) in &self.0 {
+            // ITheme spells them in camelCase
+            let mut key = String::new();
+            let mut up = false;
+            for c in k.chars() {
+                match c {
+                    '_' => up = true,
+                    c if up => {
+                        key.push(c.to_ascii_uppercase());
+                        up = false;
+                    }
+                    c => key.push(c),
+                }
+            }
+            let _ = Reflect::set(&t, &key.into(), &v.into());
+        }
+        t
+    }
+}
+
+// a built-in palette, or else the one in THEME_DIR
+pub fn find(c: &Caller, name: &str) -> Result<Palette, String> {
+    if let Some((_, src)) = BUILTIN.iter().find(|(n, _)| *n == name) {
+        return Palette::parse(src);
+    }
+    // a name, not a path: nothing from outside THEME_DIR, or hidden
+    if name.is_empty() || name.contains('/') || name.starts_with('.') {
+        return Err(format!("{}: not a theme name", name));
+    }
+    let path = format!("{}/{}.toml", THEME_DIR, name);
+    let src = crate::vfs::futils::read_file(c, path.clone())
+        .map_err(|e| format!("{}: {}", name, e.errno()))?;
+    Palette::parse(&String::from_utf8_lossy(&src)).map_err(|e| format!("{}: {}", path, e))
+}
+
+// every theme there is: the built-in ones, then the ones in THEME_DIR
+pub fn names(c: &Caller) -> Vec<String> {
+    let mut names: Vec<String> = BUILTIN.iter().map(|(n, _)| n.to_string()).collect();
+    let mut more: Vec<String> = crate::glob::entries(c, THEME_DIR)
+        .unwrap_or_default()
+        .iter()
+        .filter_map(|f| f.strip_suffix(".toml"))
+        .filter(|n| !n.starts_with('.') && !names.iter().any(|b| b == n))
+        .map(|n| n.to_string())
+        .collect();
+    more.sort();
+    names.extend(more);
+    names
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Appearance {
+    pub theme: String,
+    pub palette: Palette,
+    pub font_size: u32,
+    // one of CURSORS
+    pub cursor_style: String,
+    pub cursor_blink: bool,
+}
+
+impl Default for Appearance {
+    fn default() -> Self {
+        Self {
+            theme: "dark".to_string(),
+            palette: Palette::parse(DARK).unwrap(),
+            font_size: 20,
+            cursor_style: "block".to_string(),
+            cursor_blink: true,
+        }
+    }
+}
+
+impl Appearance {
+    // what gets stored: the settings, then the palette
+    fn to_toml(&self) -> String {
+        format!(
+            "theme = \"{}\"\nfont_size = {}\ncursor_style = \"{}\"\ncursor_blink = {}\n{}",
+            self.theme,
+            self.font_size,
+            self.cursor_style,
+            self.cursor_blink,
+            self.palette.to_toml()
+        )
+    }
+    // anything that doesn't make sense stays as it is by default
+    fn from_toml(src: &str) -> Self {
+        let mut a = Appearance {
+            palette: Palette::default(),
+            ..Default::default()
+        };
+        for (k, v) in pairs(src).unwrap_or_default() {
+            match k.as_str() {
+                "theme" => a.theme = v,
+                "font_size" => {
+                    if let Some(n) = v.parse().ok().filter(|n| FONT_SIZES.contains(n)) {
+                        a.font_size = n;
+                    }
+                }
+                "cursor_style" if CURSORS.contains(&v.as_str()) => a.cursor_style = v,
+                "cursor_blink" => a.cursor_blink = v != "false",
+                c if COLORS.contains(&c) && is_color(&v) => a.palette.set(c, &v),
+                _ => {}
+            }
+        }
+        if a.palette == Palette::default() {
+            a.palette = Appearance::default().palette;
+        }
+        a
+    }
+}
+
+// not a Mutex, for the same reason as kmsg's ring: the reboot makes
+// the terminal with it
+thread_local! {
+    static CURRENT: Cell<Option<Appearance>> = const { Cell::new(None) };
+}
+
+// how the terminal looks now, or will when it's made
+pub fn current() -> Appearance {
+    let a = CURRENT.take().unwrap_or_else(|| {
+        crate::storage()
+            .and_then(|s| s.get_item(APPEARANCE_KEY).ok().flatten())
+            .map_or_else(Appearance::default, |s| Appearance::from_toml(&s))
+    });
+    CURRENT.set(Some(a.clone()));
+    a
+}
+
+// the options to make the terminal with
+pub fn options() -> TerminalOptions {
+    let a = current();
+    let cursor = match a.cursor_style.as_str() {
+        "underline" => CursorStyle::Underline,
+        "bar" => CursorStyle::Bar,
+        _ => CursorStyle::Block,
+    };
+    let opts = TerminalOptions::new();
+    opts.with_cursor_blink(a.cursor_blink)
+        .with_cursor_style(cursor)
+        .with_cursor_width(10)
+        .with_font_size(a.font_size)
+        .with_right_click_selects_word(true)
+        .with_draw_bold_text_in_bright_colors(true)
+        .with_font_family("Inconsolata")
+        .with_convert_eol(true)
+        .with_bell_style(BellStyle::Both)
+        .with_theme(&a.palette.theme());
+    opts
+}
+
+fn set(obj: &JsValue, key: &str, val: &JsValue) {
+    let _ = Reflect::set(obj, &key.into(), val);
+}
+
+// Changes every console to look like a, and keeps that for next time.
+pub fn apply(a: Appearance) {
+    if let Some(s) = crate::storage() {
+        // nothing useful we can do if the quota is exceeded
+        let _ = s.set_item(APPEARANCE_KEY, &a.to_toml());
+    }
+    let terms = crate::vt::terminals();
+    for term in &terms {
+        let Ok(opts) = Reflect::get(term, &"options".into()) else {
+            continue;
+        };
+        set(&opts, "theme", &a.palette.theme());
+        set(&opts, "fontSize", &a.font_size.into());
+        set(&opts, "cursorStyle", &a.cursor_style.as_str().into());
+        set(&opts, "cursorBlink", &a.cursor_blink.into());
+    }
+    // a new font size means a new number of rows and columns
+    if !terms.is_empty() {
+        crate::fit();
+    }
+    CURRENT.set(Some(a));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn palettes() {
+        for (n, src) in BUILTIN {
+            assert!(Palette::parse(src).is_ok(), "{}", n);
+        }
+        let p =
+            Palette::parse("# mine\nbackground = \"#000\" # black\n\nred = \"#ff0000\"\n").unwrap();
+        assert_eq!(p.get("red"), Some("#ff0000"));
+        assert_eq!(p.to_toml(), "background = \"#000\"\nred = \"#ff0000\"");
+        assert_eq!(
+            Palette::parse("red \"#f00\""),
+            Err("line 1: expected NAME = VALUE".to_string())
+        );
+        assert_eq!(
+            Palette::parse("pink = \"#f0f\""),
+            Err("unknown color 'pink'".to_string())
+        );
+        assert_eq!(
+            Palette::parse("red = \"f00\""),
+            Err("red: not a color: 'f00'".to_string())
+        );
+        assert_eq!(
+            Palette::parse("red = \"#f00"),
+            Err("line 1: unterminated string".to_string())
+        );
+
+        let a = Appearance {
+            theme: "solarized".to_string(),
+            palette: Palette::parse(SOLARIZED).unwrap(),
+            font_size: 14,
+            cursor_style: "bar".to_string(),
+            cursor_blink: false,
+        };
+        assert_eq!(Appearance::from_toml(&a.to_toml()), a);
+        assert_eq!(
+            Appearance::from_toml("font_size = 900"),
+            Appearance::default()
+        );
+    }
+
+    #[test]
+    fn command() {
+        use crate::nanotools::theme;
+        use crate::process::run_test;
+        let _l = crate::process::shell_lock();
+        crate::vfs::mount_test_fs();
+        let sh = crate::process::test_sh();
+        sh.vars.set_creds(crate::sysvars::Creds::root());
+        apply(Appearance::default());
+        run_test(crate::unix::mkdir::mkdir, &[THEME_DIR], None);
+        crate::vfs::futils::write_file(
+            &sh.caller(),
+            "/etc/themes/mine.toml",
+            b"background = \"#102030\"\n",
+            false,
+        )
+        .unwrap();
+        crate::vfs::futils::write_file(&sh.caller(), "/etc/themes/bad.toml", b"red = 1\n", false)
+            .unwrap();
+
+        assert_eq!(run_test(theme, &[], None).1, "dark\n");
+        assert_eq!(
+            run_test(theme, &["-l"], None).1,
+            "* dark\n  light\n  solarized\n  high-contrast\n  bad\n  mine\n"
+        );
+        assert_eq!(run_test(theme, &["mine"], None).0, 0);
+        assert_eq!(current().theme, "mine");
+        assert_eq!(current().palette.get("background"), Some("#102030"));
+        assert_eq!(
+            run_test(theme, &["-p"], None).1,
+            "background = \"#102030\"\n"
+        );
+        assert_eq!(
+            run_test(theme, &["bad"], None),
+            (
+                1,
+                String::new(),
+                "theme: /etc/themes/bad.toml: red: not a color: '1'\n".to_string()
+            )
+        );
+        assert_eq!(
+            run_test(theme, &["nope"], None).2,
+            "theme: nope: No such file or directory\n"
+        );
+        for bad in ["../etc/passwd", "/etc/themes/mine", ".mine", ""] {
+            assert_eq!(
+                run_test(theme, &[bad], None).2,
+                format!("theme: {}: not a theme name\n", bad)
+            );
+        }
+        assert_eq!(current().theme, "mine");
+        for f in ["/etc/themes/mine.toml", "/etc/themes/bad.toml"] {
+            assert_eq!(run_test(crate::unix::rm::rm, &[f], None).0, 0);
+        }
+        assert_eq!(run_test(crate::unix::rmdir::rmdir, &[THEME_DIR], None).0, 0);
+        apply(Appearance::default());
+    }
+}
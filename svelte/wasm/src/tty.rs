@@ -0,0 +1,236 @@
+// The terminal as standard input, for commands that read it while
+// they run. Keys typed during a command come here instead of going to
+// the line editor, and are put together into lines the way a tty in
+// canonical mode does: Enter ends a line, Backspace takes back what
+// hasn't been sent yet, Ctrl-D is end of file and Ctrl-C interrupts.
+// Whatever nobody read is typed ahead into the next prompt.
+
+use std::cell::{Cell, RefCell};
+use std::collections::VecDeque;
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll, Waker};
+
+use unicode_segmentation::UnicodeSegmentation;
+
+#[derive(Default)]
+struct Input {
+    // finished lines, with their newlines
+    lines: VecDeque<String>,
+    // the one being typed
+    line: String,
+    // Ctrl-D on an empty line, not read yet
+    eof: bool,
+    waker: Option<Waker>,
+}
+
+// A console's tty; each console's shell has one (see session.rs).
+pub struct Tty {
+    input: RefCell<Input>,
+    interrupted: Cell<bool>,
+    // The window size, columns then rows, as of the last resize. Like
+    // bash with checkwinsize, COLUMNS and LINES follow it (see
+    // Session::set_winsize).
+    winsize: Cell<(usize, usize)>,
+}
+
+impl Default for Tty {
+    fn default() -> Self {
+        Self {
+            input: RefCell::default(),
+            interrupted: Cell::new(false),
+            winsize: Cell::new((80, 24)),
+        }
+    }
+}
+
+impl Tty {
+    // changes the input, then wakes whoever is waiting on it
+    fn update(&self, f: impl FnOnce(&mut Input)) {
+        let w = {
+            let mut t = self.input.borrow_mut();
+            f(&mut t);
+            t.waker.take()
+        };
+        if let Some(w) = w {
+            w.wake();
+        }
+    }
+
+    // text typed or pasted; newlines in it end lines
+    pub fn push(&self, s: &str) {
+        self.update(|t| {
+            let mut parts = s.split('\n').peekable();
+            while let Some(p) = parts.next() {
+                t.line.push_str(p);
+                if parts.peek().is_some() {
+                    let l = std::mem::take(&mut t.line) + "\n";
+                    t.lines.push_back(l);
+                }
+            }
+        });
+    }
+
+    pub fn newline(&self) {
+        self.push("\n");
+    }
+
+    // Takes back the last character of the line being typed, and gives
+    // how wide it was, so it can be rubbed out; None if there wasn't one.
+    pub fn backspace(&self) -> Option<usize> {
+        let mut t = self.input.borrow_mut();
+        let (i, g) = t.line.grapheme_indices(true).next_back()?;
+        let w = crate::linebuf::str_width(g);
+        t.line.truncate(i);
+        Some(w)
+    }
+
+    // Ctrl-D: sends what's been typed without a newline, or if there's
+    // nothing, makes the next read see end of file.
+    pub fn eof(&self) {
+        self.update(|t| {
+            if t.line.is_empty() {
+                t.eof = true;
+            } else {
+                let l = std::mem::take(&mut t.line);
+                t.lines.push_back(l);
+            }
+        });
+    }
+
+    // Ctrl-C: throws away all the input and stops the running command
+    pub fn interrupt(&self) {
+        self.interrupted.set(true);
+        self.update(|t| {
+            t.lines.clear();
+            t.line.clear();
+            t.eof = false;
+        });
+    }
+
+    pub fn interrupted(&self) -> bool {
+        self.interrupted.get()
+    }
+
+    // for a new command: it hasn't been interrupted, and an old Ctrl-D
+    // doesn't count, but lines typed ahead are still there
+    pub fn reset(&self) {
+        self.interrupted.set(false);
+        self.input.borrow_mut().eof = false;
+    }
+
+    // everything typed that nobody read: whole lines, then the rest
+    pub fn take_unread(&self) -> (Vec<String>, String) {
+        let mut t = self.input.borrow_mut();
+        let lines = t
+            .lines
+            .drain(..)
+            .map(|l| l.trim_end_matches('\n').to_string())
+            .collect();
+        (lines, std::mem::take(&mut t.line))
+    }
+
+    pub fn winsize(&self) -> (usize, usize) {
+        self.winsize.get()
+    }
+
+    pub fn set_winsize(&self, cols: usize, rows: usize) {
+        self.winsize.set((cols, rows));
+    }
+
+    // The next line typed, with its newline unless it was sent with
+    // Ctrl-D; None at end of file or once interrupted.
+    pub fn read_line(&self) -> ReadLine<'_> {
+        ReadLine(self)
+    }
+
+    // Waits ms milliseconds, or until Ctrl-C; false if it was cut short.
+    pub async fn sleep(&self, ms: u64) -> bool {
+        let mut left = ms;
+        while left > 0 && !self.interrupted() {
+            let n = left.min(50);
+            timeout(n as i32).await;
+            left -= n;
+        }
+        !self.interrupted()
+    }
+}
+
+pub struct ReadLine<'a>(&'a Tty);
+
+impl Future for ReadLine<'_> {
+    type Output = Option<String>;
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<String>> {
+        if self.0.interrupted() {
+            return Poll::Ready(None);
+        }
+        let mut t = self.0.input.borrow_mut();
+        if let Some(l) = t.lines.pop_front() {
+            return Poll::Ready(Some(l));
+        }
+        if t.eof {
+            t.eof = false;
+            return Poll::Ready(None);
+        }
+        t.waker = Some(cx.waker().clone());
+        Poll::Pending
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+async fn timeout(ms: i32) {
+    let p = js_sys::Promise::new(&mut |resolve, _| {
+        web_sys::window()
+            .unwrap()
+            .set_timeout_with_callback_and_timeout_and_arguments_0(&resolve, ms)
+            .unwrap();
+    });
+    let _ = wasm_bindgen_futures::JsFuture::from(p).await;
+}
+
+// natively (in tests) there's no browser to wait for
+#[cfg(not(target_arch = "wasm32"))]
+async fn timeout(_ms: i32) {}
+
+// Lets the browser have a turn every so often, so the page keeps
+// drawing and Ctrl-C gets through while something long runs.
+pub async fn pause() {
+    #[cfg(target_arch = "wasm32")]
+    {
+        use std::sync::atomic::{AtomicU64, Ordering};
+        static LAST: AtomicU64 = AtomicU64::new(0);
+        let now = crate::instant::Instant::now().i();
+        if now - LAST.load(Ordering::Relaxed) < 50_000 {
+            return;
+        }
+        timeout(0).await;
+        LAST.store(crate::instant::Instant::now().i(), Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::process::block_on;
+
+    #[test]
+    fn lines() {
+        let t = Tty::default();
+        t.push("ab");
+        assert_eq!(t.backspace(), Some(1));
+        t.push("日\ncd");
+        assert_eq!(t.backspace(), Some(1));
+        assert_eq!(block_on(t.read_line()), Some("a日\n".into()));
+        t.eof();
+        t.push("ef");
+        assert_eq!(t.take_unread(), (vec!["c".to_string()], "ef".to_string()));
+        assert_eq!(t.backspace(), None);
+        t.eof();
+        assert_eq!(block_on(t.read_line()), None);
+        t.push("x\n");
+        t.interrupt();
+        assert_eq!(block_on(t.read_line()), None);
+        t.reset();
+        assert_eq!(t.take_unread(), (vec![], String::new()));
+    }
+}
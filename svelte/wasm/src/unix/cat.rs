@@ -1,24 +1,33 @@
-use xterm_js_rs::Terminal;
+use crate::common::{Opt, Spec};
+use crate::process::Proc;
 
-const CAT_HELP: &str = "Usage: cat [OPTION]... [FILE]...
+const CAT_SPEC: Spec = Spec {
+    name: "cat",
+    usage: "Usage: cat [OPTION]... [FILE]...
 Concatenate FILE(s) to standard output.
 
-Standard input mode is not supported.
-
- -A, --show-all         equivalent to -vET
- -b, --number-nonblank  number nonempty output lines, overrides -n
- -e                     equivalent to -vE
- -E, --show-ends        display $ at the end of each line
- -n, --number           number all output lines
- -s, --squeeze-blank    suppress repeated output lines
- -t                     equivalent to -vT
- -T, --show-tabs        display TAB characters as ^I
- -v, --show-nonprinting use ^ notation (except for TAB)
-     --help             display this help and exit
-     --version          output version information and exit
-
-Examples:
-  cat f g 	Output f's contents, then g's contents.";
+With no FILE, or when FILE is -, read standard input.",
+    opts: &[
+        Opt::flag('A', "show-all", "equivalent to -vET"),
+        Opt::flag(
+            'b',
+            "number-nonblank",
+            "number nonempty output lines, overrides -n",
+        ),
+        Opt::short('e', "equivalent to -vE"),
+        Opt::flag('E', "show-ends", "display $ at the end of each line"),
+        Opt::flag('n', "number", "number all output lines"),
+        Opt::flag('s', "squeeze-blank", "suppress repeated output lines"),
+        Opt::short('t', "equivalent to -vT"),
+        Opt::flag('T', "show-tabs", "display TAB characters as ^I"),
+        Opt::short('u', "(ignored)"),
+        Opt::flag('v', "show-nonprinting", "use ^ notation (except for TAB)"),
+    ],
+    footer: "Examples:
+  cat f - g  Output f's contents, then standard input, then g's contents.
+  cat        Copy standard input to standard output.",
+    version: Some(CAT_VSN),
+};
 const CAT_VSN: &str = "cat (IrisOS-nano) 0.1
 Copyright (C) Amy Parker, 2023
 License AGPLv3+: GNU AGPL version 3 or later <https://gnu.org/licenses/agpl.html>
@@ -34,169 +43,114 @@ struct CatOpts {
     squeeze_blank: bool,
     show_tabs: bool,
     show_nonprinting: bool,
-    help: bool,
-    version: bool,
 }
-// TODO: is there a better way to dedup with what is in uname so far?
 // TODO: don't read if the file is a directory
-pub fn cat(term: &Terminal, mut args: Vec<&str>) -> i32 {
-    if args.len() < 1 {
-        term.writeln("cat: stdin mode not supported in cat-irun");
-        crate::common::minfo(term, "cat");
-        return -1;
-    }
-    let mut split = args.len();
-    for n in 0..args.len() {
-        if !args[n].starts_with('-') {
-            split = n;
-            break;
-        }
-    }
-    let (opts, files) = args.split_at_mut(split);
-    let mut opt = CatOpts {
-        // if set, unset number
-        number_nonblank: false,
-        show_ends: false,
-        number: false,
-        squeeze_blank: false,
-        show_tabs: false,
+pub async fn cat(p: &Proc<'_>) -> i32 {
+    let args = match p.getopt(&CAT_SPEC) {
+        Ok(a) => a,
+        Err(st) => return st,
+    };
+    let any = |keys: &[&str]| keys.iter().any(|k| args.has(k));
+    let opt = CatOpts {
+        number_nonblank: args.has("b"),
+        show_ends: any(&["A", "e", "E"]),
+        // -b wins
+        number: args.has("n") && !args.has("b"),
+        squeeze_blank: args.has("s"),
+        show_tabs: any(&["A", "t", "T"]),
         // "use ^ and M- notation, except for LFD and TAB" (no clue what LFD is)
         // TAB should be ^I, no clue what that's about
         // map for caret notation: http://xahlee.info/comp/ascii_chars.html
-        show_nonprinting: false,
-        help: false,
-        version: false,
+        show_nonprinting: any(&["A", "e", "t", "v"]),
     };
-    for arg in opts {
-        if arg.len() <= 1 {
-            // must be "-"
-            term.writeln("cat: stdin file ('-') not supported in cat-irun");
-            crate::common::minfo(term, "cat");
-            return -1;
-        }
-        if arg.chars().take(2).last().unwrap() == '-' {
-            match *arg {
-                "--help" => opt.help = true,
-                "--version" => opt.version = true,
-                "--show-all" => {
-                    opt.show_nonprinting = true;
-                    opt.show_ends = true;
-                    opt.show_tabs = true;
-                }
-                "--number-nonblank" => opt.number_nonblank = true,
-                "--show-ends" => opt.show_ends = true,
-                "--number" => opt.number = true,
-                "--squeeze-blank" => opt.squeeze_blank = true,
-                "--show-tabs" => opt.show_tabs = true,
-                "--show-nonprinting" => opt.show_nonprinting = true,
-                _ => {
-                    term.writeln(&format!("cat: unrecognized option '{}'", arg));
-                    crate::common::minfo(term, "cat");
-                    return 1;
-                }
-            }
-            continue;
-        }
-        for n in arg.get(1..).unwrap().chars() {
-            match n {
-                'A' => {
-                    opt.show_nonprinting = true;
-                    opt.show_ends = true;
-                    opt.show_tabs = true;
-                }
-                'b' => opt.number_nonblank = true,
-                'e' => {
-                    opt.show_nonprinting = true;
-                    opt.show_ends = true;
-                }
-                'E' => opt.show_ends = true,
-                'n' => opt.number = true,
-                's' => opt.squeeze_blank = true,
-                't' => {
-                    opt.show_nonprinting = true;
-                    opt.show_tabs = true;
-                }
-                'T' => opt.show_tabs = true,
-                // does nothing
-                'u' => (),
-                'v' => opt.show_nonprinting = true,
-                _ => {
-                    term.writeln(&format!("cat: invalid option -- '{}'", n));
-                    crate::common::minfo(term, "cat");
-                    return 2;
-                }
-            }
-        }
-    }
-    if opt.help {
-        term.writeln(CAT_HELP);
-        return 0;
-    }
-    if opt.version {
-        term.writeln(CAT_VSN);
-        return 0;
-    }
-    if opt.number_nonblank {
-        opt.number = false;
-    }
+    let files = args.operands;
     let mut cl = 1;
     let mut pe = true;
+    let files: &[&str] = if files.is_empty() { &["-"] } else { &files };
     for f in files {
-        let txt = crate::vfs::futils::read_to_end(f.to_string());
-        if txt.is_none() {
-            term.writeln(&format!("cat: {}: No such file or directory", f));
-            return -2;
-        }
-        let out = String::from_utf8(txt.unwrap())
-            .unwrap()
-            .split('\n')
-            .map(|x| x.to_string())
-            .collect::<Vec<String>>();
-        for line in out {
-            if opt.squeeze_blank {
-                if pe && line == "" {
+        let txt = if *f == "-" {
+            match p.read_stdin() {
+                Some(s) => s.into_bytes(),
+                // the terminal: a line at a time, as they're typed
+                None => {
+                    while let Some(l) = p.read_line().await {
+                        show(p, &opt, l.into_bytes(), &mut cl, &mut pe);
+                    }
+                    if p.sh.tty.interrupted() {
+                        return 130;
+                    }
                     continue;
                 }
-                pe = line == "";
             }
-            if opt.number || (opt.number_nonblank && line != "") {
-                term.write(&format!("{:>6}\t", cl));
-                cl += 1;
+        } else {
+            match crate::vfs::futils::read_file(&p.sh.caller(), f.to_string()) {
+                Ok(t) => t,
+                Err(e) => {
+                    p.stderr.writeln(&format!("cat: {}: {}", f, e.errno()));
+                    return -2;
+                }
+            }
+        };
+        show(p, &opt, txt, &mut cl, &mut pe);
+    }
+    return 0;
+}
+
+// writes out txt as the options say; cl and pe are the line number
+// and whether the last line was empty, which carry on across files
+fn show(p: &Proc, opt: &CatOpts, txt: Vec<u8>, cl: &mut usize, pe: &mut bool) {
+    // binaries too, as best it can
+    let mut out = String::from_utf8_lossy(&txt)
+        .split('\n')
+        .map(|x| x.to_string())
+        .collect::<Vec<String>>();
+    // the newline at the end of the last line doesn't start another
+    if out.len() > 1 && out.last().is_some_and(|x| x.is_empty()) {
+        out.pop();
+    }
+    for line in out {
+        if opt.squeeze_blank {
+            if *pe && line == "" {
+                continue;
             }
-            if !(opt.show_nonprinting || opt.show_tabs) {
-                term.write(&line);
-            } else {
-                let mut tmp: String;
-                for c in line.chars() {
-                    term.write({
-                        if c == '\t' {
-                            if opt.show_tabs {
-                                "^I"
-                            } else {
-                                "\t"
-                            }
-                        } else if (c as u8) <= 31 {
-                            if opt.show_nonprinting {
-                                tmp = (((c as u8) + 64) as char).to_string();
-                                &tmp
-                            } else {
-                                ""
-                            }
-                        } else if (c as u8) == 127 {
-                            if opt.show_nonprinting {
-                                "^?"
-                            } else {
-                                ""
-                            }
+            *pe = line == "";
+        }
+        if opt.number || (opt.number_nonblank && line != "") {
+            p.stdout.write(&format!("{:>6}\t", cl));
+            *cl += 1;
+        }
+        if !(opt.show_nonprinting || opt.show_tabs) {
+            p.stdout.write(&line);
+        } else {
+            let mut tmp: String;
+            for c in line.chars() {
+                p.stdout.write({
+                    if c == '\t' {
+                        if opt.show_tabs {
+                            "^I"
                         } else {
-                            tmp = c.to_string();
+                            "\t"
+                        }
+                    } else if (c as u8) <= 31 {
+                        if opt.show_nonprinting {
+                            tmp = (((c as u8) + 64) as char).to_string();
                             &tmp
+                        } else {
+                            ""
                         }
-                    });
-                }
+                    } else if (c as u8) == 127 {
+                        if opt.show_nonprinting {
+                            "^?"
+                        } else {
+                            ""
+                        }
+                    } else {
+                        tmp = c.to_string();
+                        &tmp
+                    }
+                });
             }
-            term.writeln(if opt.show_ends { "$" } else { "" });
         }
+        p.stdout.writeln(if opt.show_ends { "$" } else { "" });
     }
-    return 0;
 }
@@ -1,17 +1,21 @@
-use xterm_js_rs::Terminal;
+use crate::process::Proc;
+use crate::vfs::VirtualFileSystem;
 
 // TODO: undo extras (.., ., etc)
-pub fn cd(term: &Terminal, args: Vec<&str>) -> i32 {
-    if args.len() == 0 {
-        crate::sysvars::store_cwd("/".to_string());
-        return 0;
-    }
-    if args.len() != 1 {
-        term.writeln("cd: too many arguments");
+pub fn cd(p: &Proc) -> i32 {
+    let args = p.args();
+    if args.len() > 1 {
+        p.stderr.writeln("cd: too many arguments");
         return 1;
     }
-    let mut cwd = crate::sysvars::load_cwd();
-    cwd.push_str(args[0]);
+    let home = p.sh.vars.getenv("HOME").unwrap_or("/".to_string());
+    let dir = args.first().copied().unwrap_or(&home);
+    let mut cwd = if dir.starts_with('/') {
+        String::new()
+    } else {
+        p.cwd.clone()
+    };
+    cwd.push_str(dir);
     if !cwd.ends_with("/") {
         cwd.push('/');
     }
@@ -28,7 +32,7 @@ pub fn cd(term: &Terminal, args: Vec<&str>) -> i32 {
                 ".." => match components.pop() {
                     Some(_) => {}
                     None => {
-                        term.writeln("cd: No such file or directory");
+                        p.stderr.writeln("cd: No such file or directory");
                         return 1;
                     }
                 },
@@ -46,16 +50,21 @@ pub fn cd(term: &Terminal, args: Vec<&str>) -> i32 {
         cwd.push('/');
     }
 
-    let r = crate::vfs::futils::find_file(cwd.clone(), false);
-    if r.is_right() {
-        term.writeln("cd: No such file or directory");
+    let r2 = match crate::vfs::futils::resolve(&p.sh.caller(), cwd.clone()) {
+        Ok(r) => r,
+        Err(e) => {
+            p.stderr.writeln(&format!("cd: {}", e.errno()));
+            return 1;
+        }
+    };
+    if r2.0.file_perms(&r2.1).unwrap() & 0xf000 != 0x1000 {
+        p.stderr.writeln("cd: Not a directory");
         return 1;
     }
-    let r2 = r.unwrap_left();
-    if r2.0.file_perms(&r2.1).unwrap() & 0xf000 != 0x1000 {
-        term.writeln("cd: Not a directory");
+    if let Err(e) = r2.0.access(&r2.1, crate::vfs::perm::X) {
+        p.stderr.writeln(&format!("cd: {}: {}", dir, e.errno()));
         return 1;
     }
-    crate::sysvars::store_cwd(cwd);
+    p.sh.vars.store_cwd(cwd);
     return 0;
 }
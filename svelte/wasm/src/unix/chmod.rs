@@ -0,0 +1,346 @@
+use crate::common::{Opt, Spec};
+use crate::process::Proc;
+use crate::vfs::{perm, VirtualFileDescriptor, VirtualFileSystem};
+
+const CHMOD_SPEC: Spec = Spec {
+    name: "chmod",
+    usage: "Usage: chmod [OPTION]... MODE[,MODE]... FILE...
+  or:  chmod [OPTION]... OCTAL-MODE FILE...
+Change the mode of each FILE to MODE.",
+    opts: &[RECURSIVE],
+    footer: "Each MODE is of the form '[ugoa]*([-+=]([rwxXst]*|[ugo]))+', or a
+number of up to four octal digits.",
+    version: None,
+};
+
+// -R, which chown and chgrp take too
+pub const RECURSIVE: Opt = Opt::flag('R', "recursive", "change files and directories recursively");
+
+// the bits chmod deals in; the rest say what kind of file it is
+const MODE_BITS: u16 = 0o7777;
+
+// u, g and o copy what that class already has to the others
+fn copy(bits: u16) -> u16 {
+    (bits & 7) * 0o111
+}
+
+// Works out the mode a MODE turns old into. Clauses that don't say
+// whom they're for are for everyone, save for what's in umask. X is x
+// for directories and for what's already runnable by someone.
+pub fn parse_mode(spec: &str, old: u16, dir: bool, umask: u16) -> Option<u16> {
+    if !spec.is_empty() && spec.chars().all(|c| c.is_digit(8)) {
+        return u16::from_str_radix(spec, 8)
+            .ok()
+            .filter(|m| *m <= MODE_BITS);
+    }
+    let mut mode = old & MODE_BITS;
+    for clause in spec.split(',') {
+        let mut cs = clause.chars().peekable();
+        let mut who = 0;
+        while let Some(&c) = cs.peek() {
+            who |= match c {
+                'u' => 0o4700,
+                'g' => 0o2070,
+                'o' => 0o1007,
+                'a' => MODE_BITS,
+                _ => break,
+            };
+            cs.next();
+        }
+        let keep = match who {
+            0 => umask,
+            _ => 0,
+        };
+        if who == 0 {
+            who = MODE_BITS;
+        }
+        let mut any = false;
+        while let Some(op) = cs.next() {
+            if !"+-=".contains(op) {
+                return None;
+            }
+            any = true;
+            let mut bits = 0;
+            while let Some(&c) = cs.peek() {
+                if "+-=".contains(c) {
+                    break;
+                }
+                bits |= match c {
+                    'r' => 0o444,
+                    'w' => 0o222,
+                    'x' => 0o111,
+                    'X' if dir || mode & 0o111 != 0 => 0o111,
+                    'X' => 0,
+                    's' => 0o6000,
+                    't' => 0o1000,
+                    'u' => copy(mode >> 6),
+                    'g' => copy(mode >> 3),
+                    'o' => copy(mode),
+                    _ => return None,
+                };
+                cs.next();
+            }
+            let bits = bits & who & !keep;
+            match op {
+                '+' => mode |= bits,
+                '-' => mode &= !bits,
+                _ => mode = (mode & !who) | bits,
+            }
+        }
+        if !any {
+            return None;
+        }
+    }
+    Some(mode)
+}
+
+// what walk does to each file: given its path, whether that went well
+type Visit<'a> = dyn FnMut(&mut perm::Checked, &Box<dyn VirtualFileDescriptor>, &str) -> bool + 'a;
+
+// Runs f over a file and, with recursive, everything under it that's on
+// the same fs, with each one's path for f to complain with. Directories
+// it may not read are complained about here. Whether it all went well
+// is what comes back.
+#[allow(clippy::borrowed_box)]
+pub fn walk(
+    p: &Proc,
+    cmd: &str,
+    fs: &mut perm::Checked,
+    fd: &Box<dyn VirtualFileDescriptor>,
+    path: &str,
+    recursive: bool,
+    f: &mut Visit,
+) -> bool {
+    let mut ok = f(fs, fd, path);
+    if !recursive || fs.file_perms(fd).unwrap_or(0) & 0xf000 != 0x1000 {
+        return ok;
+    }
+    if let Err(e) = fs.access(fd, perm::R) {
+        p.stderr.writeln(&format!(
+            "{}: cannot read directory '{}': {}",
+            cmd,
+            path,
+            e.errno()
+        ));
+        return false;
+    }
+    let ents = match fs.vfd_as_dentry(fd) {
+        Some(d) => d.get_entries(),
+        None => return ok,
+    };
+    let base = path.trim_end_matches('/');
+    for ent in ents {
+        // inode 0 is a deleted entry
+        if ent.inum == 0 || ent.filename == "." || ent.filename == ".." {
+            continue;
+        }
+        let cfd = match fs.get_fd(ent.inum, 0) {
+            Some(fd) => fd,
+            None => continue,
+        };
+        // links are left alone, as are wherever they point
+        if fs.file_perms(&cfd).unwrap_or(0) & 0xf000 == 0x2000 {
+            continue;
+        }
+        let cpath = format!("{}/{}", base, ent.filename);
+        ok &= walk(p, cmd, fs, &cfd, &cpath, true, f);
+    }
+    ok
+}
+
+pub fn chmod(p: &Proc) -> i32 {
+    let is_mode = |a: &str| parse_mode(a, 0, false, 0).is_some();
+    // A mode like -w looks like an option, so the first one of those is
+    // taken out before the options are read.
+    let mut args = p.args();
+    let dashed = args
+        .iter()
+        .take_while(|a| **a != "--")
+        .position(|a| a.starts_with('-') && is_mode(a))
+        .map(|i| args.remove(i));
+    let parsed = match p.getopt_from(&CHMOD_SPEC, &args) {
+        Ok(a) => a,
+        Err(st) => return st,
+    };
+    let recursive = parsed.has("recursive");
+    let rest: Vec<&str> = dashed.into_iter().chain(parsed.operands).collect();
+    let (spec, files) = match rest.split_first() {
+        Some((_, [])) => {
+            p.stderr
+                .writeln(&format!("chmod: missing operand after '{}'", rest[0]));
+            p.minfo("chmod");
+            return 1;
+        }
+        Some((s, f)) => (*s, f),
+        None => {
+            p.stderr.writeln("chmod: missing operand");
+            p.minfo("chmod");
+            return 1;
+        }
+    };
+    if !is_mode(spec) {
+        p.stderr
+            .writeln(&format!("chmod: invalid mode: '{}'", spec));
+        p.minfo("chmod");
+        return 1;
+    }
+    let umask = p.sh.vars.umask();
+    let mut st = 0;
+    for path in files {
+        let (mut fs, fd) = match crate::vfs::futils::resolve(&p.sh.caller(), path.to_string()) {
+            Ok(f) => f,
+            Err(e) => {
+                p.stderr
+                    .writeln(&format!("chmod: cannot access '{}': {}", path, e.errno()));
+                st = 1;
+                continue;
+            }
+        };
+        let ok = walk(
+            p,
+            "chmod",
+            &mut fs,
+            &fd,
+            path,
+            recursive,
+            &mut |fs, fd, path| {
+                let old = fs.file_perms(fd).unwrap_or(0);
+                let new = parse_mode(spec, old, old & 0xf000 == 0x1000, umask).unwrap();
+                match fs.chmod(fd, (old & !MODE_BITS) | new) {
+                    Ok(()) => true,
+                    Err(e) => {
+                        p.stderr.writeln(&format!(
+                            "chmod: changing permissions of '{}': {}",
+                            path,
+                            e.errno()
+                        ));
+                        false
+                    }
+                }
+            },
+        );
+        if !ok {
+            st = 1;
+        }
+    }
+    st
+}
+
+// the mode as umask prints it with -S, u=rwx,g=rx,o=rx
+pub fn symbolic(mode: u16) -> String {
+    ["u", "g", "o"]
+        .iter()
+        .enumerate()
+        .map(|(i, w)| {
+            let bits = mode >> (6 - 3 * i);
+            let rwx: String = [(4, 'r'), (2, 'w'), (1, 'x')]
+                .iter()
+                .filter(|(b, _)| bits & b != 0)
+                .map(|(_, c)| *c)
+                .collect();
+            format!("{}={}", w, rwx)
+        })
+        .collect::<Vec<String>>()
+        .join(",")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::process::run_test;
+    use crate::sysvars::Creds;
+
+    #[test]
+    fn modes() {
+        let m = |s, old| parse_mode(s, old, false, 0o022);
+        assert_eq!(m("755", 0), Some(0o755));
+        assert_eq!(m("04755", 0), Some(0o4755));
+        assert_eq!(m("17777", 0), None);
+        assert_eq!(m("u+x,go-w", 0o666), Some(0o744));
+        assert_eq!(m("a=r", 0o777), Some(0o444));
+        assert_eq!(m("o=u", 0o750), Some(0o757));
+        assert_eq!(m("u+s,+t", 0o755), Some(0o5755));
+        // nobody named, so umask has a say
+        assert_eq!(m("+w", 0o444), Some(0o644));
+        assert_eq!(m("=rwx", 0o4000), Some(0o755));
+        assert_eq!(m("+X", 0o644), Some(0o644));
+        assert_eq!(m("+X", 0o744), Some(0o755));
+        assert_eq!(parse_mode("+X", 0o644, true, 0), Some(0o755));
+        for bad in ["", "u", "u+q", "x+r", "8", "u+x,"] {
+            assert_eq!(m(bad, 0), None, "{}", bad);
+        }
+        assert_eq!(symbolic(0o750), "u=rwx,g=rx,o=");
+    }
+
+    #[test]
+    fn umask() {
+        let sh = crate::process::test_sh();
+        let run = |args: &[&str]| run_test(crate::builtins::umask, args, None);
+        assert_eq!(run(&[]).1, "0022\n");
+        assert_eq!(run(&["-S"]).1, "u=rwx,g=rx,o=rx\n");
+        assert_eq!(run(&["027"]).0, 0);
+        assert_eq!(sh.vars.umask(), 0o027);
+        assert_eq!(run(&["g+w,o="]).0, 0);
+        assert_eq!(run(&[]).1, "0007\n");
+        assert_eq!(run(&["999"]).0, 1);
+        assert_eq!(run(&["u+q"]).2, "umask: `u+q': invalid symbolic mode\n");
+    }
+
+    #[test]
+    fn chmod_and_walk() {
+        let _l = crate::process::shell_lock();
+        crate::vfs::mount_test_fs();
+        let sh = crate::process::test_sh();
+        sh.vars.set_creds(Creds::root());
+        sh.vars.store_cwd("/".to_string());
+        let run = |args: &[&str]| run_test(chmod, args, None);
+        crate::vfs::futils::write_file(&sh.caller(), "/home/chmod-t", b"", false).unwrap();
+        run_test(crate::unix::mkdir::mkdir, &["/home/chmod-d"], None);
+        crate::vfs::futils::write_file(&sh.caller(), "/home/chmod-d/f", b"", false).unwrap();
+        let perms = |f: &str| crate::script::file_perms(&sh.caller(), f).unwrap();
+        assert_eq!(run(&["u+x,go-r", "/home/chmod-t"]).0, 0);
+        assert_eq!(perms("/home/chmod-t"), 0o700);
+        assert_eq!(run(&["-w", "/home/chmod-t"]).0, 0);
+        assert_eq!(perms("/home/chmod-t"), 0o500);
+        let (st, _, err) = run(&["u+q", "/home/chmod-t"]);
+        assert_eq!(
+            (st, err.as_str()),
+            (
+                1,
+                "chmod: invalid mode: 'u+q'\nTry 'chmod --help' for more information.\n"
+            )
+        );
+        // directories keep being directories
+        assert_eq!(run(&["-R", "go-rx", "/home/chmod-d"]).0, 0);
+        assert_eq!(perms("/home/chmod-d"), 0x1000 | 0o700);
+        assert_eq!(perms("/home/chmod-d/f"), 0o600);
+        assert_eq!(run(&["-R", "go+rX", "/home/chmod-d"]).0, 0);
+        assert_eq!(perms("/home/chmod-d"), 0x1000 | 0o755);
+        assert_eq!(perms("/home/chmod-d/f"), 0o644);
+        // only the owner gets to
+        sh.vars.set_creds(Creds {
+            uid: 1000,
+            gid: 1000,
+            groups: vec![],
+        });
+        let (st, _, err) = run_test(chmod, &["777", "/home/chmod-t"], None);
+        sh.vars.set_creds(Creds::root());
+        assert_eq!(
+            (st, err.as_str()),
+            (
+                1,
+                "chmod: changing permissions of '/home/chmod-t': Operation not permitted\n"
+            )
+        );
+        let (st, _, err) = run_test(chmod, &["777", "/nope"], None);
+        assert_eq!(
+            (st, err.as_str()),
+            (
+                1,
+                "chmod: cannot access '/nope': No such file or directory\n"
+            )
+        );
+        run_test(crate::unix::rm::rm, &["/home/chmod-t"], None);
+        run_test(crate::unix::rmdir::rmdir, &["/home/chmod-d"], None);
+    }
+}
@@ -0,0 +1,216 @@
+use crate::common::Spec;
+use crate::process::Proc;
+use crate::unix::chmod::{walk, RECURSIVE};
+use crate::users;
+use crate::vfs::VirtualFileSystem;
+
+const CHOWN_SPEC: Spec = Spec {
+    name: "chown",
+    usage: "Usage: chown [OPTION]... [OWNER][:[GROUP]] FILE...
+Change the owner and/or group of each FILE to OWNER and/or GROUP.",
+    opts: &[RECURSIVE],
+    footer: "Owner is unchanged if missing.  Group is unchanged if missing, but changed
+to login group if implied by a ':' following a symbolic OWNER.",
+    version: None,
+};
+
+const CHGRP_SPEC: Spec = Spec {
+    name: "chgrp",
+    usage: "Usage: chgrp [OPTION]... GROUP FILE...
+Change the group of each FILE to GROUP.",
+    opts: &[RECURSIVE],
+    footer: "",
+    version: None,
+};
+
+// Splits OWNER[:[GROUP]] into a uid and a gid, either of which may be
+// left as it is
+fn parse_owner(spec: &str) -> Result<(Option<u32>, Option<u32>), String> {
+    let (owner, group) = match spec.split_once(':') {
+        Some((o, g)) => (o, Some(g)),
+        None => (spec, None),
+    };
+    let user = match owner {
+        "" => None,
+        o => Some(users::lookup(o).ok_or(format!("invalid user: '{}'", spec))?),
+    };
+    let gid = match (group, &user) {
+        (None, _) | (Some(""), None) => None,
+        (Some(""), Some(u)) => Some(u.gid),
+        (Some(g), _) => Some(
+            users::group_lookup(g)
+                .ok_or(format!("invalid group: '{}'", spec))?
+                .gid,
+        ),
+    };
+    Ok((user.map(|u| u.uid), gid))
+}
+
+// gives each of files the owner and group asked for, as cmd
+fn change(
+    p: &Proc,
+    cmd: &str,
+    files: &[&str],
+    recursive: bool,
+    uid: Option<u32>,
+    gid: Option<u32>,
+) -> i32 {
+    let mut st = 0;
+    for path in files {
+        let (mut fs, fd) = match crate::vfs::futils::resolve(&p.sh.caller(), path.to_string()) {
+            Ok(f) => f,
+            Err(e) => {
+                p.stderr
+                    .writeln(&format!("{}: cannot access '{}': {}", cmd, path, e.errno()));
+                st = 1;
+                continue;
+            }
+        };
+        let ok = walk(
+            p,
+            cmd,
+            &mut fs,
+            &fd,
+            path,
+            recursive,
+            &mut |fs, fd, path| {
+                let new_uid = uid.or(fs.file_owner(fd)).unwrap_or(0);
+                let new_gid = gid.or(fs.file_group(fd)).unwrap_or(0);
+                match fs.chown(fd, new_uid, new_gid) {
+                    Ok(()) => true,
+                    Err(e) => {
+                        let what = match uid {
+                            Some(_) => "ownership",
+                            None => "group",
+                        };
+                        p.stderr.writeln(&format!(
+                            "{}: changing {} of '{}': {}",
+                            cmd,
+                            what,
+                            path,
+                            e.errno()
+                        ));
+                        false
+                    }
+                }
+            },
+        );
+        if !ok {
+            st = 1;
+        }
+    }
+    st
+}
+
+// the spec and the files, or a complaint about there not being any
+fn operands<'a>(p: &Proc, cmd: &str, rest: &'a [&'a str]) -> Option<(&'a str, &'a [&'a str])> {
+    match rest {
+        [] => p.stderr.writeln(&format!("{}: missing operand", cmd)),
+        [s] => p
+            .stderr
+            .writeln(&format!("{}: missing operand after '{}'", cmd, s)),
+        [s, files @ ..] => return Some((s, files)),
+    }
+    p.minfo(cmd);
+    None
+}
+
+pub fn chown(p: &Proc) -> i32 {
+    let args = match p.getopt(&CHOWN_SPEC) {
+        Ok(a) => a,
+        Err(st) => return st,
+    };
+    let recursive = args.has("recursive");
+    let (spec, files) = match operands(p, "chown", &args.operands) {
+        Some(o) => o,
+        None => return 1,
+    };
+    match parse_owner(spec) {
+        Ok((uid, gid)) => change(p, "chown", files, recursive, uid, gid),
+        Err(e) => {
+            p.stderr.writeln(&format!("chown: {}", e));
+            1
+        }
+    }
+}
+
+pub fn chgrp(p: &Proc) -> i32 {
+    let args = match p.getopt(&CHGRP_SPEC) {
+        Ok(a) => a,
+        Err(st) => return st,
+    };
+    let recursive = args.has("recursive");
+    let (group, files) = match operands(p, "chgrp", &args.operands) {
+        Some(o) => o,
+        None => return 1,
+    };
+    match users::group_lookup(group) {
+        Some(g) => change(p, "chgrp", files, recursive, None, Some(g.gid)),
+        None => {
+            p.stderr
+                .writeln(&format!("chgrp: invalid group: '{}'", group));
+            1
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::process::run_test;
+    use crate::sysvars::Creds;
+    use crate::vfs::futils;
+
+    #[test]
+    fn owners() {
+        let _l = crate::process::shell_lock();
+        crate::vfs::mount_test_fs();
+        let sh = crate::process::test_sh();
+        sh.vars.set_creds(Creds::root());
+        assert_eq!(parse_owner("guest"), Ok((Some(1000), None)));
+        assert_eq!(parse_owner("guest:"), Ok((Some(1000), Some(1000))));
+        assert_eq!(parse_owner("0:wheel"), Ok((Some(0), Some(10))));
+        assert_eq!(parse_owner(":"), Ok((None, None)));
+        assert!(parse_owner("nobody").is_err());
+
+        run_test(crate::unix::mkdir::mkdir, &["/home/chown-d"], None);
+        futils::write_file(&sh.caller(), "/home/chown-d/f", b"", false).unwrap();
+        let owner = |f: &str| {
+            let (fs, fd) = futils::resolve(&sh.caller(), f.to_string()).unwrap();
+            (fs.file_owner(&fd).unwrap(), fs.file_group(&fd).unwrap())
+        };
+        assert_eq!(
+            run_test(chown, &["-R", "guest:", "/home/chown-d"], None).0,
+            0
+        );
+        assert_eq!(owner("/home/chown-d/f"), (1000, 1000));
+        // guest may move their files into wheel, but not give them away
+        sh.vars.set_creds(Creds {
+            uid: 1000,
+            gid: 1000,
+            groups: vec![10],
+        });
+        let chgrp_ok = run_test(chgrp, &["wheel", "/home/chown-d/f"], None).0;
+        let (st, _, err) = run_test(chown, &["root", "/home/chown-d/f"], None);
+        let chgrp_err = run_test(chgrp, &["root", "/home/chown-d/f"], None).2;
+        sh.vars.set_creds(Creds::root());
+        assert_eq!(chgrp_ok, 0);
+        assert_eq!(owner("/home/chown-d/f"), (1000, 10));
+        assert_eq!(
+            (st, err.as_str()),
+            (
+                1,
+                "chown: changing ownership of '/home/chown-d/f': Operation not permitted\n"
+            )
+        );
+        assert_eq!(
+            chgrp_err,
+            "chgrp: changing group of '/home/chown-d/f': Operation not permitted\n"
+        );
+        assert_eq!(
+            run_test(chgrp, &["nogroup", "/home/chown-d"], None).2,
+            "chgrp: invalid group: 'nogroup'\n"
+        );
+        run_test(crate::unix::rmdir::rmdir, &["/home/chown-d"], None);
+    }
+}
@@ -0,0 +1,32 @@
+// chvt N: brings virtual console N to the screen, as Alt+FN does
+// (see vt.rs).
+
+use crate::common::Spec;
+use crate::process::Proc;
+
+const CHVT_SPEC: Spec = Spec {
+    name: "chvt",
+    usage: "Usage: chvt N
+Switch to virtual console N, from 1 to 6. Alt+F1..F6 do the same.",
+    opts: &[],
+    footer: "",
+    version: None,
+};
+
+pub fn chvt(p: &Proc) -> i32 {
+    let args = match p.getopt(&CHVT_SPEC) {
+        Ok(a) => a.operands,
+        Err(st) => return st,
+    };
+    let [n] = args[..] else {
+        p.stderr.writeln("usage: chvt N");
+        return 1;
+    };
+    match n.parse() {
+        Ok(n) if crate::vt::activate(n) => 0,
+        _ => {
+            p.stderr.writeln(&format!("chvt: {}: no such console", n));
+            1
+        }
+    }
+}
@@ -1,69 +1,98 @@
-use crate::errors::{ao, axo};
-use xterm_js_rs::Terminal;
+use crate::common::Spec;
+use crate::errors::{ao, axo, axr};
+use crate::process::Proc;
+use crate::sysvars::Caller;
+use crate::vfs::{VfsErrno, VirtualFileSystem};
+
+const CP_SPEC: Spec = Spec {
+    name: "cp",
+    usage: "Usage: cp SOURCE DEST
+Copy SOURCE to DEST. Directories are copied with all they hold.",
+    opts: &[],
+    footer: "",
+    version: None,
+};
 
 // avoid options - just recurse automatically
-pub fn cp(term: &Terminal, args: Vec<&str>) -> i32 {
-    if args.len() == 1 && args[0] == "--help" {
-        term.writeln("Usage: cp [src] [dest]");
-        return 0;
-    }
+pub fn cp(p: &Proc) -> i32 {
+    let args = match p.getopt(&CP_SPEC) {
+        Ok(a) => a.operands,
+        Err(st) => return st,
+    };
     if args.len() < 2 {
-        term.writeln("cp: missing file operand");
-        crate::common::minfo(term, "cp");
+        p.stderr.writeln("cp: missing file operand");
+        p.minfo("cp");
         return -1;
     }
     if args.len() > 2 {
-        term.writeln("cp: too many arguments");
-        crate::common::minfo(term, "cp");
+        p.stderr.writeln("cp: too many arguments");
+        p.minfo("cp");
         return -1;
     }
     let mut src = ao!(
-        crate::vfs::futils::find_file(args[0].to_string(), false).left(),
+        crate::vfs::futils::find_file(&p.sh.caller(), args[0].to_string(), false).left(),
         ah,
         -2,
-        term
+        p
     );
     // TODO: optimize out this double rsplitn call
     let dds = args[1].rsplitn(2, '/').nth(1).unwrap_or(".").to_string();
     let f = args[1].rsplitn(2, '/').nth(0).unwrap().to_string();
     let perms = src.0.file_perms(&src.1).unwrap();
-    let destdir = crate::vfs::futils::find_file(dds, false).left().unwrap();
+    let mut destdir = match crate::vfs::futils::resolve(&p.sh.caller(), dds) {
+        Ok(v) => v,
+        Err(e) => return denied(p, args[1], e),
+    };
     let dd = destdir.1.get_inum();
-    for ent in destdir.0.vfd_as_dentry(&destdir.1).unwrap().get_entries() {
-        if ent.filename == f {
-            term.writeln("cp: cannot copy: File exists");
-            return -3;
-        }
+    // a lookup, not a listing, so a directory that can't be read is fine
+    if crate::vfs::futils::resolve(&p.sh.caller(), args[1].to_string()).is_ok() {
+        p.stderr.writeln("cp: cannot copy: File exists");
+        return -3;
     }
     if src.0.file_perms(&src.1).unwrap() & 0xf000 == 0x1000 {
-        let dino = destdir.0.create_directory(dd, f).unwrap();
+        let dino = match destdir.0.create_directory(dd, f) {
+            Ok(i) => i,
+            Err(e) => return denied(p, args[1], e),
+        };
         destdir.0.chmod(&destdir.0.get_fd(dino, 0).unwrap(), perms);
-        if recurse_dir(args[0].to_string(), args[1].to_string()).is_err() {
-            term.writeln("cp: copy not permitted");
+        if recurse_dir(&p.sh.caller(), args[0].to_string(), args[1].to_string()).is_err() {
+            p.stderr.writeln("cp: copy not permitted");
             return -4;
         }
     } else {
-        let fc = src.0.read_to_eof(&mut src.1).unwrap();
-        let fx = destdir.0.create_file(dd, f, &fc).unwrap();
+        let fc = match src.0.read_to_eof(&mut src.1) {
+            Some(c) => c,
+            None => return denied(p, args[0], VfsErrno::EACCES),
+        };
+        let fx = match destdir.0.create_file(dd, f, &fc) {
+            Ok(i) => i,
+            Err(e) => return denied(p, args[1], e),
+        };
         destdir.0.chmod(&destdir.0.get_fd(fx, 0).unwrap(), perms);
     }
     return 0;
 }
 
+fn denied(p: &Proc, f: &str, e: VfsErrno) -> i32 {
+    p.stderr
+        .writeln(&format!("cp: cannot copy '{}': {}", f, e.errno()));
+    -4
+}
+
 // inspired from infsprogs
 // assumes src and dest are directories
 // TODO: this may write onto the wrong FS. dest dir should not just pull the inode,
 // TODO: but should actually be a comprehensive pull, and write operations should be
 // TODO: on dd.0, not ds.0. this will cause problems...
-fn recurse_dir(src: String, dest: String) -> Result<(), ()> {
-    let mut sd = crate::vfs::futils::find_file(src.clone(), false)
+fn recurse_dir(c: &Caller, src: String, dest: String) -> Result<(), ()> {
+    let mut sd = crate::vfs::futils::find_file(c, src.clone(), false)
         .left()
         .unwrap();
-    let dx = crate::vfs::futils::find_file(dest.clone(), false)
+    let mut dx = crate::vfs::futils::find_file(c, dest.clone(), false)
         .left()
         .unwrap();
     let dd = dx.1.get_inum();
-    let sdent = sd.0.vfd_as_dentry(&sd.1).unwrap();
+    let sdent = axo!(sd.0.vfd_as_dentry(&sd.1));
     for f in sdent.get_entries() {
         if f.filename == "." || f.filename == ".." {
             continue;
@@ -74,7 +103,7 @@ fn recurse_dir(src: String, dest: String) -> Result<(), ()> {
         let perms = sd.0.file_perms(&fd).unwrap();
         if perms & 0xf000 == 0x1000 {
             // dentry copy
-            let ds = axo!(sd.0.create_directory(dd, f.filename.clone()));
+            let ds = axr!(sd.0.create_directory(dd, f.filename.clone()));
             sd.0.chmod(&sd.0.get_fd(ds, 0).unwrap(), perms);
             let mut dstr = dest.clone();
             dstr.push('/');
@@ -82,22 +111,22 @@ fn recurse_dir(src: String, dest: String) -> Result<(), ()> {
             let mut sstr = src.clone();
             sstr.push('/');
             sstr.push_str(&f.filename);
-            if recurse_dir(sstr, dstr).is_err() {
+            if recurse_dir(c, sstr, dstr).is_err() {
                 return Err(());
             }
         } else {
             // just copy the files
             // TODO: dedup with the main function
-            let fc = dx.0.read_to_eof(&mut fd).unwrap();
-            let fx = dx.0.create_file(dd, f.filename, &fc).unwrap();
+            let fc = axo!(dx.0.read_to_eof(&mut fd));
+            let fx = axr!(dx.0.create_file(dd, f.filename, &fc));
             dx.0.chmod(&dx.0.get_fd(fx, 0).unwrap(), perms);
         }
     }
     Ok(())
 }
 
-fn ah(term: &Terminal, code: i32) {
-    term.writeln(match code {
+fn ah(p: &Proc, code: i32) {
+    p.stderr.writeln(match code {
         -2 => "cp: cannot copy: No such file or directory",
         _ => "cp: unknown error",
     });
@@ -0,0 +1,166 @@
+use crate::common::{Opt, Spec};
+use crate::kmsg::{self, Message};
+use crate::process::Proc;
+
+const DMESG_SPEC: Spec = Spec {
+    name: "dmesg",
+    usage: "Usage: dmesg [options]
+Display or control the kernel ring buffer.",
+    opts: &[
+        Opt::flag('C', "clear", "clear the kernel ring buffer"),
+        Opt::flag('c', "read-clear", "read and clear all messages"),
+        Opt::flag('l', "level", "restrict output to defined levels").value("LIST"),
+        Opt::flag('T', "ctime", "show human-readable timestamp"),
+        Opt::flag('w', "follow", "wait for new messages"),
+    ],
+    footer: "Supported log levels (priorities):
+   emerg - system is unusable
+   alert - action must be taken immediately
+    crit - critical conditions
+     err - error conditions
+    warn - warning conditions
+  notice - normal but significant condition
+    info - informational
+   debug - debug-level messages",
+    version: None,
+};
+
+struct DmesgOpts {
+    clear: bool,
+    read_clear: bool,
+    // which levels to show; all of them if None
+    levels: Option<Vec<u8>>,
+    ctime: bool,
+    follow: bool,
+}
+
+// "err,warn" to the levels it names
+fn parse_levels(list: &str) -> Result<Vec<u8>, String> {
+    list.split(',')
+        .map(|l| match kmsg::LEVELS.iter().position(|x| *x == l) {
+            Some(n) => Ok(n as u8),
+            None => Err(format!("unknown level '{}'", l)),
+        })
+        .collect()
+}
+
+fn fail(p: &Proc, msg: &str) -> i32 {
+    p.stderr.writeln(&format!("dmesg: {}", msg));
+    p.minfo("dmesg");
+    1
+}
+
+fn show(p: &Proc, opt: &DmesgOpts, m: &Message) {
+    if opt.levels.as_ref().is_some_and(|l| !l.contains(&m.level)) {
+        return;
+    }
+    let stamp = if opt.ctime {
+        format!("[{}] ", kmsg::wall_time(m.usec))
+    } else {
+        kmsg::stamp(m.usec)
+    };
+    for l in m.text.lines() {
+        p.stdout.writeln(&format!("{}{}", stamp, l));
+    }
+}
+
+pub async fn dmesg(p: &Proc<'_>) -> i32 {
+    let args = match p.getopt(&DMESG_SPEC) {
+        Ok(a) => a,
+        Err(st) => return st,
+    };
+    if let Some(a) = args.operands.first() {
+        return fail(p, &format!("bad usage: '{}'", a));
+    }
+    let levels = match args.value("level").map(parse_levels).transpose() {
+        Ok(l) => l,
+        Err(e) => return fail(p, &e),
+    };
+    let opt = DmesgOpts {
+        clear: args.has("clear"),
+        read_clear: args.has("read-clear"),
+        levels,
+        ctime: args.has("ctime"),
+        follow: args.has("follow"),
+    };
+    if opt.clear {
+        kmsg::clear();
+        return 0;
+    }
+    let msgs = kmsg::messages();
+    for m in &msgs {
+        show(p, &opt, m);
+    }
+    if opt.read_clear {
+        kmsg::clear();
+    }
+    if !opt.follow {
+        return 0;
+    }
+    // keeps showing what comes in, until Ctrl-C
+    let mut next = msgs.last().map_or(0, |m| m.seq + 1);
+    while p.sh.tty.sleep(100).await {
+        for m in kmsg::messages() {
+            if m.seq >= next {
+                show(p, &opt, &m);
+                next = m.seq + 1;
+            }
+        }
+    }
+    130
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::process::run_task_test;
+
+    fn dmesg(args: &[&str]) -> (i32, String, String) {
+        run_task_test(|p| Box::pin(super::dmesg(p)), args, None)
+    }
+
+    // the text of each line, without the timestamp
+    fn texts(out: &str) -> Vec<&str> {
+        out.lines().map(|l| l.split_once("] ").unwrap().1).collect()
+    }
+
+    #[test]
+    fn levels_and_clearing() {
+        let _l = crate::process::shell_lock();
+        let sh = crate::process::test_sh();
+        kmsg::clear();
+        kmsg::log(kmsg::INFO, "dummyfs: mounted initfs at /");
+        kmsg::log(3, "oops");
+        kmsg::write_dev(b"<7>noisy\nplain\n");
+        let (st, out, _) = dmesg(&[]);
+        assert_eq!(st, 0);
+        assert_eq!(
+            texts(&out),
+            ["dummyfs: mounted initfs at /", "oops", "noisy", "plain"]
+        );
+        assert_eq!(out.find("] "), Some(13));
+        assert_eq!(texts(&dmesg(&["-l", "err,warn"]).1), ["oops", "plain"]);
+        assert_eq!(texts(&dmesg(&["--level=debug"]).1), ["noisy"]);
+        let kmsg = String::from_utf8(kmsg::read_dev()).unwrap();
+        let first = kmsg.lines().next().unwrap();
+        assert!(first.starts_with("6,") && first.ends_with(",-;dummyfs: mounted initfs at /"));
+        // [Mon Oct 19 08:12:01 2026]
+        assert_eq!(dmesg(&["-T"]).1.find("] "), Some(25));
+        let (st, _, err) = dmesg(&["-l", "bad"]);
+        assert_eq!(
+            (st, err.lines().next()),
+            (1, Some("dmesg: unknown level 'bad'"))
+        );
+        assert_eq!(dmesg(&["-x"]).0, 1);
+        assert_eq!(texts(&dmesg(&["-cl", "err"]).1), ["oops"]);
+        assert_eq!(dmesg(&[]).1, "");
+        kmsg::log(kmsg::INFO, "again");
+        // already interrupted, so -w shows what there is and stops
+        sh.tty.interrupt();
+        let (st, out, _) = dmesg(&["-w"]);
+        assert_eq!((st, texts(&out)), (130, vec!["again"]));
+        sh.tty.reset();
+        dmesg(&["-C"]);
+        assert_eq!(kmsg::messages(), vec![]);
+    }
+}
@@ -0,0 +1,192 @@
+use crate::common::{Opt, Spec};
+use crate::process::Proc;
+use crate::sysvars::Creds;
+use crate::users;
+
+const ID_SPEC: Spec = Spec {
+    name: "id",
+    usage: "Usage: id [OPTION]... [USER]
+Print user and group information for USER,
+or (when USER omitted) for the current user.",
+    opts: &[
+        Opt::flag('g', "group", "print only the effective group ID"),
+        Opt::flag('G', "groups", "print all group IDs"),
+        Opt::flag('n', "name", "print a name instead of a number, for -ugG"),
+        Opt::flag('u', "user", "print only the effective user ID"),
+    ],
+    footer: "",
+    version: None,
+};
+
+// the credentials of USER, or the shell's own
+fn who(p: &Proc, cmd: &str, user: Option<&str>) -> Option<Creds> {
+    match user {
+        None => Some(p.sh.vars.creds()),
+        Some(u) => match users::lookup(u) {
+            Some(u) => Some(users::creds_of(&u)),
+            None => {
+                p.stderr.writeln(&format!("{}: '{}': no such user", cmd, u));
+                None
+            }
+        },
+    }
+}
+
+fn extra(p: &Proc, cmd: &str, arg: &str) -> i32 {
+    p.stderr
+        .writeln(&format!("{}: extra operand '{}'", cmd, arg));
+    p.minfo(cmd);
+    1
+}
+
+pub fn whoami(p: &Proc) -> i32 {
+    let args = p.args();
+    if let Some(a) = args.first() {
+        return extra(p, "whoami", a);
+    }
+    let uid = p.sh.vars.creds().uid;
+    match users::user_name(uid) {
+        Some(n) => {
+            p.stdout.writeln(&n);
+            0
+        }
+        None => {
+            p.stderr
+                .writeln(&format!("whoami: cannot find name for user ID {}", uid));
+            1
+        }
+    }
+}
+
+pub fn id(p: &Proc) -> i32 {
+    let args = match p.getopt(&ID_SPEC) {
+        Ok(a) => a,
+        Err(st) => return st,
+    };
+    // which one of -u, -g or -G, if any
+    let mut only = None;
+    for (o, _) in &args.opts {
+        match o.short {
+            Some('n') => {}
+            c if only.is_some_and(|o| Some(o) != c) => {
+                p.stderr
+                    .writeln("id: cannot print \"only\" of more than one choice");
+                return 1;
+            }
+            c => only = c,
+        }
+    }
+    let name = args.has("name");
+    let user = match args.operands.as_slice() {
+        [] => None,
+        [u] => Some(*u),
+        [_, more, ..] => return extra(p, "id", more),
+    };
+    if name && only.is_none() {
+        p.stderr
+            .writeln("id: cannot print only names or real IDs in default format");
+        return 1;
+    }
+    let c = match who(p, "id", user) {
+        Some(c) => c,
+        None => return 1,
+    };
+    let uid = |n: u32| match (name, users::user_name(n)) {
+        (true, Some(s)) => s,
+        _ => n.to_string(),
+    };
+    let gid = |n: u32| match (name, users::group_name(n)) {
+        (true, Some(s)) => s,
+        _ => n.to_string(),
+    };
+    match only {
+        Some('u') => p.stdout.writeln(&uid(c.uid)),
+        Some('g') => p.stdout.writeln(&gid(c.gid)),
+        Some(_) => p.stdout.writeln(
+            &c.all_groups()
+                .into_iter()
+                .map(gid)
+                .collect::<Vec<String>>()
+                .join(" "),
+        ),
+        None => p.stdout.writeln(&format!(
+            "uid={} gid={} groups={}",
+            users::show_id(c.uid, users::user_name(c.uid)),
+            users::show_id(c.gid, users::group_name(c.gid)),
+            c.all_groups()
+                .into_iter()
+                .map(|g| users::show_id(g, users::group_name(g)))
+                .collect::<Vec<String>>()
+                .join(",")
+        )),
+    }
+    0
+}
+
+// groups [USER]..
+pub fn groups(p: &Proc) -> i32 {
+    let args = p.args();
+    let names = |c: &Creds| {
+        c.all_groups()
+            .into_iter()
+            .map(|g| users::group_name(g).unwrap_or_else(|| g.to_string()))
+            .collect::<Vec<String>>()
+            .join(" ")
+    };
+    if args.is_empty() {
+        p.stdout.writeln(&names(&p.sh.vars.creds()));
+        return 0;
+    }
+    let mut st = 0;
+    for a in &args {
+        match who(p, "groups", Some(a)) {
+            Some(c) => p.stdout.writeln(&format!("{} : {}", a, names(&c))),
+            None => st = 1,
+        }
+    }
+    st
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::process::run_test;
+
+    #[test]
+    fn ids() {
+        let _l = crate::process::shell_lock();
+        crate::vfs::mount_test_fs();
+        let sh = crate::process::test_sh();
+        sh.vars.set_creds(Creds::root());
+        assert_eq!(run_test(whoami, &[], None).1, "root\n");
+        assert_eq!(
+            run_test(id, &[], None).1,
+            "uid=0(root) gid=0(root) groups=0(root)\n"
+        );
+        assert_eq!(
+            run_test(id, &["guest"], None).1,
+            "uid=1000(guest) gid=1000(guest) groups=1000(guest),10(wheel)\n"
+        );
+        assert_eq!(run_test(id, &["-Gn", "guest"], None).1, "guest wheel\n");
+        assert_eq!(run_test(id, &["-u", "1000"], None).1, "1000\n");
+        assert_eq!(run_test(id, &["-n"], None).0, 1);
+        assert_eq!(run_test(id, &["-u", "-g"], None).0, 1);
+        let (st, _, err) = run_test(id, &["nobody"], None);
+        assert_eq!((st, err.as_str()), (1, "id: 'nobody': no such user\n"));
+        assert_eq!(
+            run_test(groups, &["root", "guest"], None).1,
+            "root : root\nguest : guest wheel\n"
+        );
+        sh.vars.set_creds(Creds {
+            uid: 4242,
+            gid: 0,
+            groups: vec![],
+        });
+        let (st, _, err) = run_test(whoami, &[], None);
+        assert_eq!(
+            (st, err.as_str()),
+            (1, "whoami: cannot find name for user ID 4242\n")
+        );
+        sh.vars.set_creds(Creds::root());
+    }
+}
@@ -1,20 +1,36 @@
-use xterm_js_rs::Terminal;
+use crate::common::{Opt, Spec};
+use crate::process::Proc;
+use crate::vfs::VirtualFileSystem;
 
-const LS_HELP: &str = "Usage: ls [OPTION]... [FILE]...
+const LS_SPEC: Spec = Spec {
+    name: "ls",
+    usage: "Usage: ls [OPTION]... [FILE]...
 List information about the FILEs (the current directory by default).
-Entries are sorted alphabetically.
-
-
- -a, --all             do not ignore entries starting with .
- -A, --almost-all      do not list implied . and ..
- -h, --human-readable  with -l, print sizes like 1K 234M 2G etc.
-     --si              likewise, but use powers of 1000 not 1024
- -i, --inode           print the index number of each file
- -l                    use a long listing format
- --help                display this help and exit
- --version             output version information and exit 
-
-Many POSIX ls features are not supported; see ls --version for more info.";
+Entries are sorted alphabetically.",
+    opts: &[
+        Opt::flag('a', "all", "do not ignore entries starting with ."),
+        Opt::flag('A', "almost-all", "do not list implied . and .."),
+        Opt::flag(
+            'h',
+            "human-readable",
+            "with -l, print sizes like 1K 234M 2G etc.",
+        ),
+        Opt::long("si", "likewise, but use powers of 1000 not 1024"),
+        Opt::flag('i', "inode", "print the index number of each file"),
+        Opt::short('l', "use a long listing format"),
+        Opt::flag(
+            'n',
+            "numeric-uid-gid",
+            "like -l, but list numeric user and group IDs",
+        ),
+        Opt::flag('w', "width", "set output width to COLS.  0 means no limit").value("COLS"),
+        Opt::short('x', "list entries by lines instead of by columns"),
+        Opt::short('C', "list entries by columns"),
+        Opt::short('1', "list one file per line"),
+    ],
+    footer: "Many POSIX ls features are not supported; see ls --version for more info.",
+    version: Some(LS_VSN),
+};
 const LS_VSN: &str = "ls (IrisOS-nano) 0.1
 Copyright (C) Amy Parker, 2023
 License AGPLv3+: GNU AGPL version 3 or later <https://gnu.org/licenses/agpl.html>
@@ -30,84 +46,63 @@ struct LsOpts {
     si: bool,
     inode: bool,
     longlist: bool,
-    help: bool,
-    version: bool,
+    numeric: bool,
+    // Some(across) to lay names out in columns, None for one a line
+    columns: Option<bool>,
+    width: usize,
 }
-// TODO: check if /etc/passwd, /etc/group exists, and if so, print user/group name
-pub fn ls(term: &Terminal, mut args: Vec<&str>) -> i32 {
-    let mut opt = LsOpts {
-        all: false,
-        almost_all: false,
-        human_readable: false,
-        si: false,
-        inode: false,
-        longlist: false,
-        help: false,
-        version: false,
+pub fn ls(p: &Proc) -> i32 {
+    let args = match p.getopt(&LS_SPEC) {
+        Ok(a) => a,
+        Err(st) => return st,
     };
-    // TODO: dedup (POSIX tools process largely the same)
-    let mut split = args.len();
-    for n in 0..args.len() {
-        if !args[n].starts_with('-') {
-            split = n;
-            break;
-        }
-    }
-    // TODO: does this (as well as in cat) need to be mut?
-    let (opts, files) = args.split_at_mut(split);
-    for arg in opts {
-        if arg.chars().take(2).last().unwrap() == '-' {
-            match *arg {
-                "--help" => opt.help = true,
-                "--version" => opt.version = true,
-                "--all" => opt.all = true,
-                "--almost-all" => opt.almost_all = true,
-                "--human-readable" => opt.human_readable = true,
-                "--inode" => opt.inode = true,
-                "--si" => opt.si = true,
-                _ => {
-                    term.writeln(&format!("ls: unrecognized option '{}'", arg));
-                    crate::common::minfo(term, "ls");
-                    return 1;
-                }
-            }
-            continue;
-        }
-        for n in arg.get(1..).unwrap().chars() {
-            match n {
-                'a' => opt.all = true,
-                'A' => opt.almost_all = true,
-                'h' => opt.human_readable = true,
-                'i' => opt.inode = true,
-                'l' => opt.longlist = true,
-                _ => {
-                    term.writeln(&format!("ls: invalid option -- '{}'", n));
-                    crate::common::minfo(term, "ls");
-                    return 2;
-                }
-            }
+    // the last of these says how to list; by default, it's in columns
+    // at the terminal and a name a line anywhere else
+    let format = args
+        .opts
+        .iter()
+        .rev()
+        .find_map(|(o, _)| o.short.filter(|c| "lCx1n".contains(*c)))
+        .unwrap_or(if p.stdout.size().is_some() { 'C' } else { '1' });
+    let width = match args.value("width").map(|w| w.parse()) {
+        None => p.width(),
+        Some(Ok(w)) => w,
+        Some(Err(_)) => {
+            p.stderr.writeln(&format!(
+                "ls: invalid line width: '{}'",
+                args.value("width").unwrap()
+            ));
+            return 2;
         }
-    }
-    if opt.help {
-        term.writeln(LS_HELP);
-        return 0;
-    }
-    if opt.version {
-        term.writeln(LS_VSN);
-        return 0;
-    }
+    };
+    let opt = LsOpts {
+        all: args.has("all"),
+        almost_all: args.has("almost-all"),
+        human_readable: args.has("human-readable"),
+        si: args.has("si"),
+        inode: args.has("inode"),
+        longlist: format == 'l' || format == 'n',
+        numeric: args.has("numeric-uid-gid"),
+        columns: match format {
+            'C' => Some(false),
+            'x' => Some(true),
+            _ => None,
+        },
+        width,
+    };
+    let files = args.operands;
     let flen = files.len();
     if flen == 0 {
         // check .
-        process_dir(term, ".", &opt);
+        process_dir(p, ".", &opt);
     }
     for f in files {
         if flen != 1 {
-            term.writeln(&format!("{}:", f));
+            p.stdout.writeln(&format!("{}:", f));
         }
-        process_dir(term, f, &opt);
+        process_dir(p, f, &opt);
         if flen != 1 {
-            term.writeln("");
+            p.stdout.writeln("");
         }
     }
     return 0;
@@ -123,24 +118,17 @@ struct FileEntry {
     hardlinks: u16,
 }
 // TODO: sort directory alphabetically
-fn process_dir(term: &Terminal, dir: &str, opt: &LsOpts) {
+fn process_dir(p: &Proc, dir: &str, opt: &LsOpts) {
     let mut files: Vec<FileEntry> = vec![];
-    // we still need to get the FS to read, but we can
-    // abuse read_to_end's short-circuit operation
-    let ino: u32 = u32::from_le_bytes(
-        crate::vfs::futils::find_file(dir.to_string(), true)
-            .right()
-            .unwrap()
-            .unwrap_or(u32::MAX.to_le_bytes().try_into().unwrap())
-            .try_into()
-            .unwrap(),
-    );
-    if ino == u32::MAX {
-        term.writeln(&format!("ls: {}: No such file or directory", dir));
-        return;
-    }
-    let fsw = crate::vfs::safe_wrap_fdfs(dir.to_string()).0;
-    let fd = fsw.get_fd(ino, 0).unwrap();
+    // the fs it's on, which needn't be the rootfs
+    let (mut fsw, fd) = match crate::vfs::futils::resolve(&p.sh.caller(), dir.to_string()) {
+        Ok(v) => v,
+        Err(e) => {
+            p.stderr.writeln(&format!("ls: {}: {}", dir, e.errno()));
+            return;
+        }
+    };
+    let ino = fd.get_inum();
     if fsw.file_perms(&fd).unwrap() >> 12 != 0x1 {
         files.push(FileEntry {
             perms: fsw.file_perms(&fd).unwrap(),
@@ -153,6 +141,13 @@ fn process_dir(term: &Terminal, dir: &str, opt: &LsOpts) {
             hardlinks: fsw.file_hardlinks(&fd).unwrap(),
         });
     } else {
+        if fsw.access(&fd, crate::vfs::perm::R).is_err() {
+            p.stderr.writeln(&format!(
+                "ls: cannot open directory '{}': Permission denied",
+                dir
+            ));
+            return;
+        }
         let vdent = fsw.vfd_as_dentry(&fd).unwrap();
         for e in vdent.get_entries() {
             if e.filename.starts_with('.')
@@ -184,29 +179,35 @@ fn process_dir(term: &Terminal, dir: &str, opt: &LsOpts) {
         let mut total: u64 = 0;
         let mut longest_uid: u64 = 1;
         let mut longest_gid: u64 = 1;
+        // names from /etc/passwd and /etc/group, where they have them
+        let (users, groups) = match opt.numeric {
+            true => (vec![], vec![]),
+            false => (crate::users::users(), crate::users::groups()),
+        };
+        let owners: Vec<(String, String)> = files
+            .iter()
+            .map(|f| {
+                (
+                    users
+                        .iter()
+                        .find(|u| u.uid == f.owner)
+                        .map_or(f.owner.to_string(), |u| u.name.clone()),
+                    groups
+                        .iter()
+                        .find(|g| g.gid == f.group)
+                        .map_or(f.group.to_string(), |g| g.name.clone()),
+                )
+            })
+            .collect();
         let mut longest_size: u64 = 1;
         let mut longest_hlc: u64 = 1;
         // two passes: gather data, then write
-        for f in &files {
+        for (f, (owner, group)) in files.iter().zip(&owners) {
             if f.perms >> 12 == 0 {
                 total += f.tfs;
             }
-            longest_uid = std::cmp::max(
-                longest_uid,
-                if f.owner != 0 {
-                    (f.owner.ilog10() + 1) as u64
-                } else {
-                    0
-                },
-            );
-            longest_gid = std::cmp::max(
-                longest_gid,
-                if f.group != 0 {
-                    (f.group.ilog10() + 1) as u64
-                } else {
-                    0
-                },
-            );
+            longest_uid = std::cmp::max(longest_uid, owner.len() as u64);
+            longest_gid = std::cmp::max(longest_gid, group.len() as u64);
             longest_size = std::cmp::max(
                 longest_size,
                 calculate_size_chars_necessary(f.tfs, opt.human_readable, opt.si),
@@ -220,15 +221,15 @@ fn process_dir(term: &Terminal, dir: &str, opt: &LsOpts) {
                 },
             );
         }
-        term.writeln(&format!("total {}", total));
-        for f in files {
+        p.stdout.writeln(&format!("total {}", total));
+        for (f, (owner, group)) in files.into_iter().zip(owners) {
             if opt.inode {
-                term.write(&format!(
+                p.stdout.write(&format!(
                     "{} ",
                     crate::common::shift_in_text(&f.inode.to_string(), longest_inum as usize)
                 ));
             }
-            term.write(if f.perms & (1 << 11) != 0 {
+            p.stdout.write(if f.perms & (1 << 11) != 0 {
                 "a"
             } else {
                 match f.perms >> 12 {
@@ -238,9 +239,11 @@ fn process_dir(term: &Terminal, dir: &str, opt: &LsOpts) {
                     _ => panic!("unsupported file type"),
                 }
             });
-            term.write(if f.perms & (1 << 8) != 0 { "r" } else { "-" });
-            term.write(if f.perms & (1 << 7) != 0 { "w" } else { "-" });
-            term.write(if f.perms & (1 << 10) != 0 {
+            p.stdout
+                .write(if f.perms & (1 << 8) != 0 { "r" } else { "-" });
+            p.stdout
+                .write(if f.perms & (1 << 7) != 0 { "w" } else { "-" });
+            p.stdout.write(if f.perms & (1 << 10) != 0 {
                 "s"
             } else {
                 if f.perms & (1 << 6) != 0 {
@@ -249,9 +252,11 @@ fn process_dir(term: &Terminal, dir: &str, opt: &LsOpts) {
                     "-"
                 }
             });
-            term.write(if f.perms & (1 << 5) != 0 { "r" } else { "-" });
-            term.write(if f.perms & (1 << 4) != 0 { "w" } else { "-" });
-            term.write(if f.perms & (1 << 9) != 0 {
+            p.stdout
+                .write(if f.perms & (1 << 5) != 0 { "r" } else { "-" });
+            p.stdout
+                .write(if f.perms & (1 << 4) != 0 { "w" } else { "-" });
+            p.stdout.write(if f.perms & (1 << 9) != 0 {
                 "s"
             } else {
                 if f.perms & (1 << 3) != 0 {
@@ -260,15 +265,18 @@ fn process_dir(term: &Terminal, dir: &str, opt: &LsOpts) {
                     "-"
                 }
             });
-            term.write(if f.perms & (1 << 2) != 0 { "r" } else { "-" });
-            term.write(if f.perms & (1 << 1) != 0 { "w" } else { "-" });
-            term.write(if f.perms & (1 << 0) != 0 { "x" } else { "-" });
+            p.stdout
+                .write(if f.perms & (1 << 2) != 0 { "r" } else { "-" });
+            p.stdout
+                .write(if f.perms & (1 << 1) != 0 { "w" } else { "-" });
+            p.stdout
+                .write(if f.perms & (1 << 0) != 0 { "x" } else { "-" });
             // TODO: add color to file name
-            term.writeln(&format!(
+            p.stdout.writeln(&format!(
                 " {} {} {}  {} {} {}",
                 crate::common::shift_in_text(&f.hardlinks.to_string(), longest_hlc as usize),
-                crate::common::shift_in_text(&f.owner.to_string(), longest_uid as usize),
-                crate::common::shift_in_text(&f.group.to_string(), longest_gid as usize),
+                crate::common::shift_in_text(&owner, longest_uid as usize),
+                crate::common::shift_in_text(&group, longest_gid as usize),
                 crate::common::shift_in_text(
                     &format_sizes(f.tfs, opt.human_readable, opt.si),
                     longest_size as usize
@@ -278,17 +286,23 @@ fn process_dir(term: &Terminal, dir: &str, opt: &LsOpts) {
             ));
         }
     } else {
-        // We could support columns, but it's unnecessarily intensive
-        // Instead, we just print out line by line
-        // TODO: support columns
-        for f in files {
-            if opt.inode {
-                term.write(&format!(
-                    "{} ",
-                    crate::common::shift_in_text(&f.inode.to_string(), longest_inum as usize)
-                ));
-            }
-            term.writeln(&f.filename);
+        let names: Vec<String> = files
+            .into_iter()
+            .map(|f| match opt.inode {
+                true => format!(
+                    "{} {}",
+                    crate::common::shift_in_text(&f.inode.to_string(), longest_inum as usize),
+                    f.filename
+                ),
+                false => f.filename,
+            })
+            .collect();
+        let lines = match opt.columns {
+            Some(across) => crate::linebuf::columns(&names, opt.width, across),
+            None => names,
+        };
+        for l in lines {
+            p.stdout.writeln(&l);
         }
     }
 }
@@ -335,3 +349,42 @@ fn format_sizes(num: u64, human: bool, si: bool) -> String {
     };
     format!("{}{}", num / div.pow(log), size)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::process::run_test;
+    use crate::vfs::futils::write_file;
+
+    #[test]
+    fn columns() {
+        let _l = crate::process::shell_lock();
+        crate::vfs::mount_test_fs();
+        let sh = crate::process::test_sh();
+        sh.vars.set_creds(crate::sysvars::Creds::root());
+        run_test(crate::unix::mkdir::mkdir, &["/home/ls-c"], None);
+        for f in ["a", "bb", "ccc", "dddd", "e"] {
+            write_file(&sh.caller(), &format!("/home/ls-c/{}", f), b"", false).unwrap();
+        }
+        let out = |args: &[&str]| run_test(ls, args, None).1;
+        // not at the terminal, it's one a line unless asked
+        assert_eq!(out(&["/home/ls-c"]), "a\nbb\nccc\ndddd\ne\n");
+        assert_eq!(out(&["-C", "/home/ls-c"]), "a  bb  ccc  dddd  e\n");
+        assert_eq!(out(&["-Cw", "12", "/home/ls-c"]), "a   ccc   e\nbb  dddd\n");
+        assert_eq!(
+            out(&["-x", "--width=12", "/home/ls-c"]),
+            "a    bb\nccc  dddd\ne\n"
+        );
+        assert_eq!(out(&["-C1", "/home/ls-c"]), out(&["/home/ls-c"]));
+        let (st, _, err) = run_test(ls, &["-w", "x"], None);
+        assert_eq!((st, err.as_str()), (2, "ls: invalid line width: 'x'\n"));
+        for f in ["a", "bb", "ccc", "dddd", "e"] {
+            let f = format!("/home/ls-c/{}", f);
+            assert_eq!(run_test(crate::unix::rm::rm, &[&f], None).0, 0);
+        }
+        assert_eq!(
+            run_test(crate::unix::rmdir::rmdir, &["/home/ls-c"], None).0,
+            0
+        );
+    }
+}
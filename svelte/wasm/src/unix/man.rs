@@ -0,0 +1,304 @@
+// man, whatis and apropos. Pages live on the rootfs, as
+// /usr/share/man/manN/NAME.N in roff (see roff.rs); MANPATH can point
+// elsewhere. whatis and apropos go by each page's NAME section.
+
+use crate::common::{Opt, Spec};
+use crate::glob::entries;
+use crate::process::Proc;
+use crate::sysvars::Caller;
+use crate::vfs::futils::read_file;
+use crate::{pager, roff};
+
+const MAN_SPEC: Spec = Spec {
+    name: "man",
+    usage: "Usage: man [SECTION] PAGE...
+  or:  man -k KEYWORD...
+  or:  man -f PAGE...
+Show the manual page for each PAGE, from the first section that has
+one, or only from SECTION.",
+    opts: &[
+        Opt::flag(
+            'f',
+            "whatis",
+            "same as whatis: describe each PAGE in a line",
+        ),
+        Opt::flag('k', "apropos", "same as apropos: search the descriptions"),
+    ],
+    footer: "",
+    version: None,
+};
+
+const WHATIS_SPEC: Spec = Spec {
+    name: "whatis",
+    usage: "Usage: whatis PAGE...
+Show the one-line description of each manual page named PAGE.",
+    opts: &[],
+    footer: "",
+    version: None,
+};
+
+const APROPOS_SPEC: Spec = Spec {
+    name: "apropos",
+    usage: "Usage: apropos KEYWORD...
+Search the names and descriptions of the manual pages for KEYWORD.",
+    opts: &[],
+    footer: "",
+    version: None,
+};
+
+// the sections, in the order man looks through them
+const SECTIONS: [&str; 8] = ["1", "8", "3", "2", "5", "4", "6", "7"];
+
+fn manpath(p: &Proc) -> Vec<String> {
+    match p.env.get("MANPATH").filter(|m| !m.is_empty()) {
+        Some(m) => m
+            .split(':')
+            .filter(|d| !d.is_empty())
+            .map(|d| d.to_string())
+            .collect(),
+        None => vec!["/usr/share/man".to_string()],
+    }
+}
+
+// a page as found: its name, section and roff
+struct Page {
+    name: String,
+    sec: String,
+    src: String,
+}
+
+impl Page {
+    // "ls (1) - list directory contents", as whatis puts it
+    fn whatis(&self) -> String {
+        let desc = roff::name_section(&self.src)
+            .and_then(|n| n.split_once(" - ").map(|(_, d)| d.to_string()))
+            .unwrap_or_else(|| "(unknown subject)".to_string());
+        format!("{:<20} - {}", format!("{} ({})", self.name, self.sec), desc)
+    }
+}
+
+// A page that only says ".so man1/other.1" is the same as that one, as
+// for whatis.1 standing in for apropos.
+fn read(c: &Caller, dir: &str, name: &str, sec: &str) -> Option<Page> {
+    let mut path = format!("{}/man{}/{}.{}", dir, sec, name, sec);
+    let mut src = String::new();
+    // only so far, in case they point at each other
+    for _ in 0..4 {
+        src = String::from_utf8_lossy(&read_file(c, path).ok()?).into_owned();
+        match src.trim_end().strip_prefix(".so ") {
+            Some(to) if !to.contains('\n') => path = format!("{}/{}", dir, to.trim()),
+            _ => break,
+        }
+    }
+    Some(Page {
+        name: name.to_string(),
+        sec: sec.to_string(),
+        src,
+    })
+}
+
+// the page called name, from sec or the first section that has one
+fn find(p: &Proc, name: &str, sec: Option<&str>) -> Option<Page> {
+    let secs = match sec {
+        Some(s) => vec![s],
+        None => SECTIONS.to_vec(),
+    };
+    let c = p.sh.caller();
+    manpath(p)
+        .iter()
+        .find_map(|d| secs.iter().find_map(|s| read(&c, d, name, s)))
+}
+
+// every page there is
+fn all(p: &Proc) -> Vec<Page> {
+    let c = p.sh.caller();
+    let mut pages = vec![];
+    for d in manpath(p) {
+        for s in SECTIONS {
+            let mut names: Vec<String> = entries(&c, &format!("{}/man{}", d, s))
+                .unwrap_or_default()
+                .into_iter()
+                .filter_map(|f| f.strip_suffix(&format!(".{}", s)).map(|n| n.to_string()))
+                .collect();
+            names.sort();
+            pages.extend(names.iter().filter_map(|n| read(&c, &d, n, s)));
+        }
+    }
+    pages
+}
+
+pub fn whatis(p: &Proc) -> i32 {
+    let args = match p.getopt(&WHATIS_SPEC) {
+        Ok(a) => a.operands,
+        Err(st) => return st,
+    };
+    if args.is_empty() {
+        p.stderr.writeln("whatis what?");
+        return 1;
+    }
+    let mut st = 0;
+    for a in args {
+        match find(p, a, None) {
+            Some(pg) => p.stdout.writeln(&pg.whatis()),
+            None => {
+                p.stderr.writeln(&format!("{}: nothing appropriate.", a));
+                st = 1;
+            }
+        }
+    }
+    st
+}
+
+pub fn apropos(p: &Proc) -> i32 {
+    let args = match p.getopt(&APROPOS_SPEC) {
+        Ok(a) => a.operands,
+        Err(st) => return st,
+    };
+    if args.is_empty() {
+        p.stderr.writeln("apropos what?");
+        return 1;
+    }
+    let mut st = 0;
+    let pages = all(p);
+    for a in args {
+        let k = a.to_lowercase();
+        let found: Vec<String> = pages
+            .iter()
+            .map(|pg| pg.whatis())
+            .filter(|w| w.to_lowercase().contains(&k))
+            .collect();
+        if found.is_empty() {
+            p.stderr.writeln(&format!("{}: nothing appropriate.", a));
+            st = 1;
+        }
+        for w in found {
+            p.stdout.writeln(&w);
+        }
+    }
+    st
+}
+
+pub async fn man(p: &Proc<'_>) -> i32 {
+    let args = match p.getopt(&MAN_SPEC) {
+        Ok(a) => a,
+        Err(st) => return st,
+    };
+    if args.has("whatis") {
+        return whatis(&p.with_args(&args.operands));
+    }
+    if args.has("apropos") {
+        return apropos(&p.with_args(&args.operands));
+    }
+    let mut names = args.operands;
+    // a section first, if there's something after it
+    let sec = match names.as_slice() {
+        [s, _, ..] if s.chars().all(|c| c.is_ascii_digit()) => Some(names.remove(0)),
+        _ => None,
+    };
+    if names.is_empty() {
+        p.stderr.writeln("What manual page do you want?");
+        p.stderr.writeln("For example, try 'man man'.");
+        return 1;
+    }
+    let width = p.stdout.size().map_or(80, |(cols, _)| cols);
+    let mut st = 0;
+    for name in names {
+        let pg = match find(p, name, sec) {
+            Some(pg) => pg,
+            None => {
+                match sec {
+                    Some(s) => p
+                        .stderr
+                        .writeln(&format!("No manual entry for {} in section {}", name, s)),
+                    None => p.stderr.writeln(&format!("No manual entry for {}", name)),
+                }
+                st = 16;
+                continue;
+            }
+        };
+        pager::page(p, &roff::render(&pg.src, width)).await;
+        if p.sh.tty.interrupted() {
+            return 130;
+        }
+    }
+    st
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::process::{run_task_test, run_test};
+    use crate::sysvars::Creds;
+    use crate::vfs::futils::write_file;
+
+    #[test]
+    fn pages() {
+        let _l = crate::process::shell_lock();
+        crate::vfs::mount_test_fs();
+        let sh = crate::process::test_sh();
+        sh.vars.set_creds(Creds::root());
+        sh.vars.export("MANPATH", Some("/home/man"));
+        for d in ["/home/man", "/home/man/man1", "/home/man/man5"] {
+            run_test(crate::unix::mkdir::mkdir, &[d], None);
+        }
+        write_file(
+            &sh.caller(),
+            "/home/man/man1/hi.1",
+            b".TH HI 1\n.SH NAME\nhi \\- say \\fBhello\\fR\n.SH DESCRIPTION\nSays hello.\n",
+            false,
+        )
+        .unwrap();
+        write_file(
+            &sh.caller(),
+            "/home/man/man5/hi.5",
+            b".SH NAME\nhi \\- the hi file\n",
+            false,
+        )
+        .unwrap();
+        write_file(
+            &sh.caller(),
+            "/home/man/man1/hello.1",
+            b".so man1/hi.1\n",
+            false,
+        )
+        .unwrap();
+
+        let man = |args: &[&str]| run_task_test(|p| Box::pin(super::man(p)), args, None);
+        let (st, out, _) = man(&["hi"]);
+        assert_eq!(st, 0);
+        assert!(out.starts_with("HI(1)"));
+        assert!(out.contains("\n       Says hello.\n"));
+        assert!(man(&["5", "hi"]).1.contains("the hi file"));
+        assert_eq!(man(&["hello"]).1, out);
+        assert_eq!(
+            man(&["3", "hi"]),
+            (
+                16,
+                String::new(),
+                "No manual entry for hi in section 3\n".to_string()
+            )
+        );
+        assert_eq!(
+            run_test(whatis, &["hi"], None).1,
+            "hi (1)               - say hello\n"
+        );
+        assert_eq!(
+            man(&["-k", "FILE"]).1,
+            "hi (5)               - the hi file\n"
+        );
+        assert_eq!(
+            run_test(apropos, &["hi"], None).1,
+            "hi (1)               - say hello\nhi (5)               - the hi file\n"
+        );
+        assert_eq!(
+            run_test(apropos, &["say"], None).1,
+            "hello (1)            - say hello\nhi (1)               - say hello\n"
+        );
+        assert_eq!(
+            run_test(whatis, &["nope"], None).2,
+            "nope: nothing appropriate.\n"
+        );
+        run_test(crate::unix::rmdir::rmdir, &["/home/man"], None);
+        sh.vars.unsetenv("MANPATH");
+    }
+}
@@ -1,37 +1,57 @@
-use xterm_js_rs::Terminal;
+use crate::common::Spec;
+use crate::process::Proc;
+use crate::vfs::{VfsErrno, VirtualFileSystem};
 
-pub fn mkdir(term: &Terminal, args: Vec<&str>) -> i32 {
+const MKDIR_SPEC: Spec = Spec {
+    name: "mkdir",
+    usage: "Usage: mkdir DIRECTORY
+Create the DIRECTORY, if it does not already exist.",
+    opts: &[],
+    footer: "",
+    version: None,
+};
+
+pub fn mkdir(p: &Proc) -> i32 {
+    let args = match p.getopt(&MKDIR_SPEC) {
+        Ok(a) => a.operands,
+        Err(st) => return st,
+    };
     if args.len() == 0 {
-        term.writeln("mkdir: missing directory operand");
+        p.stderr.writeln("mkdir: missing directory operand");
         return -1;
     }
     if args.len() >= 2 {
-        term.writeln("mkdir: too many arguments");
+        p.stderr.writeln("mkdir: too many arguments");
         return -1;
     }
-    if args[0] == "--help" {
-        term.writeln("Usage: mkdir [target]");
-        return 0;
-    }
     let mut parts: Vec<&str> = args[0].rsplitn(2, '/').collect();
     if parts.len() == 1 {
         parts.push(".");
     }
-    if crate::vfs::futils::find_file(args[0].to_string(), false).is_left() {
-        term.writeln("mkdir: cannot create dir: File exists");
+    if crate::vfs::futils::find_file(&p.sh.caller(), args[0].to_string(), false).is_left() {
+        p.stderr.writeln("mkdir: cannot create dir: File exists");
         return -2;
     }
-    let pino = crate::vfs::futils::find_file(parts[1].to_string(), false)
-        .left()
-        .unwrap();
-
-    if pino
-        .0
-        .create_directory(pino.1.get_inum(), parts[0].to_string())
-        .is_none()
-    {
-        term.writeln("mkdir: could not create directory: read-only filesystem");
-        return -3;
+    let r =
+        crate::vfs::futils::resolve(&p.sh.caller(), parts[1].to_string()).and_then(|mut pino| {
+            pino.0
+                .create_directory(pino.1.get_inum(), parts[0].to_string())
+        });
+    match r {
+        Ok(_) => {}
+        Err(e @ (VfsErrno::EACCES | VfsErrno::ENOENT)) => {
+            p.stderr.writeln(&format!(
+                "mkdir: cannot create directory '{}': {}",
+                args[0],
+                e.errno()
+            ));
+            return -3;
+        }
+        Err(_) => {
+            p.stderr
+                .writeln("mkdir: could not create directory: read-only filesystem");
+            return -3;
+        }
     }
     return 0;
 }
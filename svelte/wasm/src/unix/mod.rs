@@ -1,21 +1,25 @@
 pub mod cat;
 pub mod cd;
+pub mod chmod;
+pub mod chown;
+pub mod chvt;
 pub mod cp;
+pub mod dmesg;
 pub mod echo;
+pub mod id;
 pub mod ln;
 pub mod ls;
+pub mod man;
 pub mod mkdir;
 pub mod mv;
 pub mod pwd;
 pub mod rm;
 pub mod rmdir;
+pub mod setterm;
+pub mod sleep;
+pub mod stty;
+pub mod su;
+pub mod test;
 pub mod touch;
+pub mod tput;
 pub mod uname;
-
-// Model for arg parsing:
-// &bool Option<&str> Option<&str>
-// &bool points to the bool in the structure
-// first option is longname
-// second option is shortname
-// None if one is not present
-// May need to set up as a macro
@@ -1,30 +1,42 @@
+use crate::common::Spec;
 use crate::errors::ao;
-use xterm_js_rs::Terminal;
+use crate::process::Proc;
+use crate::vfs::VirtualFileSystem;
 
 // Not UNIX compliant, no options support
 // TODO: don't allow moves to somewhere that already exists
 
-pub fn mv(term: &Terminal, args: Vec<&str>) -> i32 {
-    if args.len() == 1 && args[0] == "--help" {
-        term.writeln("Usage: mv [src] [dest]");
-        return 0;
-    }
+const MV_SPEC: Spec = Spec {
+    name: "mv",
+    usage: "Usage: mv SOURCE DEST
+Rename SOURCE to DEST.",
+    opts: &[],
+    footer: "",
+    version: None,
+};
+
+pub fn mv(p: &Proc) -> i32 {
+    let args = match p.getopt(&MV_SPEC) {
+        Ok(a) => a.operands,
+        Err(st) => return st,
+    };
     if args.len() < 2 {
-        term.writeln("mv: missing file operand");
-        crate::common::minfo(term, "mv");
+        p.stderr.writeln("mv: missing file operand");
+        p.minfo("mv");
         return -1;
     }
     if args.len() > 2 {
-        term.writeln("mv: too many arguments");
-        crate::common::minfo(term, "mv");
+        p.stderr.writeln("mv: too many arguments");
+        p.minfo("mv");
         return -1;
     }
-    if crate::vfs::futils::find_file(args[1].to_string(), false).is_left() {
-        term.writeln("mv: cannot move: File exists");
+    if crate::vfs::futils::find_file(&p.sh.caller(), args[1].to_string(), false).is_left() {
+        p.stderr.writeln("mv: cannot move: File exists");
         return -3;
     }
     let pi: u32 = u32::from_le_bytes(
         crate::vfs::futils::find_file(
+            &p.sh.caller(),
             args[0].rsplitn(2, '/').nth(1).unwrap_or(".").to_string(),
             true,
         )
@@ -35,16 +47,17 @@ pub fn mv(term: &Terminal, args: Vec<&str>) -> i32 {
         .unwrap(),
     );
     // we don't short - we need the resulting FS
-    let df = ao!(
-        crate::vfs::futils::find_file(args[0].to_string(), false).left(),
+    let mut df = ao!(
+        crate::vfs::futils::find_file(&p.sh.caller(), args[0].to_string(), false).left(),
         ah,
         -2,
-        term
+        p
     );
     let di = df.1.get_inum();
     // TODO: optimize out this double rsplitn call
     let si: u32 = u32::from_le_bytes(
         crate::vfs::futils::find_file(
+            &p.sh.caller(),
             args[1].rsplitn(2, '/').nth(1).unwrap_or(".").to_string(),
             true,
         )
@@ -57,13 +70,25 @@ pub fn mv(term: &Terminal, args: Vec<&str>) -> i32 {
     let name = args[1].rsplitn(2, '/').next().unwrap().to_string();
     // this will fail if they aren't on the same fs - if the same inode num exists, this goes very bad
     // TODO: check that they are both actually on the same fs
-    df.0.hardlink(si, di, name);
-    df.0.delete_file(di, pi);
+    // taking it out of where it was needs the same permission as
+    // putting it in, so check before doing either
+    let r = match df.0.get_fd(pi, 0) {
+        Some(fd) => df.0.access(&fd, crate::vfs::perm::W),
+        None => Err(crate::vfs::VfsErrno::EINVFD),
+    };
+    let r = r
+        .and_then(|_| df.0.hardlink(si, di, name))
+        .and_then(|_| df.0.delete_file(di, pi));
+    if let Err(e) = r {
+        p.stderr
+            .writeln(&format!("mv: cannot move '{}': {}", args[0], e.errno()));
+        return -4;
+    }
     return 0;
 }
 
-fn ah(term: &Terminal, code: i32) {
-    term.writeln(match code {
+fn ah(p: &Proc, code: i32) {
+    p.stderr.writeln(match code {
         -2 => "mv: cannot move: No such file or directory",
         _ => "mv: unknown error",
     })
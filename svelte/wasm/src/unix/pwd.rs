@@ -1,13 +1,14 @@
-use xterm_js_rs::Terminal;
+use crate::process::Proc;
 
-pub fn pwd(term: &Terminal, _args: Vec<&str>) -> i32 {
-    if _args.len() != 0 {
-        term.writeln("pwd: warn: implementation does not support arguments");
+pub fn pwd(p: &Proc) -> i32 {
+    if p.args.len() != 0 {
+        p.stderr
+            .writeln("pwd: warn: implementation does not support arguments");
     }
-    let mut r = crate::sysvars::load_cwd();
+    let mut r = p.cwd.clone();
     if r.ends_with('/') && r.len() > 1 {
         r.pop();
     }
-    term.writeln(&r);
+    p.stdout.writeln(&r);
     return 0;
 }
@@ -1,37 +1,47 @@
-use xterm_js_rs::Terminal;
+use crate::common::Spec;
+use crate::process::Proc;
+use crate::vfs::VirtualFileSystem;
 
-pub fn rm(term: &Terminal, args: Vec<&str>) -> i32 {
-    // TODO: dedup this logic with other commands like touch
+const RM_SPEC: Spec = Spec {
+    name: "rm",
+    usage: "Usage: rm FILE
+Remove the FILE. Directories are removed with rmdir.",
+    opts: &[],
+    footer: "",
+    version: None,
+};
+
+pub fn rm(p: &Proc) -> i32 {
+    let args = match p.getopt(&RM_SPEC) {
+        Ok(a) => a.operands,
+        Err(st) => return st,
+    };
     if args.len() > 1 {
-        term.writeln("rm: too many arguments");
-        crate::common::minfo(term, "rm");
+        p.stderr.writeln("rm: too many arguments");
+        p.minfo("rm");
         return -1;
     }
     if args.len() < 1 {
-        term.writeln("rm: missing file operand");
-        crate::common::minfo(term, "rm");
+        p.stderr.writeln("rm: missing file operand");
+        p.minfo("rm");
         return -1;
     }
-    if args[0] == "--help" {
-        term.writeln("Usage: rm [target]");
-        return 0;
-    }
-    let mut f = match crate::vfs::futils::find_file(args[0].to_string(), false).left() {
-        Some(v) => v,
-        None => {
-            term.writeln(&format!(
-                "rm: cannot remove '{}': No such file or directory",
-                args[0]
-            ));
+    let mut f = match crate::vfs::futils::resolve(&p.sh.caller(), args[0].to_string()) {
+        Ok(v) => v,
+        Err(e) => {
+            p.stderr
+                .writeln(&format!("rm: cannot remove '{}': {}", args[0], e.errno()));
             return 1;
         }
     };
     if f.0.file_perms(&f.1).unwrap() & 0xf000 == 0x1000 {
-        term.writeln(&format!("rm: cannot remove '{}': Is a directory", args[0]));
+        p.stderr
+            .writeln(&format!("rm: cannot remove '{}': Is a directory", args[0]));
         return 2;
     }
     let pino = u32::from_le_bytes(
         crate::vfs::futils::find_file(
+            &p.sh.caller(),
             args[0].rsplitn(2, '/').nth(1).unwrap_or(".").to_string(),
             true,
         )
@@ -41,6 +51,54 @@ pub fn rm(term: &Terminal, args: Vec<&str>) -> i32 {
         .try_into()
         .unwrap(),
     );
-    f.0.delete_file(f.1.get_inum(), pino).unwrap();
+    if let Err(e) = f.0.delete_file(f.1.get_inum(), pino) {
+        p.stderr
+            .writeln(&format!("rm: cannot remove '{}': {}", args[0], e.errno()));
+        return 1;
+    }
     return 0;
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::process::run_test;
+    use crate::sysvars::Creds;
+
+    #[test]
+    fn operands() {
+        let _l = crate::process::shell_lock();
+        crate::vfs::mount_test_fs();
+        let sh = crate::process::test_sh();
+        sh.vars.set_creds(Creds::root());
+        let touch = crate::unix::touch::touch;
+        let mkdir = crate::unix::mkdir::mkdir;
+        // -- ends the options, so a file can start with -
+        assert_eq!(run_test(touch, &["--", "/home/-x"], None).0, 0);
+        assert_eq!(run_test(rm, &["-x"], None).0, 1);
+        assert_eq!(
+            run_test(rm, &["--", "/home/-x"], None),
+            (0, "".into(), "".into())
+        );
+        assert_eq!(run_test(touch, &["/home/-x"], None).0, 0);
+        assert_eq!(run_test(rm, &["/home/-x"], None).0, 0);
+        // --help wherever it is
+        let (st, out, _) = run_test(mkdir, &["/home/x", "--help"], None);
+        assert_eq!(
+            (st, out.lines().next()),
+            (0, Some("Usage: mkdir DIRECTORY"))
+        );
+        assert_eq!(
+            run_test(rm, &["-f", "x"], None),
+            (
+                1,
+                "".into(),
+                "rm: invalid option -- 'f'\nTry 'rm --help' for more information.\n".into()
+            )
+        );
+        assert_eq!(
+            run_test(rm, &[], None).2,
+            "rm: missing file operand\nTry 'rm --help' for more information.\n"
+        );
+    }
+}
@@ -1,66 +1,95 @@
+use crate::common::Spec;
 use crate::errors::ao;
-use xterm_js_rs::Terminal;
+use crate::process::Proc;
+use crate::vfs::VirtualFileSystem;
 
-pub fn rmdir(term: &Terminal, args: Vec<&str>) -> i32 {
+const RMDIR_SPEC: Spec = Spec {
+    name: "rmdir",
+    usage: "Usage: rmdir DIRECTORY
+Remove the DIRECTORY, along with everything in it.",
+    opts: &[],
+    footer: "",
+    version: None,
+};
+
+pub fn rmdir(p: &Proc) -> i32 {
+    let args = match p.getopt(&RMDIR_SPEC) {
+        Ok(a) => a.operands,
+        Err(st) => return st,
+    };
     if args.len() < 1 {
-        term.writeln("rmdir: missing file operand");
-        crate::common::minfo(term, "rmdir");
+        p.stderr.writeln("rmdir: missing file operand");
+        p.minfo("rmdir");
         return -1;
     }
     if args.len() > 1 {
-        term.writeln("rmdir: too many arguments");
-        crate::common::minfo(term, "rmdir");
+        p.stderr.writeln("rmdir: too many arguments");
+        p.minfo("rmdir");
         return -2;
     }
-    if args[0] == "--help" {
-        term.writeln("Usage: rmdir [target]");
-        return 0;
-    }
     let mut dd = ao!(
-        crate::vfs::futils::find_file(args[0].to_string(), false).left(),
+        crate::vfs::futils::find_file(&p.sh.caller(), args[0].to_string(), false).left(),
         ah,
         -3,
-        term
+        p
     );
     if dd.0.file_perms(&dd.1).unwrap() & 0xf000 != 0x1000 {
-        term.writeln("rmdir: cannot remove directory: Not a directory");
+        p.stderr
+            .writeln("rmdir: cannot remove directory: Not a directory");
         return -4;
     }
-    recurse_dir(&mut dd.0, &dd.1);
-    let pd = ao!(
+    if let Err(e) = recurse_dir(&mut dd.0, &dd.1) {
+        p.stderr.writeln(&format!(
+            "rmdir: cannot remove '{}': {}",
+            args[0],
+            e.errno()
+        ));
+        return -5;
+    }
+    let mut pd = ao!(
         crate::vfs::futils::find_file(
+            &p.sh.caller(),
             args[0].rsplitn(2, '/').nth(1).unwrap_or(".").to_string(),
             false
         )
         .left(),
         ah,
         -3,
-        term
+        p
     );
-    pd.0.delete_file(dd.1.get_inum(), pd.1.get_inum());
+    if let Err(e) = pd.0.delete_file(dd.1.get_inum(), pd.1.get_inum()) {
+        p.stderr.writeln(&format!(
+            "rmdir: cannot remove '{}': {}",
+            args[0],
+            e.errno()
+        ));
+        return -5;
+    }
     return 0;
 }
 
-// Assumes directory is pre-checked
+// Assumes directory is pre-checked; stops at the first thing that
+// can't be removed
 fn recurse_dir(
-    fs: &mut Box<dyn crate::vfs::VirtualFileSystem>,
+    fs: &mut crate::vfs::perm::Checked,
     dent: &Box<dyn crate::vfs::VirtualFileDescriptor>,
-) {
-    let mut ents = fs.vfd_as_dentry(dent).unwrap();
+) -> crate::vfs::VfsResult {
+    let ents = fs.vfd_as_dentry(dent).ok_or(crate::vfs::VfsErrno::EACCES)?;
     for ent in ents.get_entries() {
         if ent.filename == "." || ent.filename == ".." {
             continue;
         }
-        let mut fd = fs.get_fd(ent.inum, 0).unwrap();
+        let fd = fs.get_fd(ent.inum, 0).unwrap();
         if fs.file_perms(&fd).unwrap() & 0xf000 == 0x1000 {
-            recurse_dir(fs, &fd);
+            recurse_dir(fs, &fd)?;
         }
-        fs.delete_file(ent.inum, ents.get_inode()).unwrap();
+        fs.delete_file(ent.inum, ents.get_inode())?;
     }
+    Ok(())
 }
 
-fn ah(term: &Terminal, code: i32) {
-    term.writeln(match code {
+fn ah(p: &Proc, code: i32) {
+    p.stderr.writeln(match code {
         -3 => "rmdir: cannot remove directory: No such file or directory",
         _ => "rmdir: unknown error",
     });
@@ -0,0 +1,120 @@
+// setterm, for the parts of the terminal that aren't colors (see
+// theme in nanotools.rs for those). Unlike the real one, it also sees
+// to the font, and what it changes is kept across reloads.
+
+use crate::common::{Opt, Spec};
+use crate::process::Proc;
+use crate::theme::{self, CURSORS, FONT_SIZES};
+
+const SETTERM_SPEC: Spec = Spec {
+    name: "setterm",
+    usage: "Usage: setterm [options]
+Set terminal attributes. With no options, show the ones that stick.",
+    opts: &[
+        Opt::long("cursor", "display cursor").value("on|off"),
+        Opt::long("cursor-blink", "make the cursor blink").value("on|off"),
+        Opt::long("cursor-style", "cursor shape").value("block|underline|bar"),
+        Opt::long("font-size", "font size, from 6 to 72").value("SIZE"),
+        Opt::long("default", "go back to the default font and cursor"),
+    ],
+    footer: "",
+    version: None,
+};
+
+fn on_off(on: bool) -> &'static str {
+    if on {
+        "on"
+    } else {
+        "off"
+    }
+}
+
+pub fn setterm(p: &Proc) -> i32 {
+    let args = match p.getopt(&SETTERM_SPEC) {
+        Ok(a) => a,
+        Err(st) => return st,
+    };
+    if let Some(a) = args.operands.first() {
+        p.stderr.writeln(&format!("setterm: extra operand '{}'", a));
+        p.minfo("setterm");
+        return 1;
+    }
+    let mut a = theme::current();
+    if args.opts.is_empty() {
+        p.stdout.writeln(&format!("font-size {}", a.font_size));
+        p.stdout
+            .writeln(&format!("cursor-style {}", a.cursor_style));
+        p.stdout
+            .writeln(&format!("cursor-blink {}", on_off(a.cursor_blink)));
+        return 0;
+    }
+    let mut show_cursor = None;
+    for (o, val) in &args.opts {
+        let long = o.long.unwrap();
+        let val = val.unwrap_or("");
+        let bad = || {
+            p.stderr.writeln(&format!(
+                "setterm: invalid argument '{}' for '--{}'",
+                val, long
+            ));
+            p.minfo("setterm");
+            1
+        };
+        match (long, val) {
+            ("cursor", "on" | "off") => show_cursor = Some(val == "on"),
+            ("cursor-blink", "on" | "off") => a.cursor_blink = val == "on",
+            ("cursor-style", s) if CURSORS.contains(&s) => a.cursor_style = s.to_string(),
+            ("font-size", n) => match n.parse().ok().filter(|n| FONT_SIZES.contains(n)) {
+                Some(n) => a.font_size = n,
+                None => return bad(),
+            },
+            ("default", _) => {
+                let d = theme::Appearance::default();
+                a.font_size = d.font_size;
+                a.cursor_style = d.cursor_style;
+                a.cursor_blink = d.cursor_blink;
+            }
+            _ => return bad(),
+        }
+    }
+    match show_cursor {
+        Some(true) => p.stdout.write("\x1b[?25h"),
+        Some(false) => p.stdout.write("\x1b[?25l"),
+        None => {}
+    }
+    theme::apply(a);
+    0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::process::run_test;
+
+    #[test]
+    fn settings() {
+        let _l = crate::process::shell_lock();
+        theme::apply(theme::Appearance::default());
+        assert_eq!(
+            run_test(setterm, &["--font-size=14", "--cursor-style", "bar"], None).0,
+            0
+        );
+        assert_eq!(
+            run_test(setterm, &[], None).1,
+            "font-size 14\ncursor-style bar\ncursor-blink on\n"
+        );
+        assert_eq!(
+            run_test(setterm, &["--cursor", "off", "--cursor-b", "off"], None).1,
+            "\x1b[?25l"
+        );
+        assert!(!theme::current().cursor_blink);
+        let (st, _, err) = run_test(setterm, &["--font-size", "200"], None);
+        assert_eq!(
+            (st, err.lines().next()),
+            (1, Some("setterm: invalid argument '200' for '--font-size'"))
+        );
+        assert_eq!(theme::current().font_size, 14);
+        run_test(setterm, &["--default"], None);
+        assert_eq!(theme::current(), theme::Appearance::default());
+    }
+}
@@ -0,0 +1,50 @@
+use crate::common::Spec;
+use crate::process::Proc;
+
+// sleep NUMBER[SUFFIX]...
+// Like GNU sleep, NUMBER can have a fraction and a suffix of s, m, h
+// or d, and several are added up. Ctrl-C cuts it short.
+const SLEEP_SPEC: Spec = Spec {
+    name: "sleep",
+    usage: "Usage: sleep NUMBER[SUFFIX]...
+Pause for NUMBER seconds. SUFFIX may be 's', 'm', 'h' or 'd'.",
+    opts: &[],
+    footer: "",
+    version: None,
+};
+
+pub async fn sleep(p: &Proc<'_>) -> i32 {
+    let args = match p.getopt(&SLEEP_SPEC) {
+        Ok(a) => a.operands,
+        Err(st) => return st,
+    };
+    if args.is_empty() {
+        p.stderr.writeln("sleep: missing operand");
+        p.minfo("sleep");
+        return 1;
+    }
+    let mut secs = 0.0;
+    for a in &args {
+        let (n, mul) = match a.char_indices().last() {
+            Some((i, 's')) => (&a[..i], 1.0),
+            Some((i, 'm')) => (&a[..i], 60.0),
+            Some((i, 'h')) => (&a[..i], 3600.0),
+            Some((i, 'd')) => (&a[..i], 86400.0),
+            _ => (*a, 1.0),
+        };
+        match n.parse::<f64>() {
+            Ok(v) if v >= 0.0 && v.is_finite() => secs += v * mul,
+            _ => {
+                p.stderr
+                    .writeln(&format!("sleep: invalid time interval '{}'", a));
+                p.minfo("sleep");
+                return 1;
+            }
+        }
+    }
+    if p.sh.tty.sleep((secs * 1000.0) as u64).await {
+        0
+    } else {
+        130
+    }
+}
@@ -0,0 +1,43 @@
+// stty, as far as a browser tab goes: the terminal's size is all there
+// is to it, and that follows the window.
+
+use crate::common::{Opt, Spec};
+use crate::process::Proc;
+
+const STTY_SPEC: Spec = Spec {
+    name: "stty",
+    usage: "Usage: stty [-a]
+  or:  stty size
+Print terminal line settings. The size follows the window, so it
+can't be set.",
+    opts: &[Opt::flag(
+        'a',
+        "all",
+        "print all current settings in human-readable form",
+    )],
+    footer: "Special settings:
+  size          print the number of rows and columns",
+    version: None,
+};
+
+pub fn stty(p: &Proc) -> i32 {
+    let args = match p.getopt(&STTY_SPEC) {
+        Ok(a) => a,
+        Err(st) => return st,
+    };
+    let (cols, rows) = p.sh.tty.winsize();
+    match args.operands.as_slice() {
+        ["size"] => p.stdout.writeln(&format!("{} {}", rows, cols)),
+        [] if args.has("all") => p.stdout.writeln(&format!(
+            "speed 38400 baud; rows {}; columns {}; line = 0;\nintr = ^C; eof = ^D; erase = ^?;\nicanon echo",
+            rows, cols
+        )),
+        [] => p.stdout.writeln("speed 38400 baud; line = 0;"),
+        [a, ..] => {
+            p.stderr.writeln(&format!("stty: invalid argument '{}'", a));
+            p.minfo("stty");
+            return 1;
+        }
+    }
+    0
+}
@@ -0,0 +1,180 @@
+// su and login. A console has only the one shell, so rather than starting
+// a new one, su puts the old shell aside (see Vars::push_session) and
+// exit brings it back. login starts over from scratch instead.
+
+use crate::common::{Opt, Spec};
+use crate::interp::{self, Flow};
+use crate::process::Proc;
+use crate::script;
+use crate::unix::uname::UNAME_NODENAME;
+use crate::users::{self, User};
+
+const SU_SPEC: Spec = Spec {
+    name: "su",
+    usage: "Usage: su [options] [-] [USER]
+Change the effective user ID and group ID to that of USER.
+A mere - implies -l. If USER is not given, root is assumed.",
+    opts: &[
+        Opt::flag('c', "command", "pass a single command to the shell").value("COMMAND"),
+        Opt::flag('l', "login", "make the shell a login shell"),
+    ],
+    footer: "",
+    version: None,
+};
+
+const LOGIN_HELP: &str = "Usage: login [USER]
+Begin a session on the system as USER, asking for a name if there
+isn't one. Any earlier su sessions are ended.";
+
+// becomes u, complaining if there's no home to go to
+fn switch(p: &Proc, cmd: &str, u: &User, login: bool) {
+    if let Err(e) = users::switch_to(p.sh, u, login) {
+        p.stderr.writeln(&format!("{}: warning: {}", cmd, e));
+    }
+}
+
+pub async fn su(p: &Proc<'_>) -> i32 {
+    let args = match p.getopt(&SU_SPEC) {
+        Ok(a) => a,
+        Err(st) => return st,
+    };
+    // a lone - is -l
+    let login = args.has("login") || args.operands.contains(&"-");
+    let cmd = args.value("command");
+    let name = match args
+        .operands
+        .iter()
+        .filter(|a| **a != "-")
+        .collect::<Vec<_>>()[..]
+    {
+        [] => None,
+        [n] => Some(*n),
+        [_, extra, ..] => {
+            p.stderr.writeln(&format!("su: extra operand '{}'", extra));
+            p.minfo("su");
+            return 1;
+        }
+    };
+    let name = name.unwrap_or("root");
+    let u = match users::by_name(name) {
+        Some(u) => u,
+        None => {
+            p.stderr
+                .writeln(&format!("su: user {} does not exist", name));
+            return 1;
+        }
+    };
+    match cmd {
+        // just the one command, in a subshell, so nothing sticks
+        Some(c) => {
+            let st =
+                p.sh.vars
+                    .subshell(async {
+                        switch(p, "su", &u, login);
+                        script::run_text(p.sh, p.stdout, p.stderr, c).await
+                    })
+                    .await;
+            // exit in it only ends the command, as it would a script
+            interp::take_flow(p.sh, Flow::Exit);
+            st
+        }
+        None => {
+            p.sh.vars.push_session();
+            switch(p, "su", &u, login);
+            if login {
+                script::load_rc(p.sh, p.stdout, p.stderr).await;
+            }
+            0
+        }
+    }
+}
+
+pub async fn login(p: &Proc<'_>) -> i32 {
+    let args = p.args();
+    let name = match args.as_slice() {
+        ["--help"] => {
+            p.stdout.writeln(LOGIN_HELP);
+            return 0;
+        }
+        [a] if a.starts_with('-') => {
+            p.stderr.writeln(&format!("login: invalid option '{}'", a));
+            p.minfo("login");
+            return 1;
+        }
+        [a] => a.to_string(),
+        [] => {
+            p.stdout.write(&format!("{} login: ", UNAME_NODENAME));
+            match p.read_line().await {
+                Some(l) => l.trim().to_string(),
+                None if p.sh.tty.interrupted() => return 130,
+                None => return 1,
+            }
+        }
+        [_, a, ..] => {
+            p.stderr.writeln(&format!("login: extra operand '{}'", a));
+            p.minfo("login");
+            return 1;
+        }
+    };
+    let u = match users::by_name(&name) {
+        Some(u) => u,
+        None => {
+            p.stderr.writeln("Login incorrect");
+            return 1;
+        }
+    };
+    p.sh.vars.clear_sessions();
+    switch(p, "login", &u, true);
+    script::load_rc(p.sh, p.stdout, p.stderr).await;
+    0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::process::{run_task_test, test_sh};
+    use crate::sysvars::Creds;
+
+    fn su(args: &[&str]) -> (i32, String, String) {
+        run_task_test(|p| Box::pin(super::su(p)), args, None)
+    }
+
+    #[test]
+    fn su_and_login() {
+        let _l = crate::process::shell_lock();
+        crate::vfs::mount_test_fs();
+        let sh = test_sh();
+        sh.vars.set_creds(Creds::root());
+        sh.vars.export("USER", Some("root"));
+        sh.vars.store_cwd("/".to_string());
+        let (st, out, _) = su(&["-c", "echo $USER $HOME; pwd", "-", "guest"]);
+        assert_eq!((st, out.as_str()), (0, "guest /home/guest\n/home/guest\n"));
+        // none of that stuck
+        assert_eq!(sh.vars.creds(), Creds::root());
+        assert_eq!(sh.vars.load_cwd(), "/");
+        assert_eq!(su(&["-c", "exit 3", "guest"]).0, 3);
+        assert_eq!(interp::flow(&sh), None);
+        assert_eq!(su(&["nobody"]).2, "su: user nobody does not exist\n");
+
+        assert_eq!(su(&["guest"]).0, 0);
+        assert_eq!(sh.vars.creds().uid, 1000);
+        assert_eq!(sh.vars.getenv("USER").as_deref(), Some("guest"));
+        assert_eq!(sh.vars.load_cwd(), "/");
+        assert_eq!(su(&["-"]).0, 0);
+        assert_eq!(sh.vars.creds().uid, 0);
+        // exit goes back a level at a time
+        assert!(sh.vars.pop_session());
+        assert_eq!(sh.vars.getenv("USER").as_deref(), Some("guest"));
+        assert!(sh.vars.pop_session());
+        assert_eq!(sh.vars.getenv("USER").as_deref(), Some("root"));
+        assert!(!sh.vars.pop_session());
+
+        su(&["guest"]);
+        let (st, out, _) = run_task_test(|p| Box::pin(login(p)), &[], Some("root\n"));
+        assert_eq!((st, out.as_str()), (0, "amyip.net login: "));
+        assert_eq!(sh.vars.creds(), Creds::root());
+        assert!(!sh.vars.pop_session());
+        let (st, _, err) = run_task_test(|p| Box::pin(login(p)), &["nobody"], None);
+        assert_eq!((st, err.as_str()), (1, "Login incorrect\n"));
+    }
+}
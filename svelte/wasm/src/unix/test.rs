@@ -0,0 +1,244 @@
+use either::Either;
+
+use crate::process::Proc;
+use crate::sysvars::Caller;
+use crate::vfs::{perm, VirtualFileSystem};
+
+// test EXPRESSION, or [ EXPRESSION ]
+// Exits with 0 if EXPRESSION is true, 1 if it's false and 2 if it
+// couldn't be worked out. Like POSIX says, up to four arguments are
+// read by how many there are, so `[ "$x" = -n ]` does the right thing
+// whatever $x is; anything longer is parsed with ! -a -o and ( ).
+pub fn test(p: &Proc) -> i32 {
+    run(p, "test", &p.args())
+}
+
+pub fn bracket(p: &Proc) -> i32 {
+    let args = p.args();
+    match args.split_last() {
+        Some((&"]", rest)) => run(p, "[", rest),
+        _ => {
+            p.stderr.writeln("[: missing `]'");
+            2
+        }
+    }
+}
+
+fn run(p: &Proc, name: &str, args: &[&str]) -> i32 {
+    match eval(&p.sh.caller(), args) {
+        Ok(true) => 0,
+        Ok(false) => 1,
+        Err(e) => {
+            p.stderr.writeln(&format!("{}: {}", name, e));
+            2
+        }
+    }
+}
+
+fn is_unary(op: &str) -> bool {
+    matches!(
+        op,
+        "-n" | "-z" | "-e" | "-f" | "-d" | "-r" | "-w" | "-x" | "-s" | "-L" | "-h"
+    )
+}
+
+fn is_binary(op: &str) -> bool {
+    matches!(
+        op,
+        "=" | "==" | "!=" | "<" | ">" | "-eq" | "-ne" | "-lt" | "-le" | "-gt" | "-ge"
+    )
+}
+
+fn int(s: &str) -> Result<i64, String> {
+    s.trim()
+        .parse::<i64>()
+        .map_err(|_| format!("{}: integer expression expected", s))
+}
+
+// the mode bits, size and owners of a file, if there is one
+fn stat(c: &Caller, path: &str) -> Option<(u16, u64, u32, u32)> {
+    match crate::vfs::futils::find_file(c, path.to_string(), false) {
+        Either::Left((fs, fd)) => Some((
+            fs.file_perms(&fd)?,
+            fs.file_size(&fd).unwrap_or(0),
+            fs.file_owner(&fd)?,
+            fs.file_group(&fd)?,
+        )),
+        Either::Right(_) => None,
+    }
+}
+
+fn unary(c: &Caller, op: &str, a: &str) -> bool {
+    if op == "-n" {
+        return !a.is_empty();
+    }
+    if op == "-z" {
+        return a.is_empty();
+    }
+    let (mode, size, uid, gid) = match stat(c, a) {
+        Some(s) => s,
+        None => return false,
+    };
+    match op {
+        "-f" => mode & 0xf000 == 0,
+        "-d" => mode & 0xf000 == 0x1000,
+        "-L" | "-h" => mode & 0xf000 == 0x2000,
+        "-s" => size > 0,
+        "-r" | "-w" | "-x" => {
+            let want = match op {
+                "-r" => perm::R,
+                "-w" => perm::W,
+                _ => perm::X,
+            };
+            perm::allowed(&c.creds, mode, uid, gid, want)
+        }
+        // -e
+        _ => true,
+    }
+}
+
+fn binary(a: &str, op: &str, b: &str) -> Result<bool, String> {
+    Ok(match op {
+        "=" | "==" => a == b,
+        "!=" => a != b,
+        "<" => a < b,
+        ">" => a > b,
+        "-eq" => int(a)? == int(b)?,
+        "-ne" => int(a)? != int(b)?,
+        "-lt" => int(a)? < int(b)?,
+        "-le" => int(a)? <= int(b)?,
+        "-gt" => int(a)? > int(b)?,
+        _ => int(a)? >= int(b)?,
+    })
+}
+
+fn eval(c: &Caller, a: &[&str]) -> Result<bool, String> {
+    match a {
+        [] => Ok(false),
+        [x] => Ok(!x.is_empty()),
+        ["!", x] => Ok(x.is_empty()),
+        [op, x] if is_unary(op) => Ok(unary(c, op, x)),
+        [op, _] => Err(format!("{}: unary operator expected", op)),
+        [x, op, y] if is_binary(op) => binary(x, op, y),
+        [x, "-a", y] => Ok(!x.is_empty() && !y.is_empty()),
+        [x, "-o", y] => Ok(!x.is_empty() || !y.is_empty()),
+        ["!", rest @ ..] if a.len() <= 4 => Ok(!eval(c, rest)?),
+        ["(", x, ")"] => Ok(!x.is_empty()),
+        ["(", x, y, ")"] => eval(c, &[x, y]),
+        _ => {
+            let mut e = Expr { c, a, i: 0 };
+            let r = e.or()?;
+            match e.a.get(e.i) {
+                None => Ok(r),
+                Some(x) => Err(format!("{}: unexpected argument", x)),
+            }
+        }
+    }
+}
+
+// the general case, for more than four arguments
+struct Expr<'a> {
+    c: &'a Caller,
+    a: &'a [&'a str],
+    i: usize,
+}
+
+impl<'a> Expr<'a> {
+    fn next(&mut self) -> Result<&'a str, String> {
+        let x = *self.a.get(self.i).ok_or("argument expected")?;
+        self.i += 1;
+        Ok(x)
+    }
+    fn or(&mut self) -> Result<bool, String> {
+        let mut r = self.and()?;
+        while self.a.get(self.i) == Some(&"-o") {
+            self.i += 1;
+            r |= self.and()?;
+        }
+        Ok(r)
+    }
+    fn and(&mut self) -> Result<bool, String> {
+        let mut r = self.not()?;
+        while self.a.get(self.i) == Some(&"-a") {
+            self.i += 1;
+            r &= self.not()?;
+        }
+        Ok(r)
+    }
+    fn not(&mut self) -> Result<bool, String> {
+        if self.a.get(self.i) == Some(&"!") {
+            self.i += 1;
+            return Ok(!self.not()?);
+        }
+        self.primary()
+    }
+    fn primary(&mut self) -> Result<bool, String> {
+        let x = self.next()?;
+        if x == "(" {
+            let r = self.or()?;
+            if self.next()? != ")" {
+                return Err("`)' expected".to_string());
+            }
+            return Ok(r);
+        }
+        if is_unary(x) && self.i < self.a.len() {
+            let y = self.next()?;
+            return Ok(unary(self.c, x, y));
+        }
+        match self.a.get(self.i) {
+            Some(op) if is_binary(op) => {
+                self.i += 1;
+                let y = self.next()?;
+                binary(x, op, y)
+            }
+            _ => Ok(!x.is_empty()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn t(s: &str) -> Result<bool, String> {
+        eval(
+            &Caller::root(),
+            &s.split(' ')
+                .filter(|x| !x.is_empty())
+                .collect::<Vec<&str>>(),
+        )
+    }
+
+    #[test]
+    fn expressions() {
+        assert_eq!(t(""), Ok(false));
+        assert_eq!(t("-n"), Ok(true));
+        assert_eq!(t("-z -n"), Ok(false));
+        assert_eq!(t("a = a"), Ok(true));
+        assert_eq!(t("-n = -n"), Ok(true));
+        assert_eq!(t("! a = b"), Ok(true));
+        assert_eq!(t("10 -gt 9"), Ok(true));
+        assert_eq!(t("b < a"), Ok(false));
+        assert_eq!(t("( a = a )"), Ok(true));
+        assert_eq!(t("a = b -o 1 -eq 1 -a x != y"), Ok(true));
+        assert_eq!(t("! ( a = a ) -o -z x"), Ok(false));
+        assert_eq!(
+            t("1 -eq one"),
+            Err("one: integer expression expected".to_string())
+        );
+        assert!(t("-q x").is_err());
+        assert!(t("( a = a -o b").is_err());
+    }
+
+    #[test]
+    fn brackets() {
+        let r = |a: &[&str]| crate::process::run_test(bracket, a, None);
+        assert_eq!(r(&["1", "-lt", "2", "]"]).0, 0);
+        assert_eq!(r(&["a", "]"]).0, 0);
+        assert_eq!(r(&["]"]).0, 1);
+        assert_eq!(
+            r(&["a"]),
+            (2, "".to_string(), "[: missing `]'\n".to_string())
+        );
+    }
+}
@@ -0,0 +1,79 @@
+// tput, for the handful of capabilities xterm.js has that scripts ask
+// about most: the size, and a few ways to dress up text.
+
+use crate::common::Spec;
+use crate::process::Proc;
+
+const TPUT_SPEC: Spec = Spec {
+    name: "tput",
+    usage: "Usage: tput CAPNAME...
+Print the value of each terminfo capability CAPNAME for the terminal:
+  cols, lines     the number of columns and rows
+  colors          the number of colors
+  clear           clear the screen
+  bold, rev, smul, rmul, sgr0
+                  bold, reverse, underline on and off, back to normal",
+    opts: &[],
+    footer: "",
+    version: None,
+};
+
+fn cap(p: &Proc, name: &str) -> Option<String> {
+    let (cols, rows) = p.sh.tty.winsize();
+    let s = match name {
+        "cols" => return Some(format!("{}\n", cols)),
+        "lines" => return Some(format!("{}\n", rows)),
+        "colors" => "256\n",
+        "clear" => "\x1b[H\x1b[2J",
+        "bold" => "\x1b[1m",
+        "rev" => "\x1b[7m",
+        "smul" => "\x1b[4m",
+        "rmul" => "\x1b[24m",
+        "sgr0" => "\x1b[0m",
+        _ => return None,
+    };
+    Some(s.to_string())
+}
+
+pub fn tput(p: &Proc) -> i32 {
+    let args = match p.getopt(&TPUT_SPEC) {
+        Ok(a) => a,
+        Err(st) => return st,
+    };
+    if args.operands.is_empty() {
+        p.stderr.writeln("tput: usage: tput CAPNAME...");
+        return 2;
+    }
+    for name in args.operands {
+        match cap(p, name) {
+            Some(s) => p.stdout.write(&s),
+            None => {
+                p.stderr
+                    .writeln(&format!("tput: unknown terminfo capability '{}'", name));
+                return 4;
+            }
+        }
+    }
+    0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::process::run_test;
+
+    #[test]
+    fn sizes() {
+        let _l = crate::process::shell_lock();
+        let sh = crate::process::test_sh();
+        sh.set_winsize(100, 30);
+        assert_eq!(run_test(tput, &["cols", "lines"], None).1, "100\n30\n");
+        assert_eq!(sh.vars.getenv("COLUMNS").as_deref(), Some("100"));
+        assert_eq!(
+            run_test(crate::unix::stty::stty, &["size"], None).1,
+            "30 100\n"
+        );
+        assert_eq!(run_test(tput, &["nope"], None).0, 4);
+        sh.set_winsize(80, 24);
+    }
+}
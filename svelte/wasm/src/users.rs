@@ -0,0 +1,249 @@
+// The user database: /etc/passwd and /etc/group on the rootfs. They're
+// read afresh every time, since anything might have edited them, and
+// without them (on the initfs, say) there's only root.
+//
+// There's no /etc/shadow and nothing worth guarding, so the password
+// field is ignored and anyone may become anyone.
+
+use crate::script::{file_perms, is_dir};
+use crate::session::Session;
+use crate::sysvars::{Caller, Creds};
+use crate::vfs::futils::read_to_end;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct User {
+    pub name: String,
+    pub uid: u32,
+    pub gid: u32,
+    pub gecos: String,
+    pub home: String,
+    pub shell: String,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Group {
+    pub name: String,
+    pub gid: u32,
+    pub members: Vec<String>,
+}
+
+// what there is when /etc/passwd doesn't say
+fn root() -> User {
+    User {
+        name: "root".to_string(),
+        uid: 0,
+        gid: 0,
+        gecos: "root".to_string(),
+        home: "/".to_string(),
+        shell: "/bin/irun".to_string(),
+    }
+}
+
+// name:password:uid:gid:gecos:home:shell, a line each; lines that
+// don't look like that are skipped
+fn parse_passwd(txt: &str) -> Vec<User> {
+    txt.lines()
+        .filter_map(|l| {
+            let f: Vec<&str> = l.split(':').collect();
+            if f.len() != 7 || f[0].is_empty() {
+                return None;
+            }
+            Some(User {
+                name: f[0].to_string(),
+                uid: f[2].parse().ok()?,
+                gid: f[3].parse().ok()?,
+                gecos: f[4].to_string(),
+                home: f[5].to_string(),
+                shell: f[6].to_string(),
+            })
+        })
+        .collect()
+}
+
+// name:password:gid:member,member,...
+fn parse_group(txt: &str) -> Vec<Group> {
+    txt.lines()
+        .filter_map(|l| {
+            let f: Vec<&str> = l.split(':').collect();
+            if f.len() != 4 || f[0].is_empty() {
+                return None;
+            }
+            Some(Group {
+                name: f[0].to_string(),
+                gid: f[2].parse().ok()?,
+                members: f[3]
+                    .split(',')
+                    .filter(|m| !m.is_empty())
+                    .map(|m| m.to_string())
+                    .collect(),
+            })
+        })
+        .collect()
+}
+
+// read as root, whoever is asking, since ls and id need names for
+// everyone's ids
+fn read_etc(name: &str) -> String {
+    read_to_end(&Caller::root(), format!("/etc/{}", name))
+        .map(|t| String::from_utf8_lossy(&t).into_owned())
+        .unwrap_or_default()
+}
+
+pub fn users() -> Vec<User> {
+    let mut us = parse_passwd(&read_etc("passwd"));
+    if !us.iter().any(|u| u.uid == 0) {
+        us.insert(0, root());
+    }
+    us
+}
+
+pub fn groups() -> Vec<Group> {
+    let mut gs = parse_group(&read_etc("group"));
+    if !gs.iter().any(|g| g.gid == 0) {
+        gs.insert(
+            0,
+            Group {
+                name: "root".to_string(),
+                gid: 0,
+                members: vec![],
+            },
+        );
+    }
+    gs
+}
+
+pub fn by_name(name: &str) -> Option<User> {
+    users().into_iter().find(|u| u.name == name)
+}
+
+pub fn by_uid(uid: u32) -> Option<User> {
+    users().into_iter().find(|u| u.uid == uid)
+}
+
+pub fn user_name(uid: u32) -> Option<String> {
+    by_uid(uid).map(|u| u.name)
+}
+
+pub fn group_name(gid: u32) -> Option<String> {
+    groups().into_iter().find(|g| g.gid == gid).map(|g| g.name)
+}
+
+// a user, or failing that a uid, as commands take them
+pub fn lookup(s: &str) -> Option<User> {
+    by_name(s).or_else(|| by_uid(s.parse().ok()?))
+}
+
+// the same, for groups
+pub fn group_lookup(s: &str) -> Option<Group> {
+    let gs = groups();
+    match gs.iter().position(|g| g.name == s) {
+        Some(i) => Some(gs[i].clone()),
+        None => gs.into_iter().find(|g| Some(g.gid) == s.parse().ok()),
+    }
+}
+
+// what someone logging in as u gets: their own group, and every group
+// that lists them as a member
+pub fn creds_of(u: &User) -> Creds {
+    let groups = groups()
+        .into_iter()
+        .filter(|g| g.gid != u.gid && g.members.contains(&u.name))
+        .map(|g| g.gid)
+        .collect();
+    Creds {
+        uid: u.uid,
+        gid: u.gid,
+        groups,
+    }
+}
+
+// "0(root)", or just the number if it has no name
+pub fn show_id(id: u32, name: Option<String>) -> String {
+    match name {
+        Some(n) => format!("{}({})", id, n),
+        None => id.to_string(),
+    }
+}
+
+// Switches the shell over to u. A login shell also starts out in u's
+// home directory; if there isn't one, it's / instead.
+pub fn switch_to(sh: &Session, u: &User, login: bool) -> Result<(), String> {
+    sh.vars.set_creds(creds_of(u));
+    let home = match u.home.as_str() {
+        "" => "/",
+        h => h,
+    };
+    for (k, v) in [
+        ("USER", u.name.as_str()),
+        ("LOGNAME", u.name.as_str()),
+        ("HOME", home),
+        ("SHELL", u.shell.as_str()),
+    ] {
+        sh.vars.export(k, Some(v));
+    }
+    if !login {
+        return Ok(());
+    }
+    if !file_perms(&sh.caller(), home).is_some_and(is_dir) {
+        sh.vars.export("HOME", Some("/"));
+        sh.vars.store_cwd("/".to_string());
+        return Err(format!("No directory {}, logging in with HOME=/", home));
+    }
+    let mut cwd = home.to_string();
+    if !cwd.ends_with('/') {
+        cwd.push('/');
+    }
+    sh.vars.store_cwd(cwd);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parsing() {
+        let us = parse_passwd(
+            "root:x:0:0:root:/:/bin/irun\n\
+             # not a user\n\
+             amy:x:1000:100:Amy:/home/amy:/bin/irun\n\
+             bad:x:no:0:::\n",
+        );
+        assert_eq!(us.len(), 2);
+        assert_eq!(
+            (
+                us[1].name.as_str(),
+                us[1].uid,
+                us[1].gid,
+                us[1].home.as_str()
+            ),
+            ("amy", 1000, 100, "/home/amy")
+        );
+        let gs = parse_group("root:x:0:\nwheel:x:10:amy,guest\n:x:5:\n");
+        assert_eq!(gs.len(), 2);
+        assert_eq!(gs[0].members, Vec::<String>::new());
+        assert_eq!(gs[1].members, ["amy", "guest"]);
+    }
+
+    #[test]
+    fn from_the_rootfs() {
+        let _l = crate::process::shell_lock();
+        crate::vfs::mount_test_fs();
+        let guest = by_name("guest").unwrap();
+        assert_eq!(guest.uid, 1000);
+        assert_eq!(lookup("1000"), Some(guest.clone()));
+        assert_eq!(user_name(0).as_deref(), Some("root"));
+        assert_eq!(group_name(10).as_deref(), Some("wheel"));
+        assert_eq!(group_lookup("10").map(|g| g.name).as_deref(), Some("wheel"));
+        assert_eq!(group_lookup("wheel").map(|g| g.gid), Some(10));
+        assert_eq!(
+            creds_of(&guest),
+            Creds {
+                uid: 1000,
+                gid: 1000,
+                groups: vec![10],
+            }
+        );
+        assert_eq!(show_id(4242, user_name(4242)), "4242");
+    }
+}
@@ -0,0 +1,209 @@
+// devfs: a flat directory of character devices, each of them just a
+// function to read it and one to write to it. What the devices are is
+// up to whoever mounts it; irun mounts /dev/kmsg and /dev/null.
+// Reads see the whole of what the device has at that moment, so
+// nothing ever blocks.
+
+use crate::vfs::*;
+
+pub struct Device {
+    pub name: &'static str,
+    // permission bits; every device is root's
+    pub mode: u16,
+    pub read: fn() -> Vec<u8>,
+    pub write: fn(&[u8]),
+}
+
+// inode 1 is the directory, then one for each device, in order
+pub struct FileSystem {
+    devs: Vec<Device>,
+}
+impl FileSystem {
+    pub fn new(devs: Vec<Device>) -> Self {
+        Self { devs }
+    }
+    fn dev(&self, inode: u32) -> Option<&Device> {
+        self.devs.get((inode as usize).checked_sub(2)?)
+    }
+}
+
+impl VirtualFileSystem for FileSystem {
+    fn get_fd(&self, inode: u32, _fd: u32) -> Option<Box<dyn VirtualFileDescriptor>> {
+        if inode != 1 && self.dev(inode).is_none() {
+            return None;
+        }
+        Some(Box::new(FileDescriptor { inode, pos: 0 }))
+    }
+    fn delete_file(&mut self, _inode: u32, _dir_inode: u32) -> VfsResult {
+        Err(VfsErrno::EINVFD)
+    }
+    fn create_file(
+        &mut self,
+        _dir_inode: u32,
+        _filename: String,
+        _data: &[u8],
+    ) -> Result<u32, VfsErrno> {
+        Err(VfsErrno::EINVFD)
+    }
+    fn create_directory(&mut self, _parent_inode: u32, _name: String) -> Result<u32, VfsErrno> {
+        Err(VfsErrno::EINVFD)
+    }
+    fn hardlink(&mut self, _parent_inode: u32, _deploy_inode: u32, _name: String) -> VfsResult {
+        Err(VfsErrno::EINVFD)
+    }
+    fn rewind_zero(&mut self, fd: &mut Box<dyn VirtualFileDescriptor>) -> VfsResult {
+        fd.set_pos_raw(0)
+    }
+    fn rewind(&mut self, fd: &mut Box<dyn VirtualFileDescriptor>, count: u64) -> VfsResult {
+        let p = fd.get_pos().checked_sub(count).ok_or(VfsErrno::EFPOOB)?;
+        fd.set_pos_raw(p)
+    }
+    fn seek_forward(&mut self, fd: &mut Box<dyn VirtualFileDescriptor>, count: u64) -> VfsResult {
+        let p = fd.get_pos() + count;
+        fd.set_pos_raw(p)
+    }
+    fn seek(&mut self, fd: &mut Box<dyn VirtualFileDescriptor>, location: u64) -> VfsResult {
+        fd.set_pos_raw(location)
+    }
+    fn read_n(&mut self, fd: &mut Box<dyn VirtualFileDescriptor>, count: u64) -> Option<Vec<u8>> {
+        let data = (self.dev(fd.get_inum())?.read)();
+        let start = std::cmp::min(fd.get_pos() as usize, data.len());
+        let end = std::cmp::min(start + count as usize, data.len());
+        fd.set_pos_raw(end as u64).ok()?;
+        Some(data[start..end].to_vec())
+    }
+    fn read_to_eof(&mut self, fd: &mut Box<dyn VirtualFileDescriptor>) -> Option<Vec<u8>> {
+        self.read_n(fd, u32::MAX as u64)
+    }
+    fn write_in_place(&mut self, fd: &mut Box<dyn VirtualFileDescriptor>, buf: &[u8]) -> VfsResult {
+        self.append(fd, buf)
+    }
+    // there's nowhere to write over, so every write goes to the device
+    fn overwrite(&mut self, fd: &mut Box<dyn VirtualFileDescriptor>, buf: &[u8]) -> VfsResult {
+        self.append(fd, buf)
+    }
+    fn append(&mut self, fd: &mut Box<dyn VirtualFileDescriptor>, buf: &[u8]) -> VfsResult {
+        let d = self.dev(fd.get_inum()).ok_or(VfsErrno::EINVFD)?;
+        (d.write)(buf);
+        Ok(())
+    }
+    fn vfd_as_dentry(
+        &mut self,
+        fd: &Box<dyn VirtualFileDescriptor>,
+    ) -> Option<Box<dyn VirtualDentry>> {
+        if fd.get_inum() != 1 {
+            return None;
+        }
+        let mut entries = vec![
+            VirtualDentryEntry {
+                inum: 1,
+                filename: ".".to_string(),
+            },
+            VirtualDentryEntry {
+                inum: 1,
+                filename: "..".to_string(),
+            },
+        ];
+        for (i, d) in self.devs.iter().enumerate() {
+            entries.push(VirtualDentryEntry {
+                inum: i as u32 + 2,
+                filename: d.name.to_string(),
+            });
+        }
+        Some(Box::new(Dentry { entries }))
+    }
+    fn file_perms(&self, fd: &Box<dyn VirtualFileDescriptor>) -> Option<u16> {
+        match fd.get_inum() {
+            1 => Some(0x1000 + 0o755),
+            i => self.dev(i).map(|d| d.mode),
+        }
+    }
+    fn file_owner(&self, _fd: &Box<dyn VirtualFileDescriptor>) -> Option<u32> {
+        Some(0)
+    }
+    fn file_group(&self, _fd: &Box<dyn VirtualFileDescriptor>) -> Option<u32> {
+        Some(0)
+    }
+    // like on Linux, devices don't have a size
+    fn file_size(&self, _fd: &Box<dyn VirtualFileDescriptor>) -> Option<u64> {
+        Some(0)
+    }
+    fn file_modified(&self, _fd: &Box<dyn VirtualFileDescriptor>) -> Option<u64> {
+        Some(0)
+    }
+    fn file_hardlinks(&self, fd: &Box<dyn VirtualFileDescriptor>) -> Option<u16> {
+        match fd.get_inum() {
+            1 => Some(2),
+            _ => Some(1),
+        }
+    }
+    fn chmod(&mut self, _fd: &Box<dyn VirtualFileDescriptor>, _perms: u16) -> VfsResult {
+        Err(VfsErrno::EINVFD)
+    }
+    fn chown(&mut self, _fd: &Box<dyn VirtualFileDescriptor>, _uid: u32, _gid: u32) -> VfsResult {
+        Err(VfsErrno::EINVFD)
+    }
+}
+
+struct FileDescriptor {
+    inode: u32,
+    pos: u64,
+}
+impl VirtualFileDescriptor for FileDescriptor {
+    fn get_inum(&self) -> u32 {
+        self.inode
+    }
+    fn get_pos(&self) -> u64 {
+        self.pos
+    }
+    fn set_pos_raw(&mut self, pos: u64) -> VfsResult {
+        self.pos = pos;
+        Ok(())
+    }
+}
+
+struct Dentry {
+    entries: Vec<VirtualDentryEntry>,
+}
+impl VirtualDentry for Dentry {
+    fn get_entries(&self) -> Vec<VirtualDentryEntry> {
+        self.entries
+            .iter()
+            .map(|e| VirtualDentryEntry {
+                inum: e.inum,
+                filename: e.filename.clone(),
+            })
+            .collect()
+    }
+    fn get_inode(&self) -> u32 {
+        1
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn modes() {
+        let mut fs = FileSystem::new(vec![
+            Device {
+                name: "kmsg",
+                mode: 0o644,
+                read: Vec::new,
+                write: |_| {},
+            },
+            Device {
+                name: "null",
+                mode: 0o666,
+                read: Vec::new,
+                write: |_| {},
+            },
+        ]);
+        let perms = |fs: &mut FileSystem, i| fs.file_perms(&fs.get_fd(i, 0).unwrap());
+        assert_eq!(perms(&mut fs, 1), Some(0x1000 + 0o755));
+        assert_eq!(perms(&mut fs, 2), Some(0o644));
+        assert_eq!(perms(&mut fs, 3), Some(0o666));
+        assert!(fs.get_fd(4, 0).is_none());
+    }
+}
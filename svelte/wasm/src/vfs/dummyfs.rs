@@ -20,11 +20,16 @@ impl VirtualFileSystem for FileSystem {
     fn delete_file(&mut self, inode: u32, dir_inode: u32) -> VfsResult {
         Err(VfsErrno::EINVFD)
     }
-    fn create_file(&mut self, dir_inode: u32, filename: String, data: &[u8]) -> Option<u32> {
-        None
+    fn create_file(
+        &mut self,
+        dir_inode: u32,
+        filename: String,
+        data: &[u8],
+    ) -> Result<u32, VfsErrno> {
+        Err(VfsErrno::EINVFD)
     }
-    fn create_directory(&mut self, parent_inode: u32, name: String) -> Option<u32> {
-        None
+    fn create_directory(&mut self, parent_inode: u32, name: String) -> Result<u32, VfsErrno> {
+        Err(VfsErrno::EINVFD)
     }
     fn hardlink(&mut self, parent_inode: u32, deploy_inode: u32, name: String) -> VfsResult {
         Err(VfsErrno::EINVFD)
@@ -123,6 +128,9 @@ impl VirtualFileSystem for FileSystem {
     fn chmod(&mut self, fd: &Box<dyn VirtualFileDescriptor>, perms: u16) -> VfsResult {
         Err(VfsErrno::EINVFD)
     }
+    fn chown(&mut self, fd: &Box<dyn VirtualFileDescriptor>, uid: u32, gid: u32) -> VfsResult {
+        Err(VfsErrno::EINVFD)
+    }
 }
 
 struct FileDescriptor {
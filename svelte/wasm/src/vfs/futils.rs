@@ -1,27 +1,72 @@
-use crate::vfs::VirtualFileSystem;
+// Everything here is done for a Caller: relative paths start from
+// their cwd, and the fs that's found checks what they do with it.
+
+use crate::sysvars::Caller;
+use crate::vfs::{perm, VfsErrno, VirtualFileSystem};
 use either::Either;
 
+// a file, and the fs it's on
+type Found<'a> = (
+    perm::Checked<'a>,
+    Box<dyn crate::vfs::VirtualFileDescriptor>,
+);
+
 // assumes fs is mounted
 // TODO: factor out further (read?)
-pub fn read_to_end(mut path: String) -> Option<Vec<u8>> {
-    let mut r = match find_file(path, false) {
-        Either::Left(v) => v,
-        Either::Right(_) => return None,
+pub fn read_to_end(c: &Caller, path: String) -> Option<Vec<u8>> {
+    read_file(c, path).ok()
+}
+
+// the whole of a file, if it's there and may be read
+pub fn read_file(c: &Caller, path: String) -> Result<Vec<u8>, VfsErrno> {
+    let (mut fs, mut fd) = resolve(c, path)?;
+    fs.access(&fd, perm::R)?;
+    fs.read_to_eof(&mut fd).ok_or(VfsErrno::EINVFD)
+}
+
+// Writes data to the file at path, replacing what was there or adding
+// to the end of it. The file is created if it doesn't exist yet.
+pub fn write_file(c: &Caller, path: &str, data: &[u8], append: bool) -> Result<(), String> {
+    match resolve(c, path.to_string()) {
+        Ok((mut fs, mut fd)) => {
+            if fs.file_perms(&fd).unwrap_or(0) & 0xf000 == 0x1000 {
+                return Err("Is a directory".to_string());
+            }
+            let r = if append {
+                fs.append(&mut fd, data)
+            } else {
+                fs.overwrite(&mut fd, data)
+            };
+            return r.map_err(|e| e.errno().to_string());
+        }
+        Err(VfsErrno::ENOENT) => {}
+        Err(e) => return Err(e.errno().to_string()),
+    }
+    let (dir, name) = match path.rsplit_once('/') {
+        Some(("", n)) => ("/", n),
+        Some((d, n)) => (d, n),
+        None => (".", path),
     };
-    r.0.read_to_eof(&mut r.1)
+    let (mut fs, fd) = resolve(c, dir.to_string()).map_err(|e| e.errno().to_string())?;
+    match fs.create_file(fd.get_inum(), name.to_string(), data) {
+        Ok(_) => Ok(()),
+        Err(VfsErrno::EACCES) => Err(VfsErrno::EACCES.errno().to_string()),
+        Err(_) => Err("cannot create file".to_string()),
+    }
 }
 
 // TODO: follow_links option for cat, others
-pub fn find_file<'a>(
-    mut path: String,
-    short: bool,
-) -> Either<
-    (
-        &'a mut Box<dyn crate::vfs::VirtualFileSystem>,
-        Box<dyn crate::vfs::VirtualFileDescriptor>,
-    ),
-    Option<Vec<u8>>,
-> {
+pub fn find_file<'a>(c: &Caller, path: String, short: bool) -> Either<Found<'a>, Option<Vec<u8>>> {
+    match resolve(c, path) {
+        Ok((_, fd)) if short => Either::Right(Some(Vec::from(fd.get_inum().to_le_bytes()))),
+        Ok(r) => Either::Left(r),
+        Err(_) => Either::Right(None),
+    }
+}
+
+// Looks a path up, failing with ENOENT if it isn't there, or EACCES
+// if a directory on the way can't be searched.
+pub fn resolve<'a>(c: &Caller, mut path: String) -> Result<Found<'a>, VfsErrno> {
     // prepare destination string
     if path.ends_with("/") {
         path.push('.');
@@ -32,10 +77,12 @@ pub fn find_file<'a>(
         path = m.collect::<String>();
     } else {
         // presumes CWD ends in slash
-        path = format!("{}{}", crate::sysvars::load_cwd(), path);
+        path = format!("{}{}", c.cwd, path);
     }
     let fsw = crate::vfs::safe_wrap_fdfs(path.to_string());
-    let mut vdent = fsw.0.vfd_as_dentry(&fsw.0.get_fd(1, 0).unwrap()).unwrap();
+    // the rest of the path, from the root of the fs it's on
+    path = fsw.1;
+    let mut dir = fsw.0.get_fd(1, 0).unwrap();
     let mut target: u32;
     'bb: loop {
         // set into vdent?
@@ -44,6 +91,9 @@ pub fn find_file<'a>(
             path = rem[1].clone();
             continue 'bb;
         }
+        // looking in a directory takes search permission on it
+        perm::access(&c.creds, &**fsw.0, &dir, perm::X)?;
+        let vdent = fsw.0.vfd_as_dentry(&dir).unwrap();
         //if rem.len() == 1 {
         // file MUST be in current directory, or DNE
         for ent in vdent.get_entries() {
@@ -55,19 +105,65 @@ pub fn find_file<'a>(
                     break 'bb;
                 } else {
                     // subdir found
-                    let fd = fsw.0.get_fd(ent.inum, 0).unwrap();
-                    vdent = fsw.0.vfd_as_dentry(&fd).unwrap();
+                    dir = fsw.0.get_fd(ent.inum, 0).unwrap();
+                    if fsw.0.vfd_as_dentry(&dir).is_none() {
+                        return Err(VfsErrno::ENOENT);
+                    }
                     path = rem[1].clone();
                     continue 'bb;
                 }
             }
         }
         // can't go any further
-        return Either::Right(None);
-    }
-    if short {
-        return Either::Right(Some(Vec::from(target.to_le_bytes())));
+        return Err(VfsErrno::ENOENT);
     }
     let fd = fsw.0.get_fd(target, 0).unwrap();
-    Either::Left((fsw.0, fd))
+    Ok((perm::Checked::new(fsw.0, c), fd))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sysvars::Creds;
+
+    #[test]
+    fn denied() {
+        let _l = crate::process::shell_lock();
+        crate::vfs::mount_test_fs();
+        let guest = Caller {
+            creds: Creds {
+                uid: 1000,
+                gid: 1000,
+                groups: vec![],
+            },
+            ..Caller::root()
+        };
+        let root = Caller::root();
+        assert!(matches!(
+            resolve(&guest, "/private/secret".to_string()).err(),
+            Some(VfsErrno::EACCES)
+        ));
+        assert!(resolve(&guest, "/private".to_string()).is_ok());
+        assert!(read_file(&guest, "/etc/passwd".to_string()).is_ok());
+        assert_eq!(
+            write_file(&guest, "/etc/passwd", b"", false),
+            Err("Permission denied".to_string())
+        );
+        assert_eq!(
+            write_file(&guest, "/etc/new", b"", false),
+            Err("Permission denied".to_string())
+        );
+        assert!(resolve(&root, "/private/secret".to_string()).is_ok());
+        assert!(matches!(
+            resolve(&root, "/private/nope".to_string()).err(),
+            Some(VfsErrno::ENOENT)
+        ));
+        // relative to the caller's cwd
+        let in_etc = Caller {
+            cwd: "/etc/".to_string(),
+            ..Caller::root()
+        };
+        assert!(resolve(&in_etc, "passwd".to_string()).is_ok());
+        assert!(resolve(&root, "passwd".to_string()).is_err());
+    }
 }
@@ -16,7 +16,6 @@
 
 use crate::vfs;
 use crate::vfs::VirtualFileSystem;
-use std::sync::atomic::Ordering;
 
 // Box<[u8]> is cool, but Vec<u8> ends up being necessary
 // because on fs creation, [u8] is unsized
@@ -300,7 +299,7 @@ impl FileSystem {
         let sbi = sb % 8;
         let eby = eb >> 3;
         let ebi = eb % 8;
-        if sby - eby > 1 {
+        if eby - sby > 1 {
             for n in sby + 1..eby {
                 self.data_use_table[n as usize] &= 0x0;
             }
@@ -581,7 +580,12 @@ impl vfs::VirtualFileSystem for FileSystem {
         Ok(())
     }
     // todo: explicit typing
-    fn create_file(&mut self, dir_inode: u32, filename: String, data: &[u8]) -> Option<u32> {
+    fn create_file(
+        &mut self,
+        dir_inode: u32,
+        filename: String,
+        data: &[u8],
+    ) -> Result<u32, vfs::VfsErrno> {
         let mut dentry = Dentry::from_internal(dir_inode, &self).unwrap();
         // uniqueness check
         for e in &dentry.intern {
@@ -589,19 +593,19 @@ impl vfs::VirtualFileSystem for FileSystem {
                 continue;
             }
             if crate::common::bytes_to_string(&e.filename_cstr) == filename {
-                return None;
+                return Err(vfs::VfsErrno::EALREX);
             }
         }
         let _file_inode = self.alloc_inode();
         if _file_inode.is_none() {
-            return None;
+            return Err(vfs::VfsErrno::ENSTOR);
         }
         let file_inode: usize = _file_inode.unwrap() as usize;
         let bc = crate::common::fastceildiv(data.len() as u64, self.sup.data_block_size as u64);
         let _first_block = self.alloc_data(bc);
         if _first_block.is_none() && bc != 0 {
             self.clear_inode(file_inode as u32).unwrap();
-            return None;
+            return Err(vfs::VfsErrno::ENSTOR);
         }
         // TODO: factor out self.inodes[file_inode]
         let fb = _first_block.unwrap_or(1);
@@ -611,9 +615,9 @@ impl vfs::VirtualFileSystem for FileSystem {
         self.inodes[file_inode].first_block = fb;
         self.inodes[file_inode].end_block = fb + bc - 1;
         self.inodes[file_inode].total_file_size = data.len() as u64;
-        self.inodes[file_inode].perms = 0o666 & !crate::sysvars::UMASK.load(Ordering::Relaxed);
-        // for now we're presuming the user is root
-        // TODO: update vfs to allow fs to set/check uid, gid, perms
+        // root's, as with umask 022; perm::Checked gives them to
+        // whoever is making them
+        self.inodes[file_inode].perms = 0o644;
         self.inodes[file_inode].uid = 0;
         self.inodes[file_inode].gid = 0;
         self.inodes[file_inode].hard_link_count = 1;
@@ -630,22 +634,22 @@ impl vfs::VirtualFileSystem for FileSystem {
             filename_cstr: mv,
         });
         dentry.write_back(self, false);
-        Some(file_inode as u32)
+        Ok(file_inode as u32)
     }
-    fn create_directory(&mut self, parent_inode: u32, name: String) -> Option<u32> {
+    fn create_directory(&mut self, parent_inode: u32, name: String) -> Result<u32, vfs::VfsErrno> {
         if !self.check_inode(parent_inode) {
-            return None;
+            return Err(vfs::VfsErrno::EINVFD);
         }
         let _nino = self.alloc_inode();
         if _nino.is_none() {
-            return None;
+            return Err(vfs::VfsErrno::ENSTOR);
         }
         let nino: usize = _nino.unwrap() as usize;
         self.inodes[nino].num = nino as u32;
         let mut pdent = Dentry::from_internal(parent_inode, &self).unwrap();
         self.inodes[nino].uid = 0;
         self.inodes[nino].gid = 0;
-        self.inodes[nino].perms = 0o10777 & !crate::sysvars::UMASK.load(Ordering::Relaxed);
+        self.inodes[nino].perms = 0o10755;
         self.inodes[nino].hard_link_count = 1;
         self.inodes[nino].accessed = 0;
         self.inodes[nino].modified = 0;
@@ -673,7 +677,7 @@ impl vfs::VirtualFileSystem for FileSystem {
         });
         qdent.write_back(self, true);
         pdent.write_back(self, false);
-        Some(nino as u32)
+        Ok(nino as u32)
     }
     fn hardlink(
         &mut self,
@@ -802,10 +806,11 @@ impl vfs::VirtualFileSystem for FileSystem {
         }
         let bc = crate::common::fastceildiv(buf.len() as u64, self.sup.data_block_size as u64);
         let _fb = self.alloc_data(bc);
-        if _fb.is_none() {
+        if _fb.is_none() && bc != 0 {
             return Err(vfs::VfsErrno::ENSTOR);
         }
-        let fb = _fb.unwrap();
+        // empty files point at block 1, same as in create_file
+        let fb = _fb.unwrap_or(1);
         let sp: usize = (fb * self.sup.data_block_size as u64) as usize;
         self.data[sp..sp + buf.len()].copy_from_slice(buf);
         let ino_s = &self.inodes[i as usize];
@@ -824,8 +829,16 @@ impl vfs::VirtualFileSystem for FileSystem {
         fd: &mut Box<dyn vfs::VirtualFileDescriptor>,
         buf: &[u8],
     ) -> vfs::VfsResult {
-        // CRITICAL TODO: implement this
-        unimplemented!()
+        let i: u32 = fd.get_inum();
+        if !self.check_inode(i) {
+            return Err(vfs::VfsErrno::EINVFD);
+        }
+        // copy the old contents and the new together into fresh blocks
+        let sp = self.getcpos(i, 0);
+        let mut nbuf =
+            self.data[sp..sp + self.inodes[i as usize].total_file_size as usize].to_vec();
+        nbuf.extend_from_slice(buf);
+        self.overwrite(fd, &nbuf)
     }
     // TODO: factor out self.inodes[i as usize]
     fn vfd_as_dentry(
@@ -895,6 +908,20 @@ impl vfs::VirtualFileSystem for FileSystem {
         self.inodes[i as usize].perms = perms;
         Ok(())
     }
+    fn chown(
+        &mut self,
+        fd: &Box<dyn vfs::VirtualFileDescriptor>,
+        uid: u32,
+        gid: u32,
+    ) -> vfs::VfsResult {
+        let i: u32 = fd.get_inum();
+        if !self.check_inode(i) {
+            return Err(vfs::VfsErrno::EINVFD);
+        }
+        self.inodes[i as usize].uid = uid;
+        self.inodes[i as usize].gid = gid;
+        Ok(())
+    }
 }
 
 // file system structure
@@ -902,3 +929,28 @@ impl vfs::VirtualFileSystem for FileSystem {
 // if a dentryentry refers to inode 0, that means nothing there
 // V1's magic is 0x1815f05f7470ff65
 //                 IRIS-OS-NANO--FS
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vfs::VirtualFileSystem;
+
+    #[test]
+    fn overwrite_and_append() {
+        let mut fs = FileSystem::create_test_fs();
+        let ino = fs.create_file(1, "a".to_string(), b"hello").unwrap();
+        let other = fs.create_file(1, "b".to_string(), b"other").unwrap();
+        let mut fd = fs.get_fd(ino, 0).unwrap();
+        fs.append(&mut fd, b" world").unwrap();
+        assert_eq!(fs.read_to_eof(&mut fd).unwrap(), b"hello world");
+        fs.overwrite(&mut fd, b"").unwrap();
+        assert_eq!(fs.file_size(&fd), Some(0));
+        fs.append(&mut fd, b"x").unwrap();
+        fs.append(&mut fd, &[b'y'; 5000]).unwrap();
+        // reading moved fd along, so read through a new one
+        let d = fs.read_to_eof(&mut fs.get_fd(ino, 0).unwrap()).unwrap();
+        assert_eq!((d.len(), d[0], d[5000]), (5001, b'x', b'y'));
+        let mut ofd = fs.get_fd(other, 0).unwrap();
+        assert_eq!(fs.read_to_eof(&mut ofd).unwrap(), b"other");
+    }
+}
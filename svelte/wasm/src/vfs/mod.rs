@@ -1,7 +1,9 @@
 // TODO: project-wide, change to pub(crate) where applicable
+pub mod devfs;
 pub mod dummyfs;
 pub mod futils;
 pub mod infs;
+pub mod perm;
 
 //use once_cell::sync::Lazy;
 
@@ -11,9 +13,7 @@ use std::collections::HashMap;
 // mount.root downloads the standard rootfs, mounts on /
 // mount.web downloads an alternative fs, mounts wherever
 
-// TODO: permissions checks
-
-// TODO: procfs/sysfs/devfs (base: chardevfs)
+// TODO: procfs/sysfs (base: chardevfs)
 
 // TODO: add note to splash saying to run `get-rootfs` to get the rootfs
 // TODO: tool that hooks vfs to generate filesystems outside
@@ -33,9 +33,15 @@ impl VfsTreeNode {
         match self {
             VfsTreeNode::Mounted(ref mut n) => (n, path),
             VfsTreeNode::MultiMount(ref mut n) => {
+                let path = path.trim_start_matches('/');
                 let mut rempath: Vec<String> = path.splitn(2, '/').map(|x| x.to_string()).collect();
                 if rempath.len() == 1 {
-                    rempath.insert(0, ".".to_string());
+                    // the mount point itself is the root of what's mounted there
+                    if n.contains_key(&rempath[0]) {
+                        rempath.push(".".to_string());
+                    } else {
+                        rempath.insert(0, ".".to_string());
+                    }
                 }
                 if n.contains_key(&rempath[0]) {
                     return n
@@ -62,11 +68,65 @@ static mut VFS_ROOT: VfsTreeNode = VfsTreeNode::Unmounted;
 pub fn mount_dummy() -> () {
     mount_root(Box::new(dummyfs::FileSystem {}))
 }
+// replaces the rootfs, keeping whatever is mounted on top of it
 pub fn mount_root(fs: Box<dyn VirtualFileSystem>) -> () {
-    unsafe {
-        VFS_ROOT = VfsTreeNode::Mounted(fs);
+    let root = unsafe { &mut *std::ptr::addr_of_mut!(VFS_ROOT) };
+    match root {
+        VfsTreeNode::MultiMount(m) => {
+            m.insert(".".to_string(), VfsTreeNode::Mounted(fs));
+        }
+        _ => *root = VfsTreeNode::Mounted(fs),
     }
 }
+// Mounts fs on /name. Only directly under / for now (see the BUG
+// above), and there has to be a rootfs already.
+pub fn mount(name: &str, fs: Box<dyn VirtualFileSystem>) {
+    let root = unsafe { &mut *std::ptr::addr_of_mut!(VFS_ROOT) };
+    let mut m = match std::mem::replace(root, VfsTreeNode::Unmounted) {
+        VfsTreeNode::MultiMount(m) => m,
+        r => HashMap::from([(".".to_string(), r)]),
+    };
+    m.insert(name.to_string(), VfsTreeNode::Mounted(fs));
+    *root = VfsTreeNode::MultiMount(m);
+}
+// Mounts the rootfs that tests share: an empty INFS with whatever
+// fixtures they need. Tests run in parallel, so it's only ever built
+// once and nothing should change it afterwards.
+#[cfg(test)]
+pub fn mount_test_fs() {
+    static ONCE: std::sync::Once = std::sync::Once::new();
+    ONCE.call_once(|| {
+        let mut fs = infs::FileSystem::create_test_fs();
+        let g = fs.create_directory(1, "globd".to_string()).unwrap();
+        for f in ["a.txt", "b.txt", ".hidden", "c"] {
+            fs.create_file(g, f.to_string(), b"").unwrap();
+        }
+        let d = fs.create_directory(g, "sub".to_string()).unwrap();
+        fs.create_file(d, "x".to_string(), b"").unwrap();
+        fs.create_file(d, "yy".to_string(), b"").unwrap();
+        let etc = fs.create_directory(1, "etc".to_string()).unwrap();
+        fs.create_file(
+            etc,
+            "passwd".to_string(),
+            b"root:x:0:0:root:/:/bin/irun\nguest:x:1000:1000:Guest:/home/guest:/bin/irun\n",
+        )
+        .unwrap();
+        fs.create_file(
+            etc,
+            "group".to_string(),
+            b"root:x:0:\nwheel:x:10:guest\nguest:x:1000:\n",
+        )
+        .unwrap();
+        let home = fs.create_directory(1, "home".to_string()).unwrap();
+        fs.create_directory(home, "guest".to_string()).unwrap();
+        // only root gets in here
+        let private = fs.create_directory(1, "private".to_string()).unwrap();
+        fs.create_file(private, "secret".to_string(), b"").unwrap();
+        fs.chmod(&fs.get_fd(private, 0).unwrap(), 0x1000 + 0o700)
+            .unwrap();
+        mount_root(Box::new(fs));
+    });
+}
 pub fn safe_wrap_fdfs<'a>(path: String) -> (&'a mut Box<dyn VirtualFileSystem>, String) {
     unsafe { VFS_ROOT.find_destination_fs(path) }
 }
@@ -76,6 +136,9 @@ pub enum VfsErrno {
     EFPOOB,
     ENSTOR,
     EALREX,
+    ENOENT,
+    EACCES,
+    EPERM,
 }
 impl VfsErrno {
     pub fn errno(&self) -> &str {
@@ -84,6 +147,9 @@ impl VfsErrno {
             VfsErrno::EFPOOB => "file seek went out of bounds",
             VfsErrno::ENSTOR => "not enough space on disk",
             VfsErrno::EALREX => "file already exists in directory",
+            VfsErrno::ENOENT => "No such file or directory",
+            VfsErrno::EACCES => "Permission denied",
+            VfsErrno::EPERM => "Operation not permitted",
         }
     }
 }
@@ -101,8 +167,13 @@ pub trait VirtualFileSystem {
     fn get_fd(&self, inode: u32, fd: u32) -> Option<Box<dyn VirtualFileDescriptor>>;
     fn delete_file(&mut self, inode: u32, dir_inode: u32) -> VfsResult;
     // returns the inode of the new file
-    fn create_file(&mut self, dir_inode: u32, filename: String, data: &[u8]) -> Option<u32>;
-    fn create_directory(&mut self, parent_inode: u32, name: String) -> Option<u32>;
+    fn create_file(
+        &mut self,
+        dir_inode: u32,
+        filename: String,
+        data: &[u8],
+    ) -> Result<u32, VfsErrno>;
+    fn create_directory(&mut self, parent_inode: u32, name: String) -> Result<u32, VfsErrno>;
     fn hardlink(&mut self, parent_inode: u32, deploy_inode: u32, name: String) -> VfsResult;
     fn rewind_zero(&mut self, fd: &mut Box<dyn VirtualFileDescriptor>) -> VfsResult;
     fn rewind(&mut self, fd: &mut Box<dyn VirtualFileDescriptor>, count: u64) -> VfsResult;
@@ -126,6 +197,7 @@ pub trait VirtualFileSystem {
     fn file_modified(&self, fd: &Box<dyn VirtualFileDescriptor>) -> Option<u64>;
     fn file_hardlinks(&self, fd: &Box<dyn VirtualFileDescriptor>) -> Option<u16>;
     fn chmod(&mut self, fd: &Box<dyn VirtualFileDescriptor>, perms: u16) -> VfsResult;
+    fn chown(&mut self, fd: &Box<dyn VirtualFileDescriptor>, uid: u32, gid: u32) -> VfsResult;
 }
 
 pub trait VirtualFileDescriptor {
@@ -0,0 +1,290 @@
+// Permission checks. Filesystems only keep the bits; a lookup
+// (futils::resolve) hands out the fs a file is on wrapped in a
+// Checked, which holds each operation up against them for whoever
+// asked (a sysvars::Caller) before letting it through. Path lookups
+// check search permission themselves, in futils::resolve.
+
+use crate::sysvars::{Caller, Creds};
+use crate::vfs::*;
+
+// what may be done, as in the rwx bits
+pub const R: u16 = 4;
+pub const W: u16 = 2;
+pub const X: u16 = 1;
+
+// Whether c may do everything in mode to a file with these perms and
+// owner. Root may do anything, except run what nobody may run.
+pub fn allowed(c: &Creds, perms: u16, uid: u32, gid: u32, mode: u16) -> bool {
+    if c.uid == 0 {
+        return mode & X == 0 || perms & 0o111 != 0 || perms & 0xf000 == 0x1000;
+    }
+    let bits = if c.uid == uid {
+        perms >> 6
+    } else if c.all_groups().contains(&gid) {
+        perms >> 3
+    } else {
+        perms
+    };
+    bits & mode == mode
+}
+
+// &Box, since that's what VirtualFileSystem takes
+#[allow(clippy::borrowed_box)]
+pub fn access(
+    c: &Creds,
+    fs: &dyn VirtualFileSystem,
+    fd: &Box<dyn VirtualFileDescriptor>,
+    mode: u16,
+) -> VfsResult {
+    let perms = fs.file_perms(fd).ok_or(VfsErrno::EINVFD)?;
+    let uid = fs.file_owner(fd).ok_or(VfsErrno::EINVFD)?;
+    let gid = fs.file_group(fd).ok_or(VfsErrno::EINVFD)?;
+    match allowed(c, perms, uid, gid, mode) {
+        true => Ok(()),
+        false => Err(VfsErrno::EACCES),
+    }
+}
+
+// a mounted fs, as seen by one caller
+pub struct Checked<'a> {
+    fs: &'a mut Box<dyn VirtualFileSystem>,
+    creds: Creds,
+    umask: u16,
+}
+impl<'a> Checked<'a> {
+    pub fn new(fs: &'a mut Box<dyn VirtualFileSystem>, c: &Caller) -> Self {
+        Self {
+            fs,
+            creds: c.creds.clone(),
+            umask: c.umask,
+        }
+    }
+    // whether the caller may do mode to fd
+    #[allow(clippy::borrowed_box)]
+    pub fn access(&self, fd: &Box<dyn VirtualFileDescriptor>, mode: u16) -> VfsResult {
+        access(&self.creds, &**self.fs, fd, mode)
+    }
+    #[allow(clippy::borrowed_box)]
+    fn check(&self, fd: &Box<dyn VirtualFileDescriptor>, mode: u16) -> VfsResult {
+        self.access(fd, mode)
+    }
+    // new files belong to whoever makes them, less their umask
+    fn own(&mut self, inode: u32, perms: u16) -> Result<u32, VfsErrno> {
+        let fd = self.fs.get_fd(inode, 0).ok_or(VfsErrno::EINVFD)?;
+        self.fs.chown(&fd, self.creds.uid, self.creds.gid)?;
+        self.fs.chmod(&fd, perms & !self.umask)?;
+        Ok(inode)
+    }
+    // adding to or taking from a directory takes writing to it and
+    // searching it
+    fn check_dir(&self, inode: u32) -> VfsResult {
+        let fd = self.fs.get_fd(inode, 0).ok_or(VfsErrno::EINVFD)?;
+        self.check(&fd, W | X)
+    }
+}
+
+impl VirtualFileSystem for Checked<'_> {
+    fn get_fd(&self, inode: u32, fd: u32) -> Option<Box<dyn VirtualFileDescriptor>> {
+        self.fs.get_fd(inode, fd)
+    }
+    fn delete_file(&mut self, inode: u32, dir_inode: u32) -> VfsResult {
+        self.check_dir(dir_inode)?;
+        self.fs.delete_file(inode, dir_inode)
+    }
+    fn create_file(
+        &mut self,
+        dir_inode: u32,
+        filename: String,
+        data: &[u8],
+    ) -> Result<u32, VfsErrno> {
+        self.check_dir(dir_inode)?;
+        let inode = self.fs.create_file(dir_inode, filename, data)?;
+        self.own(inode, 0o666)
+    }
+    fn create_directory(&mut self, parent_inode: u32, name: String) -> Result<u32, VfsErrno> {
+        self.check_dir(parent_inode)?;
+        let inode = self.fs.create_directory(parent_inode, name)?;
+        self.own(inode, 0o10777)
+    }
+    fn hardlink(&mut self, parent_inode: u32, deploy_inode: u32, name: String) -> VfsResult {
+        self.check_dir(parent_inode)?;
+        self.fs.hardlink(parent_inode, deploy_inode, name)
+    }
+    fn rewind_zero(&mut self, fd: &mut Box<dyn VirtualFileDescriptor>) -> VfsResult {
+        self.fs.rewind_zero(fd)
+    }
+    fn rewind(&mut self, fd: &mut Box<dyn VirtualFileDescriptor>, count: u64) -> VfsResult {
+        self.fs.rewind(fd, count)
+    }
+    fn seek_forward(&mut self, fd: &mut Box<dyn VirtualFileDescriptor>, count: u64) -> VfsResult {
+        self.fs.seek_forward(fd, count)
+    }
+    fn seek(&mut self, fd: &mut Box<dyn VirtualFileDescriptor>, location: u64) -> VfsResult {
+        self.fs.seek(fd, location)
+    }
+    fn read_n(&mut self, fd: &mut Box<dyn VirtualFileDescriptor>, count: u64) -> Option<Vec<u8>> {
+        self.check(fd, R).ok()?;
+        self.fs.read_n(fd, count)
+    }
+    fn read_to_eof(&mut self, fd: &mut Box<dyn VirtualFileDescriptor>) -> Option<Vec<u8>> {
+        self.check(fd, R).ok()?;
+        self.fs.read_to_eof(fd)
+    }
+    fn write_in_place(&mut self, fd: &mut Box<dyn VirtualFileDescriptor>, buf: &[u8]) -> VfsResult {
+        self.check(fd, W)?;
+        self.fs.write_in_place(fd, buf)
+    }
+    fn overwrite(&mut self, fd: &mut Box<dyn VirtualFileDescriptor>, buf: &[u8]) -> VfsResult {
+        self.check(fd, W)?;
+        self.fs.overwrite(fd, buf)
+    }
+    fn append(&mut self, fd: &mut Box<dyn VirtualFileDescriptor>, buf: &[u8]) -> VfsResult {
+        self.check(fd, W)?;
+        self.fs.append(fd, buf)
+    }
+    // listing a directory takes reading it; lookups only need search
+    // permission, and futils::resolve goes around this to do them
+    fn vfd_as_dentry(
+        &mut self,
+        fd: &Box<dyn VirtualFileDescriptor>,
+    ) -> Option<Box<dyn VirtualDentry>> {
+        self.check(fd, R).ok()?;
+        self.fs.vfd_as_dentry(fd)
+    }
+    fn file_perms(&self, fd: &Box<dyn VirtualFileDescriptor>) -> Option<u16> {
+        self.fs.file_perms(fd)
+    }
+    fn file_owner(&self, fd: &Box<dyn VirtualFileDescriptor>) -> Option<u32> {
+        self.fs.file_owner(fd)
+    }
+    fn file_group(&self, fd: &Box<dyn VirtualFileDescriptor>) -> Option<u32> {
+        self.fs.file_group(fd)
+    }
+    fn file_size(&self, fd: &Box<dyn VirtualFileDescriptor>) -> Option<u64> {
+        self.fs.file_size(fd)
+    }
+    fn file_modified(&self, fd: &Box<dyn VirtualFileDescriptor>) -> Option<u64> {
+        self.fs.file_modified(fd)
+    }
+    fn file_hardlinks(&self, fd: &Box<dyn VirtualFileDescriptor>) -> Option<u16> {
+        self.fs.file_hardlinks(fd)
+    }
+    // only the owner (or root) may change the mode
+    fn chmod(&mut self, fd: &Box<dyn VirtualFileDescriptor>, perms: u16) -> VfsResult {
+        let uid = self.creds.uid;
+        if uid != 0 && self.fs.file_owner(fd) != Some(uid) {
+            return Err(VfsErrno::EPERM);
+        }
+        self.fs.chmod(fd, perms)
+    }
+    // only root may give a file away; its owner may only move it between
+    // their own groups
+    fn chown(&mut self, fd: &Box<dyn VirtualFileDescriptor>, uid: u32, gid: u32) -> VfsResult {
+        let c = &self.creds;
+        if c.uid != 0
+            && (self.fs.file_owner(fd) != Some(c.uid)
+                || uid != c.uid
+                || !c.all_groups().contains(&gid))
+        {
+            return Err(VfsErrno::EPERM);
+        }
+        self.fs.chown(fd, uid, gid)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn modes() {
+        let root = Creds::root();
+        let amy = Creds {
+            uid: 1000,
+            gid: 100,
+            groups: vec![10],
+        };
+        // rw-r-----, amy:wheel
+        let (perms, uid, gid) = (0o640, 1000, 10);
+        assert!(allowed(&amy, perms, uid, gid, R | W));
+        assert!(!allowed(&amy, perms, uid, gid, X));
+        // her group only gets to read
+        let other = Creds {
+            uid: 1001,
+            ..amy.clone()
+        };
+        assert!(allowed(&other, perms, uid, gid, R));
+        assert!(!allowed(&other, perms, uid, gid, W));
+        let stranger = Creds {
+            uid: 1002,
+            gid: 1002,
+            groups: vec![],
+        };
+        assert!(!allowed(&stranger, perms, uid, gid, R));
+        // the owner bits win, even when the others say more
+        assert!(!allowed(&amy, 0o077, uid, gid, R));
+        assert!(allowed(&root, 0, uid, gid, R | W));
+        assert!(!allowed(&root, 0o644, uid, gid, X));
+        assert!(allowed(&root, 0o100, uid, gid, X));
+        assert!(allowed(&root, 0x1000, uid, gid, X));
+    }
+
+    #[test]
+    fn checked() {
+        let mut raw: Box<dyn VirtualFileSystem> =
+            Box::new(crate::vfs::infs::FileSystem::create_test_fs());
+        let root = Caller::root();
+        let guest = Caller {
+            creds: Creds {
+                uid: 1000,
+                gid: 1000,
+                groups: vec![],
+            },
+            umask: 0o027,
+            cwd: "/".to_string(),
+        };
+        let mut fs = Checked::new(&mut raw, &root);
+        let f = fs.create_file(1, "f".to_string(), b"hi").unwrap();
+        let mut fd = fs.get_fd(f, 0).unwrap();
+        fs.chmod(&fd, 0o600).unwrap();
+        let d = fs.create_directory(1, "pub".to_string()).unwrap();
+        fs.chmod(&fs.get_fd(d, 0).unwrap(), 0o10777).unwrap();
+        let mut fs = Checked::new(&mut raw, &guest);
+        // / is root's, rwxr-xr-x
+        let denied = [
+            fs.create_file(1, "g".to_string(), b"").err(),
+            fs.create_directory(1, "d".to_string()).err(),
+            fs.append(&mut fd, b"!").err(),
+            fs.delete_file(f, 1).err(),
+            fs.chmod(&fd, 0o666).err(),
+            fs.chown(&fd, 1000, 1000).err(),
+        ];
+        let read = fs.read_to_eof(&mut fd);
+        let private = fs.create_directory(d, "p".to_string()).unwrap();
+        let pd = fs.get_fd(private, 0).unwrap();
+        fs.chmod(&pd, 0o10300).unwrap();
+        let listed = fs.vfd_as_dentry(&pd).is_some();
+        // what they make is theirs, less their umask
+        let g = fs.create_file(d, "g".to_string(), b"").unwrap();
+        let gd = fs.get_fd(g, 0).unwrap();
+        let made = (fs.file_owner(&gd), fs.file_group(&gd), fs.file_perms(&gd));
+        assert_eq!(
+            denied.map(|e| e.map(|e| e.errno().to_string())),
+            [
+                Some("Permission denied".to_string()),
+                Some("Permission denied".to_string()),
+                Some("Permission denied".to_string()),
+                Some("Permission denied".to_string()),
+                Some("Operation not permitted".to_string()),
+                Some("Operation not permitted".to_string()),
+            ]
+        );
+        assert_eq!(read, None);
+        // they can't list their own write-only directory, and root can
+        assert!(!listed);
+        assert!(Checked::new(&mut raw, &root).vfd_as_dentry(&pd).is_some());
+        assert_eq!(made, (Some(1000), Some(1000), Some(0o640)));
+        let mut fs = Checked::new(&mut raw, &root);
+        assert_eq!(fs.read_to_eof(&mut fd), Some(b"hi".to_vec()));
+    }
+}
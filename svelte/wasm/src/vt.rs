@@ -0,0 +1,188 @@
+// Virtual consoles, tty1 to tty6, switched between with Alt+F1..F6 or
+// chvt, as on Linux. Each has a terminal of its own, and so its own
+// scrollback, and a shell of its own (see session.rs). They all share
+// the one VFS, the kernel log and how the terminal looks. A console is
+// made the first time it's switched to.
+
+use std::cell::Cell;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use wasm_bindgen::JsValue;
+use xterm_js_rs::addons::fit::FitAddon;
+use xterm_js_rs::Terminal;
+
+pub const CONSOLES: usize = 6;
+
+// the one on the screen
+static ACTIVE: AtomicUsize = AtomicUsize::new(1);
+
+pub fn active() -> usize {
+    ACTIVE.load(Ordering::Relaxed)
+}
+
+// what's in the page for a console
+struct Screen {
+    n: usize,
+    term: Terminal,
+    fit: FitAddon,
+    el: web_sys::HtmlElement,
+}
+
+// Not a RefCell: wasm can't unwind, so a borrow that was going on when
+// it panicked would never end, and the reboot would panic on it too.
+// The list is taken out for as long as it's looked at, and nothing but
+// the looking happens meanwhile.
+thread_local! {
+    static SCREENS: Cell<Vec<Screen>> = const { Cell::new(Vec::new()) };
+}
+
+fn screens<R>(f: impl FnOnce(&mut Vec<Screen>) -> R) -> R {
+    let mut s = SCREENS.take();
+    let r = f(&mut s);
+    SCREENS.set(s);
+    r
+}
+
+// main() hands over each console it makes
+pub fn add(n: usize, term: &Terminal, fit: FitAddon, el: web_sys::HtmlElement) {
+    let term = Terminal::from(JsValue::clone(term));
+    screens(|s| s.push(Screen { n, term, fit, el }));
+}
+
+// every console's terminal, for changes to how they all look
+pub fn terminals() -> Vec<Terminal> {
+    screens(|s| {
+        s.iter()
+            .map(|x| Terminal::from(JsValue::clone(&x.term)))
+            .collect()
+    })
+}
+
+// Runs f on the terminal on the screen, None if there isn't one. The
+// panic screen goes through here too, so it mustn't panic itself.
+pub fn with_active<R>(f: impl FnOnce(&Terminal) -> R) -> Option<R> {
+    // a copy, so the list is back before f runs
+    let term = screens(|s| {
+        s.iter()
+            .find(|x| x.n == active())
+            .map(|x| Terminal::from(JsValue::clone(&x.term)))
+    })?;
+    Some(f(&term))
+}
+
+// Fits the terminal on the screen to the page. The others are hidden,
+// and get fitted when they come back.
+pub fn fit() {
+    // fitting can call back into the console, for its new size
+    let fit = screens(|s| {
+        s.iter()
+            .find(|x| x.n == active())
+            .map(|x| FitAddon::from(JsValue::clone(&x.fit)))
+    });
+    if let Some(f) = fit {
+        f.fit();
+    }
+}
+
+// Brings console n to the screen, making it first if it has never
+// been there. False if there's no such console.
+pub fn activate(n: usize) -> bool {
+    if !(1..=CONSOLES).contains(&n) {
+        return false;
+    }
+    ACTIVE.store(n, Ordering::Relaxed);
+    #[cfg(target_arch = "wasm32")]
+    {
+        let made = screens(|s| s.iter().any(|x| x.n == n));
+        if !made {
+            if let Err(e) = crate::open_console(n) {
+                crate::log(&format!("tty{}: {:?}", n, e));
+                return false;
+            }
+        }
+        screens(|s| {
+            for x in s.iter() {
+                let show = if x.n == n { "block" } else { "none" };
+                let _ = x.el.style().set_property("display", show);
+            }
+        });
+        fit();
+        with_active(|t| t.focus());
+    }
+    true
+}
+
+// After a panic: the consoles go, with all they had. tty1 is what
+// comes back.
+pub fn reset() {
+    for x in SCREENS.take() {
+        x.term.dispose();
+        x.el.remove();
+    }
+    // if it panicked while the list was out, its consoles are still in
+    // the page, if nowhere else
+    #[cfg(target_arch = "wasm32")]
+    if let Some(el) = web_sys::window()
+        .and_then(|w| w.document())
+        .and_then(|d| d.get_element_by_id("terminal"))
+    {
+        el.set_inner_html("");
+    }
+    ACTIVE.store(1, Ordering::Relaxed);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::process::{block_on, Buffer};
+    use crate::session::Session;
+
+    #[test]
+    fn consoles() {
+        crate::vfs::mount_test_fs();
+        let _l = crate::process::shell_lock();
+        // two consoles' shells, one running while the other waits
+        let (one, two) = (Session::new(1), Session::new(2));
+        let run = |sh: &Session, cmd: &str| {
+            let out = Buffer::new();
+            block_on(crate::run_shell_instruction(sh, &out, &out, cmd));
+            out.take()
+        };
+        run(&one, "VT_T=1; cd /globd; f() { echo one; }");
+        assert_eq!(
+            run(&two, "echo $VT_T; pwd; f"),
+            "\n/\nirun: f: command not found...\n"
+        );
+        run(&two, "VT_T=2; exit");
+        assert_eq!(run(&one, "echo $VT_T; pwd; f"), "1\n/globd\none\n");
+        assert!(two.hung_up() && !one.hung_up());
+        one.tty.interrupt();
+        assert!(!two.tty.interrupted());
+        one.tty.reset();
+
+        let chvt = crate::unix::chvt::chvt;
+        let run_test = crate::process::run_test;
+        assert_eq!(run_test(chvt, &["3"], None), (0, "".into(), "".into()));
+        assert_eq!(active(), 3);
+        assert_eq!(
+            run_test(chvt, &["7"], None),
+            (1, "".into(), "chvt: 7: no such console\n".into())
+        );
+        assert_eq!(run_test(chvt, &[], None).0, 1);
+        run_test(chvt, &["1"], None);
+        assert_eq!(active(), 1);
+    }
+
+    #[test]
+    fn reset_after_panic() {
+        let _l = crate::process::shell_lock();
+        activate(4);
+        // a panic with the list out leaves it out, and nothing stuck
+        let r = std::panic::catch_unwind(|| screens::<()>(|_| panic!("oops")));
+        assert!(r.is_err());
+        assert!(terminals().is_empty());
+        assert_eq!(with_active(|_| ()), None);
+        reset();
+        assert_eq!(active(), 1);
+    }
+}
@@ -1,7 +1,14 @@
 cd "$(dirname "$0")"
 
-if ! which infsprogs; then
-	cargo install infsprogs
-fi
+# IrisOS programs are built into the stage, see ../iris-hello
+cargo build --release --target wasm32-unknown-unknown --manifest-path ../iris-hello/Cargo.toml
+install -m 755 ../iris-hello/target/wasm32-unknown-unknown/release/iris_hello.wasm stage/bin/iris-hello
 
-infsprogs build -i 64 -b 512 -n 64 -o ../../svelte/wasm/pkg/i.iar stage/
+# git doesn't keep the modes of directories, and everything is root's
+# unless it says otherwise here
+chmod 755 stage stage/*/ stage/home/guest
+chmod 1777 stage/tmp
+
+# the infsprogs next door, since --owner is newer than the published one
+cargo run --release --manifest-path ../infsprogs/Cargo.toml -- \
+	build -i 64 -b 512 -n 128 -u home/guest=1000:1000 -o ../../svelte/wasm/pkg/i.iar stage/
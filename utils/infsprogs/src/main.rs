@@ -43,6 +43,10 @@ enum Commands {
         block_size: Option<u32>,
         #[clap(short, long)]
         num_blocks: Option<u64>,
+        // PATH=UID:GID, for PATH in dir and everything in it; the rest
+        // is root's
+        #[clap(short = 'u', long = "owner")]
+        owners: Vec<String>,
     },
     Mkfs {
         #[clap(short, long)]
@@ -70,12 +74,14 @@ fn main() -> std::io::Result<()> {
             inodes,
             block_size,
             num_blocks,
+            owners,
         } => build(
             dir,
             outfile,
             inodes.unwrap_or(256),
             block_size.unwrap_or(4096),
             num_blocks.unwrap_or(1024),
+            &owners,
         ),
         Commands::Mkfs {
             inodes,
@@ -111,17 +117,19 @@ fn unpack(target: OsString, outdir: OsString) -> std::io::Result<()> {
         .unwrap_or_else(|| exito("root is not dentry"));
     _recurse_write_dentry(root, &mut fs, dent)
 }
-// TODO: copy perms
 fn build(
     dir: OsString,
     outfile: OsString,
     inodes: u32,
     block_size: u32,
     num_blocks: u64,
+    owners: &[String],
 ) -> std::io::Result<()> {
+    let dir = std::path::PathBuf::from(&dir);
+    let owners: Vec<Owner> = owners.iter().map(|o| parse_owner(&dir, o)).collect();
     let mut fs = vfs::infs::mknrfs(inodes, block_size, num_blocks);
     let root = fs.vfd_as_dentry(&fs.get_fd(1, 0).unwrap()).unwrap();
-    _recurse_read_localfs(std::path::PathBuf::from(&dir), &mut fs, root);
+    _recurse_read_localfs(dir, &mut fs, root, &owners);
     let mut file = std::fs::File::create(outfile)?;
     file.write_all(&fs.to_bytes())?;
     Ok(())
@@ -130,6 +138,27 @@ fn mkfs() -> std::io::Result<()> {
     Ok(())
 }
 
+// a path in the stage, and who it and everything in it belong to
+type Owner = (std::path::PathBuf, u32, u32);
+
+fn parse_owner(dir: &std::path::Path, o: &str) -> Owner {
+    let (path, ids) = o
+        .split_once('=')
+        .unwrap_or_else(|| exito(&format!("bad owner '{}', expected PATH=UID:GID", o)));
+    let (uid, gid) = ids
+        .split_once(':')
+        .and_then(|(u, g)| Some((u.parse().ok()?, g.parse().ok()?)))
+        .unwrap_or_else(|| exito(&format!("bad owner '{}', expected PATH=UID:GID", o)));
+    (dir.join(path.trim_matches('/')), uid, gid)
+}
+
+// gives p to whoever the last owner covering it names
+fn _own(fs: &mut vfs::infs::FileSystem, ino: u32, p: &std::path::Path, owners: &[Owner]) {
+    if let Some((_, uid, gid)) = owners.iter().rev().find(|o| p.starts_with(&o.0)) {
+        fs.chown(&fs.get_fd(ino, 0).unwrap(), *uid, *gid).unwrap();
+    }
+}
+
 fn exito(s: &str) -> ! {
     eprintln!("infsprogs: error: {}", s);
     std::process::exit(1);
@@ -179,6 +208,7 @@ fn _recurse_read_localfs(
     cwd: std::path::PathBuf,
     fs: &mut vfs::infs::FileSystem,
     mut cdent: Box<dyn vfs::VirtualDentry>,
+    owners: &[Owner],
 ) {
     let mut ents = cdent.get_entries();
     let parent_inode = cdent.get_inode();
@@ -190,8 +220,17 @@ fn _recurse_read_localfs(
         p.push(&f);
         if ft.is_dir() {
             let ino = fs.create_directory(parent_inode, f.clone()).unwrap();
+            // keep the mode, so that /tmp stays 1777
+            #[cfg(unix)]
+            {
+                use std::os::unix::fs::PermissionsExt;
+                let mode = d.metadata().unwrap().permissions().mode() & 0o7777;
+                fs.chmod(&fs.get_fd(ino, 0).unwrap(), 0x1000 | mode as u16)
+                    .unwrap();
+            }
+            _own(fs, ino, &p, owners);
             let vfd = fs.vfd_as_dentry(&fs.get_fd(ino, 0).unwrap()).unwrap();
-            _recurse_read_localfs(p, fs, vfd);
+            _recurse_read_localfs(p, fs, vfd, owners);
             ents.push(vfs::VirtualDentryEntry {
                 inum: ino,
                 filename: f,
@@ -210,8 +249,19 @@ fn _recurse_read_localfs(
                 )
                 .unwrap();
             fs.chmod(&fs.get_fd(ino, 0).unwrap(), 0o20777);
+            _own(fs, ino, &p, owners);
         } else {
-            let ino = fs.create_file(parent_inode, f, &std::fs::read(&p).unwrap());
+            let ino = fs
+                .create_file(parent_inode, f, &std::fs::read(&p).unwrap())
+                .unwrap();
+            // keep the mode, so that scripts stay executable
+            #[cfg(unix)]
+            {
+                use std::os::unix::fs::PermissionsExt;
+                let mode = d.metadata().unwrap().permissions().mode() & 0o7777;
+                fs.chmod(&fs.get_fd(ino, 0).unwrap(), mode as u16).unwrap();
+            }
+            _own(fs, ino, &p, owners);
         }
     }
 }
@@ -0,0 +1,82 @@
+// The smallest useful IrisOS program: says hello, shows its arguments
+// and who's running it, and leaves a note in /tmp. See the top of
+// svelte/wasm/src/iris.rs for the syscalls.
+#![no_std]
+
+#[link(wasm_import_module = "iris")]
+extern "C" {
+    fn write(fd: i32, ptr: *const u8, len: usize) -> i32;
+    fn open(path: *const u8, len: usize, flags: i32) -> i32;
+    fn close(fd: i32) -> i32;
+    fn argc() -> i32;
+    fn argv(i: i32, buf: *mut u8, len: usize) -> i32;
+    fn getenv(name: *const u8, name_len: usize, buf: *mut u8, len: usize) -> i32;
+    fn exit(code: i32) -> !;
+    fn time() -> f64;
+}
+
+const O_WRITE: i32 = 2;
+const O_CREATE: i32 = 4;
+const O_TRUNC: i32 = 8;
+
+fn print(fd: i32, s: &[u8]) {
+    unsafe { write(fd, s.as_ptr(), s.len()) };
+}
+
+// what fits of a call's answer; anything longer gets cut off
+fn text(buf: &mut [u8], n: i32) -> &[u8] {
+    &buf[..(n.max(0) as usize).min(buf.len())]
+}
+
+fn print_num(mut n: u64) {
+    let mut buf = [0u8; 20];
+    let mut i = buf.len();
+    loop {
+        i -= 1;
+        buf[i] = b'0' + (n % 10) as u8;
+        n /= 10;
+        if n == 0 {
+            break;
+        }
+    }
+    print(1, &buf[i..]);
+}
+
+#[no_mangle]
+pub extern "C" fn _start() {
+    let mut buf = [0u8; 256];
+    print(1, b"Hello from an IrisOS program!\n");
+
+    let n = unsafe { getenv(b"USER".as_ptr(), 4, buf.as_mut_ptr(), buf.len()) };
+    print(1, b"user: ");
+    print(1, if n < 0 { b"nobody" } else { text(&mut buf, n) });
+    print(1, b"\n");
+
+    for i in 1..unsafe { argc() } {
+        let n = unsafe { argv(i, buf.as_mut_ptr(), buf.len()) };
+        print(1, b"arg ");
+        print_num(i as u64);
+        print(1, b": ");
+        print(1, text(&mut buf, n));
+        print(1, b"\n");
+    }
+
+    let secs = unsafe { time() } as u64 / 1000;
+    print(1, b"seconds since 1970: ");
+    print_num(secs);
+    print(1, b"\n");
+
+    let path = b"/tmp/hello.txt";
+    let fd = unsafe { open(path.as_ptr(), path.len(), O_WRITE | O_CREATE | O_TRUNC) };
+    if fd < 0 {
+        print(2, b"iris-hello: couldn't open /tmp/hello.txt\n");
+        unsafe { exit(1) };
+    }
+    print(fd, b"iris-hello was here\n");
+    unsafe { close(fd) };
+}
+
+#[panic_handler]
+fn panic(_: &core::panic::PanicInfo) -> ! {
+    unsafe { exit(101) }
+}